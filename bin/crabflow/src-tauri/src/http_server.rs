@@ -18,7 +18,6 @@ use sysinfo::System;
 use serde_json::{Value, json};
 use uuid::Uuid;
 use axum::body::Body;
-use axum::response::IntoResponse as _;
 
 // Shared State Container
 #[derive(Clone)]
@@ -74,6 +73,8 @@ pub async fn start_server(user_store: UserStore, session_store: SessionStore) {
         .route("/captive", get(captive_portal_page))
         .route("/login", get(captive_portal_login_page))
         .route("/portal/login", post(portal_login_handler))
+        // Block page for domains answered in block page mode
+        .route("/blocked/request", post(unblock_request_handler))
         // Trace all requests (prints to stdout/stderr) to help debug routing
         .layer(TraceLayer::new_for_http())
         .layer(cors)
@@ -369,7 +370,7 @@ async fn tag_user_handler(
         }
     };
 
-    {
+    let groups = {
         let mut db = match state.user_store.db.lock() {
             Ok(db) => db,
             Err(e) => return Json(json!({"success": false, "error": e.to_string()})),
@@ -387,13 +388,14 @@ async fn tag_user_handler(
                 device_name: req.device_name,
             };
             user.login_history.push(record);
+            user.groups.clone()
         } else {
             return Json(json!({"success": false, "error": format!("User not found: {}", req.username)}));
         }
-    }
+    };
 
-    // Whitelist the user so DNS stops hijacking them
-    dns::authorize_ip(req.ip.clone());
+    // Whitelist the user so DNS stops hijacking them; their groups pick the DNS filtering policy
+    dns::authorize_ip(req.ip.clone(), groups);
     
     // Persist changes
     let _ = state.user_store.persist().await;
@@ -700,7 +702,7 @@ async fn portal_login_handler(
             }
 
             // Authorize this IP for internet access
-            dns::authorize_ip(client_ip.clone());
+            dns::authorize_ip(client_ip.clone(), user.groups.clone());
             
             logging::log_info(&format!("Portal login successful for {} from IP {}", req.username, client_ip));
             
@@ -717,8 +719,96 @@ async fn portal_login_handler(
     }))
 }
 
-// Fallback handler to log unmatched requests (helps debug 404s from browser)
-async fn fallback_handler(req: axum::http::Request<Body>) -> Response {
+#[derive(serde::Deserialize)]
+struct UnblockRequestBody {
+    domain: String,
+    #[serde(default)]
+    reason: String,
+}
+
+/// Unblock request submitted from the block page
+async fn unblock_request_handler(
+    axum::extract::ConnectInfo(addr): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    Json(req): Json<UnblockRequestBody>,
+) -> Json<Value> {
+    match dns::blocklist::request_unblock(&req.domain, &addr.ip().to_string(), &req.reason) {
+        Ok(()) => Json(json!({"success": true, "message": "Your request has been sent to the network administrator."})),
+        Err(e) => Json(json!({"success": false, "message": e})),
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Page shown when a blocked domain resolved to us (block page mode)
+fn blocked_page_html(domain: &str, source: &str) -> String {
+    format!(r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Blocked - CrabFlow</title>
+    <style>
+        body {{ font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; background: #f4f6f9; display: flex; align-items: center; justify-content: center; min-height: 100vh; margin: 0; }}
+        .card {{ background: #fff; max-width: 460px; width: 90%; padding: 2rem; border-radius: 8px; box-shadow: 0 2px 8px rgba(0,0,0,0.1); }}
+        h1 {{ font-size: 1.4rem; color: #c0392b; margin-top: 0; }}
+        code {{ background: #f1f1f1; padding: 2px 6px; border-radius: 4px; word-break: break-all; }}
+        textarea {{ width: 100%; box-sizing: border-box; margin: 0.5rem 0; min-height: 70px; }}
+        button {{ background: #3c8dbc; color: #fff; border: 0; padding: 0.6rem 1.2rem; border-radius: 4px; cursor: pointer; }}
+        #result {{ margin-top: 0.8rem; }}
+    </style>
+</head>
+<body>
+    <div class="card">
+        <h1>This site is blocked</h1>
+        <p><code>{domain}</code> is blocked on this network by <strong>{source}</strong>.</p>
+        <p>If you think this is a mistake, ask the administrator to unblock it.</p>
+        <textarea id="reason" placeholder="Why do you need this site? (optional)"></textarea>
+        <button id="send" onclick="requestUnblock()">Request unblock</button>
+        <div id="result"></div>
+    </div>
+    <script>
+        async function requestUnblock() {{
+            const button = document.getElementById('send');
+            button.disabled = true;
+            try {{
+                const res = await fetch('/blocked/request', {{
+                    method: 'POST',
+                    headers: {{ 'Content-Type': 'application/json' }},
+                    body: JSON.stringify({{ domain: '{domain}', reason: document.getElementById('reason').value }})
+                }});
+                const data = await res.json();
+                document.getElementById('result').textContent = data.message;
+                button.disabled = data.success;
+            }} catch (e) {{
+                document.getElementById('result').textContent = 'Request failed: ' + e;
+                button.disabled = false;
+            }}
+        }}
+    </script>
+</body>
+</html>"#, domain = domain, source = html_escape(source))
+}
+
+/// Blocked domain a request was addressed to, with the list that blocks it for this client
+fn blocked_host(req: &axum::http::Request<Body>, client_ip: &str) -> Option<(String, String)> {
+    let host = req.headers().get(axum::http::header::HOST)?.to_str().ok()?;
+    let host = host.rsplit_once(':').map_or(host, |(h, port)| if port.chars().all(|c| c.is_ascii_digit()) { h } else { host });
+    let domain = dns::blocklist::normalize_domain(host)?;
+    let policy = dns::policy::for_client(client_ip);
+    let blocked = dns::blocklist::blocked_by(&domain, policy.as_deref())?;
+    Some((domain, blocked.source))
+}
+
+// Fallback handler: block page for blocked hosts, otherwise log unmatched requests (helps debug 404s from browser)
+async fn fallback_handler(
+    axum::extract::ConnectInfo(addr): axum::extract::ConnectInfo<std::net::SocketAddr>,
+    req: axum::http::Request<Body>,
+) -> Response {
+    if let Some((domain, source)) = blocked_host(&req, &addr.ip().to_string()) {
+        return (StatusCode::FORBIDDEN, Html(blocked_page_html(&domain, &source))).into_response();
+    }
     let method = req.method().clone();
     let uri = req.uri().to_string();
     let msg = format!("Fallback route hit: {} {}", method, uri);
@@ -33,11 +33,45 @@ pub fn run() {
             network::client::update_record,
             network::client::remove_record,
             network::client::update_upstream_interface,
-            network::dns::get_query_logs,
+            network::dns::querylog::get_query_logs,
             network::dns::get_blacklist,
             network::dns::block_domain,
             network::dns::unblock_domain,
             network::dns::import_blacklist,
+            network::dns::dhcp_names::list_dhcp_names,
+            network::dns::zones::list_zones,
+            network::dns::zones::save_zone,
+            network::dns::zones::remove_zone,
+            network::dns::zones::import_zone_file,
+            network::dns::zones::export_zone_file,
+            network::dns::cache::get_dns_cache_stats,
+            network::dns::cache::list_dns_cache,
+            network::dns::cache::flush_dns_cache,
+            network::dns::upstream::get_upstream_status,
+            network::dns::upstream::test_upstream,
+            network::dns::ratelimit::list_dns_bans,
+            network::dns::ratelimit::unban_dns_client,
+            network::dns::blocklist::list_blocklists,
+            network::dns::blocklist::save_blocklist,
+            network::dns::blocklist::remove_blocklist,
+            network::dns::blocklist::refresh_blocklist,
+            network::dns::blocklist::get_allowlist,
+            network::dns::blocklist::allow_domain,
+            network::dns::blocklist::disallow_domain,
+            network::dns::blocklist::check_domain_blocked,
+            network::dns::blocklist::list_unblock_requests,
+            network::dns::blocklist::resolve_unblock_request,
+            network::dns::policy::list_dns_policies,
+            network::dns::policy::save_dns_policy,
+            network::dns::policy::remove_dns_policy,
+            network::dns::policy::reorder_dns_policies,
+            network::dns::policy::get_client_policy,
+
+            // Network DHCP
+            network::dhcp::hooks::list_hook_deliveries,
+            network::dhcp::lease_formats::preview_lease_import,
+            network::dhcp::lease_formats::import_leases,
+            network::dhcp::lease_formats::export_leases,
             network::client::log_action,
             network::client::fetch_config,
             network::client::save_config,
@@ -3,6 +3,7 @@
 
 use serde::{Deserialize, Serialize};
 use crate::sysmodules::{paths, logging};
+use crate::network::dhcp;
 use std::fs;
 use std::sync::RwLock;
 use lazy_static::lazy_static;
@@ -62,7 +63,7 @@ impl Default for CaptivePortalConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteRule {
     pub name: String,
-    pub source: String,       // IP/CIDR or "*"
+    pub source: String,       // IP/CIDR, "circuit-id:<id>", "remote-id:<id>" or "*"
     pub destination: String,  // IP/CIDR or "*"
     pub port: String,         // Port number, range, or "*"
     pub protocol: String,     // "any", "tcp", "udp", "icmp"
@@ -101,7 +102,7 @@ pub struct ForwardingRule {
     pub masquerade: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct DataflowConfig {
     pub bandwidth: BandwidthLimits,
     pub qos_enabled: bool,
@@ -110,18 +111,7 @@ pub struct DataflowConfig {
     pub group_limits: Vec<GroupBandwidthLimit>,
 }
 
-impl Default for DataflowConfig {
-    fn default() -> Self {
-        Self {
-            bandwidth: BandwidthLimits::default(),
-            qos_enabled: false,
-            qos_rules: vec![],
-            group_limits: vec![],
-        }
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct BandwidthLimits {
     #[serde(rename = "globalUpload")]
     pub global_upload: u64,
@@ -134,18 +124,6 @@ pub struct BandwidthLimits {
     pub enabled: bool,
 }
 
-impl Default for BandwidthLimits {
-    fn default() -> Self {
-        Self {
-            global_upload: 0,
-            global_download: 0,
-            per_client_upload: 0,
-            per_client_download: 0,
-            enabled: false,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GroupBandwidthLimit {
     pub group_name: String,
@@ -315,7 +293,7 @@ pub fn get_forwarding_interfaces() -> (String, String) {
 pub fn evaluate_route(src: &str, dst: &str, port: u16, protocol: &str) -> Option<String> {
     if let Ok(cache) = ACL_CONFIG.read() {
         let mut rules = cache.routes.clone();
-        rules.sort_by(|a, b| a.priority.cmp(&b.priority));
+        rules.sort_by_key(|a| a.priority);
         
         for rule in rules {
             if matches_rule(&rule, src, dst, port, protocol) {
@@ -334,7 +312,7 @@ fn matches_rule(rule: &RouteRule, src: &str, dst: &str, port: u16, protocol: &st
     }
     
     // Check source
-    if rule.source != "*" && !matches_ip_pattern(&rule.source, src) {
+    if rule.source != "*" && !matches_source_pattern(&rule.source, src) {
         return false;
     }
     
@@ -351,6 +329,21 @@ fn matches_rule(rule: &RouteRule, src: &str, dst: &str, port: u16, protocol: &st
     true
 }
 
+fn matches_source_pattern(pattern: &str, src: &str) -> bool {
+    // Clients behind a DHCP relay can be matched by the option 82 IDs on their lease
+    if let Some(id) = pattern.strip_prefix("circuit-id:") {
+        return dhcp::get_relay_info_from_ip(src)
+            .and_then(|r| r.circuit_id)
+            .is_some_and(|c| c.eq_ignore_ascii_case(id.trim()));
+    }
+    if let Some(id) = pattern.strip_prefix("remote-id:") {
+        return dhcp::get_relay_info_from_ip(src)
+            .and_then(|r| r.remote_id)
+            .is_some_and(|r| r.eq_ignore_ascii_case(id.trim()));
+    }
+    matches_ip_pattern(pattern, src)
+}
+
 fn matches_ip_pattern(pattern: &str, ip: &str) -> bool {
     if pattern == "*" {
         return true;
@@ -7,13 +7,28 @@ use crate::network::dns;
 use tauri::{State, AppHandle};
 use chrono::Utc;
 use std::fs;
-// use std::process::Command;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether LAN HTTP is currently redirected to the built-in web server
+static REDIRECT_ACTIVE: AtomicBool = AtomicBool::new(false);
 
 fn get_portal_path() -> std::path::PathBuf {
     paths::get_config_path("portal.html")
 }
 
-pub fn apply_portal_rules(_enabled: bool, _config: &SetupConfig) {
+/// The captive portal and the DNS block page are both served on port 3030, so LAN HTTP is
+/// redirected there while either of them is in use, and the rules are removed once neither is
+pub fn sync_http_redirect(config: &SetupConfig) {
+    let wanted = config.dhcp.captive_portal || dns::blocklist::block_page_in_use(&config.dns);
+    if REDIRECT_ACTIVE.swap(wanted, Ordering::SeqCst) != wanted {
+        apply_portal_rules(wanted, config);
+    }
+}
+
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn apply_portal_rules(enabled: bool, config: &SetupConfig) {
     #[cfg(target_os = "linux")]
     {
         // Assume eth0 is LAN for now based on user context
@@ -84,11 +99,11 @@ pub fn save_portal_template(content: String) -> Result<(), String> {
 #[tauri::command]
 pub fn set_captive_portal(enabled: bool) -> Result<(), String> {
     let mut config: SetupConfig = fetch::fetch_setup().map_err(|e| e.to_string())?;
-    
-    // Apply (or remove) rules based on the new state
-    apply_portal_rules(enabled, &config);
-    
     config.dhcp.captive_portal = enabled;
+
+    // Apply (or remove) rules based on the new state; the block page may still need them
+    sync_http_redirect(&config);
+
     post::post_setup(config).map_err(|e| e.to_string())?;
     Ok(())
 }
@@ -109,7 +124,7 @@ pub async fn tag_user(
     ip: String,
     device_name: Option<String>
 ) -> Result<(), String> {
-    {
+    let groups = {
         let mut db = user_store.db.lock().map_err(|e| e.to_string())?;
         
         // ... (MAC lookup logic stays same)
@@ -136,15 +151,16 @@ pub async fn tag_user(
             };
             
             user.login_history.push(record);
+            user.groups.clone()
         } else {
             let err = format!("User not found: {}", username);
             notify::send_notification(&app, "Authentication Failed", &err, "error");
             return Err(err);
         }
-    }
+    };
     
-    // Whitelist the user so DNS stops hijacking them
-    dns::authorize_ip(ip.clone());
+    // Whitelist the user so DNS stops hijacking them; their groups pick the DNS filtering policy
+    dns::authorize_ip(ip.clone(), groups);
 
     notify::send_notification(&app, "User Authenticated", &format!("User: {}\nIP: {}", username, ip), "success");
     user_store.persist().await.map_err(|e| e.to_string())
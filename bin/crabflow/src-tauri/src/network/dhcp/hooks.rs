@@ -0,0 +1,363 @@
+// src-tauri/src/network/dhcp/hooks.rs
+// Lease event hooks: run a local script (like dnsmasq's --dhcp-script) or POST JSON to a webhook
+
+use serde::{Serialize, Deserialize};
+use crate::sysmodules::{fetch, logging};
+use super::Lease;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::process::Command;
+use std::sync::{Mutex, RwLock, mpsc};
+use std::thread;
+use std::time::Duration;
+use lazy_static::lazy_static;
+
+/// Deliveries kept for `list_hook_deliveries`
+const DELIVERY_LOG_SIZE: usize = 500;
+
+/// Events waiting for the dispatcher, and for each hook's worker; beyond that they are dropped
+const QUEUE_SIZE: usize = 256;
+
+/// Scripts that run longer than this are killed
+const SCRIPT_TIMEOUT_SECS: u64 = 30;
+const WEBHOOK_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+#[allow(clippy::enum_variant_names)] // The variant names are the wire names
+pub enum LeaseEvent {
+    LeaseGranted,
+    LeaseRenewed,
+    LeaseReleased,
+    LeaseExpired,
+    LeaseDeclined,
+}
+
+impl LeaseEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeaseEvent::LeaseGranted => "lease-granted",
+            LeaseEvent::LeaseRenewed => "lease-renewed",
+            LeaseEvent::LeaseReleased => "lease-released",
+            LeaseEvent::LeaseExpired => "lease-expired",
+            LeaseEvent::LeaseDeclined => "lease-declined",
+        }
+    }
+}
+
+/// What a hook does when it fires
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum HookAction {
+    /// Run `command <event> <mac> <ip> <hostname>` with the lease in CRABFLOW_* environment variables
+    Script { command: String },
+    /// POST the lease as JSON
+    Webhook {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LeaseHook {
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub events: Vec<LeaseEvent>, // Empty = every event
+    pub action: HookAction,
+    #[serde(default = "default_retries")]
+    pub retries: u32, // Extra attempts after a failure, with exponential backoff
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+/// JSON body of a webhook call
+#[derive(Serialize, Clone, Debug)]
+struct HookPayload<'a> {
+    event: LeaseEvent,
+    timestamp: i64,
+    lease: &'a Lease,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HookDelivery {
+    pub timestamp: i64,
+    pub hook: String,
+    pub event: LeaseEvent,
+    pub ip: String,
+    pub mac: String,
+    pub attempts: u32,
+    pub success: bool,
+    pub detail: String, // HTTP status, exit code or error of the last attempt
+}
+
+type HookJob = (LeaseHook, LeaseEvent, Lease);
+
+lazy_static! {
+    // The DHCP listener only queues events. A dispatcher hands them to one worker per hook,
+    // so a slow or failing hook (retries included) never holds up the others.
+    static ref HOOK_QUEUE: Mutex<mpsc::SyncSender<(LeaseEvent, Lease)>> = Mutex::new(spawn_dispatcher());
+    static ref HOOK_WORKERS: Mutex<HashMap<String, mpsc::SyncSender<HookJob>>> = Mutex::new(HashMap::new());
+    static ref DELIVERIES: RwLock<VecDeque<HookDelivery>> = RwLock::new(VecDeque::new());
+}
+
+/// Queue a lease event for every hook subscribed to it
+pub fn fire(event: LeaseEvent, lease: &Lease) {
+    match HOOK_QUEUE.lock().unwrap().try_send((event, lease.clone())) {
+        Ok(()) => {}
+        Err(mpsc::TrySendError::Full(_)) => logging::log_warn(&format!("DHCP hook queue is full, dropping {} for {}", event.as_str(), lease.ip)),
+        Err(e) => logging::log_error(&format!("Failed to queue DHCP hook event: {}", e)),
+    }
+}
+
+fn spawn_dispatcher() -> mpsc::SyncSender<(LeaseEvent, Lease)> {
+    let (tx, rx) = mpsc::sync_channel::<(LeaseEvent, Lease)>(QUEUE_SIZE);
+    thread::spawn(move || {
+        for (event, lease) in rx {
+            let hooks = match fetch::fetch_setup() {
+                Ok(cfg) => cfg.dhcp.hooks,
+                Err(_) => continue,
+            };
+            for hook in hooks.into_iter().filter(|h| subscribed(h, event)) {
+                enqueue(hook, event, lease.clone());
+            }
+        }
+    });
+    tx
+}
+
+/// Whether a hook fires for an event; no events listed means every event
+fn subscribed(hook: &LeaseHook, event: LeaseEvent) -> bool {
+    hook.enabled && (hook.events.is_empty() || hook.events.contains(&event))
+}
+
+/// Hand an event to the hook's own worker, starting it on first use
+fn enqueue(hook: LeaseHook, event: LeaseEvent, lease: Lease) {
+    let mut workers = HOOK_WORKERS.lock().unwrap();
+    let name = hook.name.clone();
+    let worker = workers.entry(name.clone()).or_insert_with(spawn_worker);
+    match worker.try_send((hook, event, lease)) {
+        Ok(()) => {}
+        Err(mpsc::TrySendError::Full((hook, event, lease))) => {
+            logging::log_warn(&format!("DHCP hook '{}' is falling behind, dropping {} for {}", hook.name, event.as_str(), lease.ip));
+            record(&hook, event, &lease, 0, Err("dropped: queue full".to_string()));
+        }
+        // The worker died (a panic in delivery); start a new one
+        Err(mpsc::TrySendError::Disconnected(job)) => {
+            let worker = spawn_worker();
+            let _ = worker.try_send(job);
+            workers.insert(name, worker);
+        }
+    }
+}
+
+fn spawn_worker() -> mpsc::SyncSender<HookJob> {
+    let (tx, rx) = mpsc::sync_channel::<HookJob>(QUEUE_SIZE);
+    thread::spawn(move || {
+        for (hook, event, lease) in rx {
+            deliver(&hook, event, &lease, thread::sleep);
+        }
+    });
+    tx
+}
+
+/// Wait before retry number `attempt` (1-based): 1s, 2s, 4s, ... capped at 64s
+fn retry_delay(attempt: u32) -> Duration {
+    Duration::from_secs(1 << (attempt - 1).min(6))
+}
+
+fn deliver(hook: &LeaseHook, event: LeaseEvent, lease: &Lease, mut sleep: impl FnMut(Duration)) {
+    let mut attempts = 0;
+    let result = loop {
+        attempts += 1;
+        let result = match &hook.action {
+            HookAction::Script { command } => run_script(command, event, lease),
+            HookAction::Webhook { url, headers } => post_webhook(url, headers, event, lease),
+        };
+        if result.is_ok() || attempts > hook.retries {
+            break result;
+        }
+        sleep(retry_delay(attempts));
+    };
+    record(hook, event, lease, attempts, result);
+}
+
+/// Log a delivery and keep it for `list_hook_deliveries`
+fn record(hook: &LeaseHook, event: LeaseEvent, lease: &Lease, attempts: u32, result: Result<String, String>) {
+    let (success, detail) = match result {
+        Ok(d) => (true, d),
+        Err(e) => (false, e),
+    };
+    if success {
+        logging::log_debug(&format!("DHCP hook '{}' delivered {} for {} ({})", hook.name, event.as_str(), lease.ip, detail));
+    } else {
+        logging::log_warn(&format!("DHCP hook '{}' failed {} for {} after {} attempts: {}", hook.name, event.as_str(), lease.ip, attempts, detail));
+    }
+
+    let mut log = DELIVERIES.write().unwrap();
+    if log.len() >= DELIVERY_LOG_SIZE {
+        log.pop_front();
+    }
+    log.push_back(HookDelivery {
+        timestamp: chrono::Utc::now().timestamp(),
+        hook: hook.name.clone(),
+        event,
+        ip: lease.ip.clone(),
+        mac: lease.mac.clone(),
+        attempts,
+        success,
+        detail,
+    });
+}
+
+fn run_script(command: &str, event: LeaseEvent, lease: &Lease) -> Result<String, String> {
+    let relay = lease.relay_info.clone().unwrap_or_default();
+    let mut child = Command::new(command)
+        .args([event.as_str(), &lease.mac, &lease.ip, &lease.hostname])
+        .env("CRABFLOW_EVENT", event.as_str())
+        .env("CRABFLOW_IP", &lease.ip)
+        .env("CRABFLOW_MAC", &lease.mac)
+        .env("CRABFLOW_HOSTNAME", &lease.hostname)
+        .env("CRABFLOW_EXPIRES_AT", lease.expires_at.map(|t| t.to_string()).unwrap_or_default())
+        .env("CRABFLOW_STATIC", if lease.static_lease { "1" } else { "0" })
+        .env("CRABFLOW_CIRCUIT_ID", relay.circuit_id.unwrap_or_default())
+        .env("CRABFLOW_REMOTE_ID", relay.remote_id.unwrap_or_default())
+        .spawn()
+        .map_err(|e| format!("failed to start: {}", e))?;
+
+    for _ in 0..SCRIPT_TIMEOUT_SECS * 10 {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => return Ok("exit 0".to_string()),
+            Ok(Some(status)) => return Err(format!("exited with {}", status)),
+            Ok(None) => thread::sleep(Duration::from_millis(100)),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    Err(format!("timed out after {}s", SCRIPT_TIMEOUT_SECS))
+}
+
+fn post_webhook(url: &str, headers: &BTreeMap<String, String>, event: LeaseEvent, lease: &Lease) -> Result<String, String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(WEBHOOK_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let payload = HookPayload { event, timestamp: chrono::Utc::now().timestamp(), lease };
+
+    let mut request = client.post(url).json(&payload);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    let response = request.send().map_err(|e| e.to_string())?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(format!("HTTP {}", status.as_u16()))
+    } else {
+        Err(format!("HTTP {}", status.as_u16()))
+    }
+}
+
+/// Most recent hook deliveries, newest first
+#[tauri::command]
+pub fn list_hook_deliveries() -> Vec<HookDelivery> {
+    DELIVERIES.read().unwrap().iter().rev().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn hook(name: &str, action: HookAction, events: Vec<LeaseEvent>, retries: u32) -> LeaseHook {
+        LeaseHook { name: name.into(), enabled: true, events, action, retries }
+    }
+
+    fn lease() -> Lease {
+        Lease {
+            ip: "10.0.0.120".into(),
+            mac: "aa:bb:cc:dd:ee:ff".into(),
+            hostname: "laptop".into(),
+            expires_at: Some(1700000000),
+            static_lease: false,
+            state: crate::network::dhcp::LeaseState::Active,
+            options: BTreeMap::new(),
+            relay_info: None,
+        }
+    }
+
+    fn deliveries_of(name: &str) -> Vec<HookDelivery> {
+        list_hook_deliveries().into_iter().filter(|d| d.hook == name).collect()
+    }
+
+    /// Answer `count` HTTP requests with `status`
+    fn webhook_stub(status: &'static str, count: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_event_filter() {
+        let script = HookAction::Script { command: "true".into() };
+        let all = hook("all", script.clone(), vec![], 0);
+        assert!(subscribed(&all, LeaseEvent::LeaseGranted) && subscribed(&all, LeaseEvent::LeaseDeclined));
+
+        let some = hook("some", script, vec![LeaseEvent::LeaseReleased, LeaseEvent::LeaseExpired], 0);
+        assert!(subscribed(&some, LeaseEvent::LeaseExpired));
+        assert!(!subscribed(&some, LeaseEvent::LeaseGranted));
+        assert!(!subscribed(&LeaseHook { enabled: false, ..all }, LeaseEvent::LeaseGranted));
+    }
+
+    #[test]
+    fn test_retries_and_deliveries() {
+        assert_eq!(retry_delay(1), Duration::from_secs(1));
+        assert_eq!(retry_delay(3), Duration::from_secs(4));
+        assert_eq!(retry_delay(20), Duration::from_secs(64));
+
+        let mut waits = Vec::new();
+        let failing = hook("test-failing-webhook", HookAction::Webhook { url: webhook_stub("500 Internal Server Error", 3), headers: BTreeMap::new() }, vec![], 2);
+        deliver(&failing, LeaseEvent::LeaseGranted, &lease(), |d| waits.push(d));
+        assert_eq!(waits, vec![Duration::from_secs(1), Duration::from_secs(2)]);
+        let failed = deliveries_of("test-failing-webhook");
+        assert_eq!(failed.len(), 1);
+        assert!(!failed[0].success);
+        assert_eq!((failed[0].attempts, failed[0].detail.as_str()), (3, "HTTP 500"));
+
+        let mut waits = Vec::new();
+        let working = hook("test-working-webhook", HookAction::Webhook { url: webhook_stub("204 No Content", 1), headers: BTreeMap::new() }, vec![], 2);
+        deliver(&working, LeaseEvent::LeaseRenewed, &lease(), |d| waits.push(d));
+        assert!(waits.is_empty());
+        let delivered = deliveries_of("test-working-webhook");
+        assert!(delivered[0].success);
+        assert_eq!((delivered[0].attempts, delivered[0].detail.as_str()), (1, "HTTP 204"));
+        assert_eq!((delivered[0].event, delivered[0].ip.as_str(), delivered[0].mac.as_str()), (LeaseEvent::LeaseRenewed, "10.0.0.120", "aa:bb:cc:dd:ee:ff"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failing_script() {
+        let mut waits = Vec::new();
+        deliver(&hook("test-failing-script", HookAction::Script { command: "false".into() }, vec![], 1), LeaseEvent::LeaseExpired, &lease(), |d| waits.push(d));
+        assert_eq!(waits.len(), 1);
+        let failed = deliveries_of("test-failing-script");
+        assert!(!failed[0].success && failed[0].attempts == 2);
+        assert!(failed[0].detail.starts_with("exited with"));
+    }
+}
@@ -0,0 +1,218 @@
+// src-tauri/src/network/dhcp/ingress.rs
+// The DHCP server listens on one wildcard socket. A socket bound to a unicast address never sees
+// broadcast DISCOVERs, so on Linux IP_PKTINFO tells which interface a request arrived on, and
+// replies are sent back out of that interface.
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The interface a request arrived on
+#[derive(Clone, Debug)]
+pub struct Ingress {
+    pub ifindex: u32,
+    pub interface: String,
+    pub addresses: Vec<Ipv4Addr>, // IPv4 addresses of the interface
+}
+
+impl Ingress {
+    fn lookup(ifindex: u32) -> Option<Self> {
+        let interface = interface_name(ifindex)?;
+        let addresses = get_if_addrs::get_if_addrs().unwrap_or_default().into_iter()
+            .filter(|i| i.name == interface)
+            .filter_map(|i| match i.addr.ip() {
+                std::net::IpAddr::V4(ip) => Some(ip),
+                _ => None,
+            })
+            .collect();
+        Some(Self { ifindex, interface, addresses })
+    }
+
+    /// Whether a scope bound to `bind_address` (an interface name or one of its addresses) lives on this interface
+    pub fn serves(&self, bind_address: &str) -> bool {
+        let bind = bind_address.trim();
+        bind == self.interface || bind.parse::<Ipv4Addr>().is_ok_and(|ip| self.addresses.contains(&ip))
+    }
+}
+
+pub struct DhcpSocket {
+    socket: UdpSocket,
+}
+
+impl DhcpSocket {
+    /// Bind 0.0.0.0:67 for every interface
+    pub fn bind() -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:67")?;
+        socket.set_broadcast(true)?;
+        // Wake up every second to notice when the server is stopped
+        socket.set_read_timeout(Some(Duration::from_secs(1)))?;
+        enable_pktinfo(&socket)?;
+        Ok(Self { socket })
+    }
+
+    /// Receive a request, with the interface it came in on where the platform can tell
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Option<Ingress>)> {
+        let (amt, src, ifindex) = recv_with_ifindex(&self.socket, buf)?;
+        Ok((amt, src, ifindex.and_then(Ingress::lookup)))
+    }
+}
+
+/// Where replies to one request go out: the listener socket, pinned to the request's interface
+#[derive(Clone)]
+pub struct Responder {
+    socket: Arc<DhcpSocket>,
+    pub ingress: Option<Ingress>,
+}
+
+impl Responder {
+    pub fn new(socket: Arc<DhcpSocket>, ingress: Option<Ingress>) -> Self {
+        Self { socket, ingress }
+    }
+
+    pub fn send_to(&self, packet: &[u8], dest: SocketAddrV4) -> io::Result<usize> {
+        match &self.ingress {
+            Some(ingress) => send_via(&self.socket.socket, packet, dest, ingress.ifindex),
+            None => self.socket.socket.send_to(packet, dest),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn enable_pktinfo(socket: &UdpSocket) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+    let on: libc::c_int = 1;
+    let rc = unsafe {
+        libc::setsockopt(socket.as_raw_fd(), libc::IPPROTO_IP, libc::IP_PKTINFO, &on as *const _ as *const libc::c_void, std::mem::size_of::<libc::c_int>() as libc::socklen_t)
+    };
+    if rc == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+#[cfg(target_os = "linux")]
+fn recv_with_ifindex(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Option<u32>)> {
+    use std::os::fd::AsRawFd;
+    let mut src: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    let mut iov = libc::iovec { iov_base: buf.as_mut_ptr() as *mut libc::c_void, iov_len: buf.len() };
+    let mut control = [0u64; 8]; // u64 for cmsghdr alignment
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_name = &mut src as *mut _ as *mut libc::c_void;
+    msg.msg_namelen = std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&control) as _;
+
+    let amt = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, 0) };
+    if amt < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut ifindex = None;
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::IPPROTO_IP && (*cmsg).cmsg_type == libc::IP_PKTINFO {
+                let info = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::in_pktinfo);
+                ifindex = Some(info.ipi_ifindex as u32);
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    let src = SocketAddrV4::new(Ipv4Addr::from(u32::from_be(src.sin_addr.s_addr)), u16::from_be(src.sin_port));
+    Ok((amt as usize, SocketAddr::V4(src), ifindex))
+}
+
+/// sendmsg with IP_PKTINFO, so a broadcast leaves through the interface the request came in on
+/// rather than whichever one the routing table picks
+#[cfg(target_os = "linux")]
+fn send_via(socket: &UdpSocket, packet: &[u8], dest: SocketAddrV4, ifindex: u32) -> io::Result<usize> {
+    use std::os::fd::AsRawFd;
+    let mut addr: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    addr.sin_family = libc::AF_INET as libc::sa_family_t;
+    addr.sin_port = dest.port().to_be();
+    addr.sin_addr.s_addr = u32::from(*dest.ip()).to_be();
+    let mut iov = libc::iovec { iov_base: packet.as_ptr() as *mut libc::c_void, iov_len: packet.len() };
+    let mut control = [0u64; 8];
+    let space = unsafe { libc::CMSG_SPACE(std::mem::size_of::<libc::in_pktinfo>() as u32) } as usize;
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_name = &mut addr as *mut _ as *mut libc::c_void;
+    msg.msg_namelen = std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = space as _;
+
+    let sent = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::IPPROTO_IP;
+        (*cmsg).cmsg_type = libc::IP_PKTINFO;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(std::mem::size_of::<libc::in_pktinfo>() as u32) as _;
+        // A zero source address lets the kernel use the interface's own
+        let info = libc::in_pktinfo {
+            ipi_ifindex: ifindex as libc::c_int,
+            ipi_spec_dst: libc::in_addr { s_addr: 0 },
+            ipi_addr: libc::in_addr { s_addr: 0 },
+        };
+        std::ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut libc::in_pktinfo, info);
+        libc::sendmsg(socket.as_raw_fd(), &msg, 0)
+    };
+    if sent < 0 { Err(io::Error::last_os_error()) } else { Ok(sent as usize) }
+}
+
+#[cfg(target_os = "linux")]
+fn interface_name(ifindex: u32) -> Option<String> {
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+    let found = unsafe { libc::if_indextoname(ifindex, name.as_mut_ptr()) };
+    if found.is_null() {
+        return None;
+    }
+    unsafe { std::ffi::CStr::from_ptr(name.as_ptr()) }.to_str().ok().map(str::to_string)
+}
+
+// Elsewhere the interface is unknown: non-relayed requests fall back to the first scope
+
+#[cfg(not(target_os = "linux"))]
+fn enable_pktinfo(_socket: &UdpSocket) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn recv_with_ifindex(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Option<u32>)> {
+    socket.recv_from(buf).map(|(amt, src)| (amt, src, None))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_via(socket: &UdpSocket, packet: &[u8], dest: SocketAddrV4, _ifindex: u32) -> io::Result<usize> {
+    socket.send_to(packet, dest)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn interface_name(_ifindex: u32) -> Option<String> {
+    None
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pktinfo_roundtrip() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        enable_pktinfo(&receiver).unwrap();
+        let dest = match receiver.local_addr().unwrap() {
+            SocketAddr::V4(addr) => addr,
+            _ => unreachable!(),
+        };
+        let lo = Ingress::lookup(1).unwrap(); // Loopback is always interface 1
+        assert_eq!(lo.interface, "lo");
+        assert!(lo.serves("lo") && lo.serves("127.0.0.1") && !lo.serves("10.0.0.1"));
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert_eq!(send_via(&sender, b"hello", dest, lo.ifindex).unwrap(), 5);
+        let mut buf = [0u8; 16];
+        let (amt, src, ifindex) = recv_with_ifindex(&receiver, &mut buf).unwrap();
+        assert_eq!(&buf[..amt], b"hello");
+        assert_eq!(src, sender.local_addr().unwrap());
+        assert_eq!(ifindex, Some(1));
+    }
+}
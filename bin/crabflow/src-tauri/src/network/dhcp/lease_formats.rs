@@ -0,0 +1,575 @@
+// src-tauri/src/network/dhcp/lease_formats.rs
+// Import/export of leases in ISC dhcpd, dnsmasq and Kea formats, for migrating sites onto CrabFlow
+
+use serde::{Serialize, Deserialize};
+use crate::sysmodules::logging;
+use super::{Lease, LeaseState, LeaseTable, LEASE_TABLE, now_secs};
+use chrono::{Datelike, NaiveDateTime, TimeZone, Utc};
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+/// Kea marks infinite leases with an all-ones lifetime
+const KEA_INFINITE: u64 = u32::MAX as u64;
+
+/// Kea has no reservations in lease4.csv; ours carry this user context so they come back as reservations
+const KEA_STATIC_CONTEXT: &str = r#"{"crabflow-static":true}"#;
+
+const KEA_HEADER: &str = "address,hwaddr,client_id,valid_lifetime,expire,subnet_id,fqdn_fwd,fqdn_rev,hostname,state,user_context";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LeaseFormat {
+    /// ISC dhcpd: `lease { }` blocks from dhcpd.leases plus `host { }` reservations from dhcpd.conf
+    Isc,
+    /// dnsmasq --dhcp-hostsfile lines: `mac,ip,hostname[,lease time]` (reservations)
+    DnsmasqHosts,
+    /// dnsmasq.leases lines: `expiry mac ip hostname client-id` (dynamic leases)
+    DnsmasqLeases,
+    /// Kea memfile lease4.csv
+    KeaCsv,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LeaseConflict {
+    pub imported: Lease,
+    pub existing: Lease,
+    pub reason: String,
+}
+
+/// What an import would do, without touching the lease table
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct LeaseImportPreview {
+    pub to_add: Vec<Lease>,
+    pub conflicts: Vec<LeaseConflict>,
+    pub unchanged: usize,
+    pub skipped: usize, // Expired or inactive entries in the source
+    pub errors: Vec<String>,
+}
+
+/// Entries parsed from a lease file; `skipped` counts entries that are not worth importing
+#[derive(Default)]
+struct Parsed {
+    leases: Vec<Lease>,
+    skipped: usize,
+    errors: Vec<String>,
+}
+
+fn new_lease(ip: String, mac: String, hostname: String, expires_at: Option<u64>, static_lease: bool) -> Lease {
+    Lease {
+        ip,
+        mac,
+        hostname,
+        expires_at,
+        static_lease,
+        state: LeaseState::Active,
+        options: BTreeMap::new(),
+        relay_info: None,
+    }
+}
+
+fn normalize_mac(raw: &str) -> Option<String> {
+    let parts: Vec<&str> = raw.trim().split([':', '-']).collect();
+    if parts.len() != 6 || !parts.iter().all(|p| p.len() <= 2 && u8::from_str_radix(p, 16).is_ok()) {
+        return None;
+    }
+    Some(parts.iter().map(|p| format!("{:0>2}", p.to_lowercase())).collect::<Vec<_>>().join(":"))
+}
+
+fn parse(format: LeaseFormat, data: &str, now: u64) -> Parsed {
+    let mut parsed = match format {
+        LeaseFormat::Isc => parse_isc(data),
+        LeaseFormat::DnsmasqHosts => parse_dnsmasq_hosts(data),
+        LeaseFormat::DnsmasqLeases => parse_dnsmasq_leases(data),
+        LeaseFormat::KeaCsv => parse_kea_csv(data),
+    };
+    // Expired dynamic leases have nothing left to migrate
+    let before = parsed.leases.len();
+    parsed.leases.retain(|l| !l.is_expired(now));
+    parsed.skipped += before - parsed.leases.len();
+    parsed
+}
+
+// --- ISC dhcpd ---
+
+/// Tokenize an ISC config/lease file into words, quoted strings and `{ } ;`
+fn isc_tokens(data: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => { while chars.next_if(|c| *c != '\n').is_some() {} }
+            '{' | '}' | ';' => tokens.push(c.to_string()),
+            '"' => {
+                let mut s = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => { if let Some(n) = chars.next() { s.push(n); } }
+                        _ => s.push(c),
+                    }
+                }
+                tokens.push(s);
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut s = c.to_string();
+                while let Some(n) = chars.next_if(|n| !n.is_whitespace() && !"{};\"".contains(*n)) {
+                    s.push(n);
+                }
+                tokens.push(s);
+            }
+        }
+    }
+    tokens
+}
+
+/// "4 2023/11/16 10:00:00" (weekday ignored, UTC), "epoch 1700000000" or "never"
+fn parse_isc_time(words: &[String]) -> Result<Option<u64>, String> {
+    match words {
+        [never] if never == "never" => Ok(None),
+        [epoch, secs, ..] if epoch == "epoch" => secs.parse().map(Some).map_err(|_| format!("bad epoch time {}", secs)),
+        [_, date, time, ..] => NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y/%m/%d %H:%M:%S")
+            .map(|dt| Some(dt.and_utc().timestamp().max(0) as u64))
+            .map_err(|_| format!("bad time {} {}", date, time)),
+        _ => Err(format!("bad time '{}'", words.join(" "))),
+    }
+}
+
+fn parse_isc(data: &str) -> Parsed {
+    let tokens = isc_tokens(data);
+    let mut parsed = Parsed::default();
+    let mut i = 0;
+    while i < tokens.len() {
+        let kind = tokens[i].as_str();
+        if (kind != "lease" && kind != "host") || tokens.get(i + 2).map(String::as_str) != Some("{") {
+            i += 1;
+            continue;
+        }
+        let name = tokens[i + 1].clone();
+        i += 3;
+
+        // Collect `statement ... ;` lines up to the closing brace
+        let mut statements: Vec<Vec<String>> = Vec::new();
+        let mut current = Vec::new();
+        while i < tokens.len() && tokens[i] != "}" {
+            if tokens[i] == ";" {
+                statements.push(std::mem::take(&mut current));
+            } else {
+                current.push(tokens[i].clone());
+            }
+            i += 1;
+        }
+        i += 1;
+
+        let find = |key: &[&str]| statements.iter().find(|s| s.len() > key.len() && s.iter().zip(key).all(|(a, b)| a == b)).map(|s| s[key.len()..].to_vec());
+        let mac = find(&["hardware", "ethernet"]).and_then(|v| normalize_mac(&v[0]));
+        let hostname = find(&["client-hostname"]).or_else(|| find(&["option", "host-name"]))
+            .map(|v| v[0].clone())
+            .unwrap_or_else(|| if kind == "host" { name.clone() } else { String::new() });
+
+        let entry = if kind == "host" {
+            let ip = find(&["fixed-address"]).map(|v| v[0].clone());
+            match (ip, mac) {
+                (Some(ip), Some(mac)) if ip.parse::<Ipv4Addr>().is_ok() => Ok(new_lease(ip, mac, hostname, None, true)),
+                _ => Err(format!("host {}: needs hardware ethernet and an IPv4 fixed-address", name)),
+            }
+        } else {
+            let state = find(&["binding", "state"]).map(|v| v[0].clone()).unwrap_or_else(|| "active".into());
+            if state != "active" {
+                parsed.skipped += 1;
+                continue;
+            }
+            let ends = find(&["ends"]).map(|v| parse_isc_time(&v)).unwrap_or(Ok(None));
+            match (name.parse::<Ipv4Addr>(), mac, ends) {
+                (Ok(_), Some(mac), Ok(ends)) => Ok(new_lease(name.clone(), mac, hostname, ends, false)),
+                (_, _, Err(e)) => Err(format!("lease {}: {}", name, e)),
+                _ => Err(format!("lease {}: needs an IPv4 address and hardware ethernet", name)),
+            }
+        };
+        match entry {
+            Ok(lease) => parsed.leases.push(lease),
+            Err(e) => parsed.errors.push(e),
+        }
+    }
+    parsed
+}
+
+fn isc_time(secs: u64) -> String {
+    match Utc.timestamp_opt(secs as i64, 0).single() {
+        Some(dt) => format!("{} {}", dt.weekday().num_days_from_sunday(), dt.format("%Y/%m/%d %H:%M:%S")),
+        None => "never".to_string(),
+    }
+}
+
+fn isc_name(lease: &Lease) -> String {
+    let base = if lease.hostname.is_empty() { lease.ip.replace('.', "-") } else { lease.hostname.clone() };
+    base.chars().map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' }).collect()
+}
+
+/// A hostname as the inside of an ISC quoted string: quotes and backslashes escaped, control characters dropped
+fn isc_quoted(hostname: &str) -> String {
+    hostname.chars().filter(|c| !c.is_control()).fold(String::new(), |mut out, c| {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+        out
+    })
+}
+
+fn export_isc(leases: &[Lease]) -> String {
+    let mut out = String::from("# Generated by CrabFlow\n");
+    for l in leases.iter().filter(|l| l.static_lease) {
+        out.push_str(&format!("host {} {{\n  hardware ethernet {};\n  fixed-address {};\n", isc_name(l), l.mac, l.ip));
+        if !l.hostname.is_empty() {
+            out.push_str(&format!("  option host-name \"{}\";\n", isc_quoted(&l.hostname)));
+        }
+        out.push_str("}\n");
+    }
+    for l in leases.iter().filter(|l| !l.static_lease) {
+        let ends = l.expires_at.map(isc_time).unwrap_or_else(|| "never".into());
+        out.push_str(&format!("lease {} {{\n  ends {};\n  binding state active;\n  hardware ethernet {};\n", l.ip, ends, l.mac));
+        if !l.hostname.is_empty() {
+            out.push_str(&format!("  client-hostname \"{}\";\n", isc_quoted(&l.hostname)));
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+// --- dnsmasq ---
+
+fn parse_dnsmasq_hosts(data: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    for (n, line) in data.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let line = line.strip_prefix("dhcp-host=").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let (mut mac, mut ip, mut hostname) = (None, None, String::new());
+        for field in line.split(',').map(str::trim) {
+            if let Some(m) = normalize_mac(field) {
+                mac = mac.or(Some(m));
+            } else if field.parse::<Ipv4Addr>().is_ok() {
+                ip = Some(field.to_string());
+            } else if field.contains(':') || field.starts_with('[') || field == "infinite" || field == "ignore"
+                || field.trim_end_matches(['s', 'm', 'h', 'd', 'w']).parse::<u64>().is_ok() {
+                // id:/set:/tag: prefixes, IPv6 addresses and lease times carry nothing we store
+            } else {
+                hostname = field.to_string();
+            }
+        }
+        match (mac, ip) {
+            (Some(mac), Some(ip)) => parsed.leases.push(new_lease(ip, mac, hostname, None, true)),
+            _ => parsed.errors.push(format!("line {}: needs a MAC and an IPv4 address", n + 1)),
+        }
+    }
+    parsed
+}
+
+fn parse_dnsmasq_leases(data: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    for (n, line) in data.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() || fields[0] == "duid" {
+            continue;
+        }
+        let entry = match fields.as_slice() {
+            [expiry, mac, ip, hostname, ..] => {
+                let expires = expiry.parse::<u64>().ok().map(|e| if e == 0 { None } else { Some(e) });
+                match (expires, normalize_mac(mac), ip.parse::<Ipv4Addr>()) {
+                    (Some(expires), Some(mac), Ok(_)) => {
+                        let hostname = if *hostname == "*" { String::new() } else { hostname.to_string() };
+                        Some(new_lease(ip.to_string(), mac, hostname, expires, false))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        match entry {
+            Some(lease) => parsed.leases.push(lease),
+            None if fields.get(2).is_some_and(|ip| ip.contains(':')) => parsed.skipped += 1, // DHCPv6 lease
+            None => parsed.errors.push(format!("line {}: expected 'expiry mac ip hostname'", n + 1)),
+        }
+    }
+    parsed
+}
+
+/// dnsmasq splits both files on whitespace or commas, so a hostname can't contain either
+fn dnsmasq_name(hostname: &str) -> String {
+    hostname.chars().filter(|c| !c.is_whitespace() && *c != ',').collect()
+}
+
+fn export_dnsmasq_hosts(leases: &[Lease]) -> String {
+    leases.iter()
+        .filter(|l| l.static_lease)
+        .map(|l| match dnsmasq_name(&l.hostname) {
+            name if name.is_empty() => format!("{},{}\n", l.mac, l.ip),
+            name => format!("{},{},{}\n", l.mac, l.ip, name),
+        })
+        .collect()
+}
+
+fn export_dnsmasq_leases(leases: &[Lease]) -> String {
+    leases.iter()
+        .filter(|l| !l.static_lease)
+        .map(|l| {
+            let hostname = match dnsmasq_name(&l.hostname) {
+                name if name.is_empty() => "*".to_string(),
+                name => name,
+            };
+            format!("{} {} {} {} *\n", l.expires_at.unwrap_or(0), l.mac, l.ip, hostname)
+        })
+        .collect()
+}
+
+// --- Kea ---
+
+fn parse_kea_csv(data: &str) -> Parsed {
+    let mut parsed = Parsed::default();
+    let mut lines = data.lines().enumerate();
+    let header: Vec<&str> = match lines.next() {
+        Some((_, h)) => h.split(',').map(str::trim).collect(),
+        None => return parsed,
+    };
+    let col = |name: &str| header.iter().position(|h| *h == name);
+    let context = col("user_context");
+    let (addr, hw, lifetime, expire, host, state) =
+        match (col("address"), col("hwaddr"), col("valid_lifetime"), col("expire"), col("hostname"), col("state")) {
+            (Some(a), Some(h), Some(l), Some(e), host, state) => (a, h, l, e, host, state),
+            _ => {
+                parsed.errors.push("missing Kea lease4.csv header (address,hwaddr,...,valid_lifetime,expire)".into());
+                return parsed;
+            }
+        };
+
+    for (n, line) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        let get = |i: usize| fields.get(i).map(|f| f.trim()).unwrap_or("");
+        // Only state 0 (default) is a live lease; declined and reclaimed rows are history
+        if state.is_some_and(|s| get(s) != "0" && !get(s).is_empty()) {
+            parsed.skipped += 1;
+            continue;
+        }
+        // An infinite lifetime is still a dynamic lease, it just never expires, unless we exported it as a reservation
+        let infinite = get(lifetime).parse::<u64>().is_ok_and(|l| l >= KEA_INFINITE);
+        let reservation = infinite && context.is_some_and(|c| is_static_context(get(c)));
+        let hostname = host.map(|h| get(h).trim_end_matches('.').to_string()).unwrap_or_default();
+        match (get(addr).parse::<Ipv4Addr>(), normalize_mac(get(hw)), get(expire).parse::<u64>()) {
+            (Ok(_), Some(mac), Ok(exp)) => parsed.leases.push(new_lease(
+                get(addr).to_string(), mac, hostname,
+                if infinite { None } else { Some(exp) },
+                reservation,
+            )),
+            _ => parsed.errors.push(format!("line {}: bad address, hwaddr or expire", n + 1)),
+        }
+    }
+    parsed
+}
+
+/// Kea escapes commas in user_context as "&#x2c"
+fn is_static_context(field: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(&field.replace("&#x2c", ","))
+        .is_ok_and(|v| v.get("crabflow-static") == Some(&serde_json::Value::Bool(true)))
+}
+
+fn export_kea_csv(leases: &[Lease]) -> String {
+    let now = now_secs();
+    let mut out = format!("{}\n", KEA_HEADER);
+    for l in leases {
+        let (lifetime, expire) = match l.expires_at {
+            Some(exp) if !l.static_lease => (exp.saturating_sub(now), exp),
+            _ => (KEA_INFINITE, KEA_INFINITE),
+        };
+        let context = if l.static_lease { KEA_STATIC_CONTEXT } else { "" };
+        out.push_str(&format!("{},{},,{},{},1,0,0,{},0,{}\n", l.ip, l.mac, lifetime, expire, l.hostname.replace(',', ""), context));
+    }
+    out
+}
+
+// --- Preview / commit ---
+
+/// Compare parsed leases against the table: new, identical, or clashing with an existing entry
+fn preview(table: &LeaseTable, parsed: Parsed) -> LeaseImportPreview {
+    let mut result = LeaseImportPreview { skipped: parsed.skipped, errors: parsed.errors, ..Default::default() };
+    for lease in parsed.leases {
+        let same_ip = table.by_ip(&lease.ip).filter(|l| l.state == LeaseState::Active);
+        // A second reservation for a MAC clashes with its first; any lease replaces the MAC's dynamic one
+        let same_mac = table.all().iter().find(|l| {
+            l.state == LeaseState::Active && l.mac.eq_ignore_ascii_case(&lease.mac) && l.ip != lease.ip
+                && (!l.static_lease || lease.static_lease)
+        });
+
+        let conflict = match (same_ip, same_mac) {
+            (Some(e), _) if e.mac.eq_ignore_ascii_case(&lease.mac) && e.static_lease == lease.static_lease => {
+                result.unchanged += 1;
+                continue;
+            }
+            (Some(e), _) if !e.mac.eq_ignore_ascii_case(&lease.mac) => Some((e, format!("{} is already leased to {}", lease.ip, e.mac))),
+            (Some(e), _) => Some((e, format!("{} exists as a {} lease", lease.ip, if e.static_lease { "static" } else { "dynamic" }))),
+            (None, Some(e)) if e.static_lease => Some((e, format!("{} already has a reservation for {}", lease.mac, e.ip))),
+            (None, Some(e)) => Some((e, format!("{} already holds a dynamic lease on {}", lease.mac, e.ip))),
+            (None, None) => None,
+        };
+        match conflict {
+            Some((existing, reason)) => result.conflicts.push(LeaseConflict { imported: lease, existing: existing.clone(), reason }),
+            None => result.to_add.push(lease),
+        }
+    }
+    result
+}
+
+/// Dry run: parse `data` and report what importing it would change
+#[tauri::command]
+pub fn preview_lease_import(format: LeaseFormat, data: String) -> LeaseImportPreview {
+    let table = LEASE_TABLE.read().unwrap();
+    preview(&table, parse(format, &data, now_secs()))
+}
+
+/// Import leases. Conflicting entries are skipped unless `replace_conflicts` is set,
+/// in which case the imported lease wins. Returns the number of leases written.
+#[tauri::command]
+pub fn import_leases(format: LeaseFormat, data: String, replace_conflicts: bool) -> Result<usize, String> {
+    let mut table = LEASE_TABLE.write().unwrap();
+    let plan = preview(&table, parse(format, &data, now_secs()));
+    if !plan.errors.is_empty() {
+        return Err(format!("Fix {} parse errors before importing: {}", plan.errors.len(), plan.errors.join("; ")));
+    }
+
+    let mut count = plan.to_add.len();
+    for lease in plan.to_add {
+        table.insert(lease);
+    }
+    if replace_conflicts {
+        count += plan.conflicts.len();
+        for c in plan.conflicts {
+            table.remove(&c.existing.ip);
+            table.insert(c.imported);
+        }
+    }
+    table.persist()?;
+    logging::log_event("system".into(), "import_leases".into(), format!("{:?}: {} leases", format, count));
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn export_leases(format: LeaseFormat) -> String {
+    let table = LEASE_TABLE.read().unwrap();
+    let leases: Vec<Lease> = table.all().iter()
+        .filter(|l| l.state == LeaseState::Active && !l.is_expired(now_secs()))
+        .cloned()
+        .collect();
+    match format {
+        LeaseFormat::Isc => export_isc(&leases),
+        LeaseFormat::DnsmasqHosts => export_dnsmasq_hosts(&leases),
+        LeaseFormat::DnsmasqLeases => export_dnsmasq_leases(&leases),
+        LeaseFormat::KeaCsv => export_kea_csv(&leases),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_isc() {
+        let data = r#"
+            # dhcpd.conf reservation
+            host printer { hardware ethernet 00:11:22:33:44:55; fixed-address 10.0.0.20; }
+            lease 10.0.0.120 {
+              starts 4 2023/11/16 10:00:00;
+              ends 5 2023/11/17 10:00:00;
+              binding state active;
+              hardware ethernet AA:BB:CC:DD:EE:FF;
+              client-hostname "laptop";
+            }
+            lease 10.0.0.121 { binding state free; hardware ethernet aa:bb:cc:dd:ee:00; }
+        "#;
+        let parsed = parse_isc(data);
+        assert!(parsed.errors.is_empty());
+        assert_eq!(parsed.skipped, 1);
+        assert_eq!(parsed.leases.len(), 2);
+        assert!(parsed.leases[0].static_lease && parsed.leases[0].hostname == "printer");
+        assert_eq!(parsed.leases[1].mac, "aa:bb:cc:dd:ee:ff");
+        assert_eq!(parsed.leases[1].expires_at, Some(1700215200));
+
+        let reparsed = parse_isc(&export_isc(&parsed.leases));
+        assert_eq!(reparsed.leases.len(), 2);
+        assert_eq!(reparsed.leases[1].expires_at, Some(1700215200));
+    }
+
+    #[test]
+    fn test_parse_dnsmasq_and_kea() {
+        let hosts = parse_dnsmasq_hosts("00:11:22:33:44:55,10.0.0.20,printer,infinite\nid:01:02,set:lab,10.0.0.21\n");
+        assert_eq!(hosts.leases.len(), 1);
+        assert_eq!(hosts.leases[0].hostname, "printer");
+        assert_eq!(hosts.errors.len(), 1);
+
+        let leases = parse_dnsmasq_leases("1700000000 aa:bb:cc:dd:ee:ff 10.0.0.120 laptop 01:aa:bb:cc:dd:ee:ff\n");
+        assert_eq!(leases.leases[0].expires_at, Some(1700000000));
+
+        let kea = parse_kea_csv(&format!("{}\n10.0.0.20,00:11:22:33:44:55,,4294967295,4294967295,1,0,0,printer.lan.,0,\n10.0.0.30,00:11:22:33:44:66,,3600,1700000000,1,0,0,,1,\n", KEA_HEADER));
+        assert_eq!(kea.leases.len(), 1);
+        assert_eq!(kea.skipped, 1);
+        assert!(!kea.leases[0].static_lease && kea.leases[0].hostname == "printer.lan");
+        assert_eq!(kea.leases[0].expires_at, None);
+
+        // Our own reservations survive an export/import round trip
+        let leases = vec![
+            new_lease("10.0.0.20".into(), "00:11:22:33:44:55".into(), "printer".into(), None, true),
+            new_lease("10.0.0.21".into(), "00:11:22:33:44:66".into(), "nas".into(), None, false),
+        ];
+        let reparsed = parse_kea_csv(&export_kea_csv(&leases));
+        assert!(reparsed.errors.is_empty());
+        assert!(reparsed.leases[0].static_lease && reparsed.leases[0].expires_at.is_none());
+        assert!(!reparsed.leases[1].static_lease && reparsed.leases[1].expires_at.is_none());
+        assert!(is_static_context(r#"{"crabflow-static":true&#x2c"note":"x"}"#));
+    }
+
+    #[test]
+    fn test_export_hostnames() {
+        let leases = vec![
+            new_lease("10.0.0.20".into(), "00:11:22:33:44:55".into(), "my \"printer\"\\".into(), None, true),
+            new_lease("10.0.0.120".into(), "aa:bb:cc:dd:ee:ff".into(), "Bob's laptop".into(), Some(1700000000), false),
+        ];
+        let isc = export_isc(&leases);
+        assert!(isc.contains(r#"option host-name "my \"printer\"\\";"#));
+        let reparsed = parse_isc(&isc);
+        assert!(reparsed.errors.is_empty());
+        assert_eq!(reparsed.leases[0].hostname, "my \"printer\"\\");
+        assert_eq!(reparsed.leases[1].hostname, "Bob's laptop");
+
+        assert_eq!(export_dnsmasq_leases(&leases), "1700000000 aa:bb:cc:dd:ee:ff 10.0.0.120 Bob'slaptop *\n");
+        let reparsed = parse_dnsmasq_leases(&export_dnsmasq_leases(&leases));
+        assert_eq!(reparsed.leases[0].hostname, "Bob'slaptop");
+        assert_eq!(export_dnsmasq_hosts(&leases), "00:11:22:33:44:55,10.0.0.20,my\"printer\"\\\n");
+    }
+
+    #[test]
+    fn test_preview_conflicts() {
+        let table = LeaseTable {
+            leases: vec![
+                new_lease("10.0.0.20".into(), "00:11:22:33:44:55".into(), "printer".into(), None, true),
+                new_lease("10.0.0.30".into(), "aa:aa:aa:aa:aa:aa".into(), "".into(), None, true),
+            ],
+        };
+        let parsed = parse_dnsmasq_hosts("00:11:22:33:44:55,10.0.0.20\n00:11:22:33:44:66,10.0.0.30\naa:aa:aa:aa:aa:aa,10.0.0.31\n00:11:22:33:44:77,10.0.0.40\n");
+        let plan = preview(&table, parsed);
+        assert_eq!(plan.unchanged, 1);
+        assert_eq!(plan.conflicts.len(), 2);
+        assert_eq!(plan.to_add.len(), 1);
+        assert_eq!(plan.to_add[0].ip, "10.0.0.40");
+
+        // Importing a lease for a MAC with a dynamic lease elsewhere would silently drop that lease
+        let table = LeaseTable { leases: vec![new_lease("10.0.0.120".into(), "aa:bb:cc:dd:ee:ff".into(), "".into(), Some(u64::MAX), false)] };
+        let plan = preview(&table, parse_dnsmasq_leases(&format!("{} aa:bb:cc:dd:ee:ff 10.0.0.121 laptop *\n", u64::MAX)));
+        assert_eq!(plan.conflicts.len(), 1);
+        assert_eq!(plan.conflicts[0].reason, "aa:bb:cc:dd:ee:ff already holds a dynamic lease on 10.0.0.120");
+        assert!(plan.to_add.is_empty());
+    }
+}
@@ -0,0 +1,1478 @@
+// src-tauri/src/network/dhcp/mod.rs
+
+use serde::{Serialize, Deserialize};
+use crate::sysmodules::{fetch, post, logging, config, notify, paths};
+use crate::network::{dhcpv6, dns, monitor};
+use tauri::AppHandle;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddrV4, UdpSocket};
+use std::thread;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH, Duration};
+use std::collections::{BTreeMap, HashSet};
+use lazy_static::lazy_static;
+
+pub mod hooks;
+pub mod ingress;
+pub mod lease_formats;
+pub mod options;
+
+use hooks::LeaseEvent;
+use ingress::{DhcpSocket, Ingress, Responder};
+use options::{DhcpOption, encode_option, encoded_len, resolve_options};
+pub use options::RelayAgentInfo;
+
+static DHCP_RUNNING: AtomicBool = AtomicBool::new(false);
+static REAPER_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// How often the reaper sweeps the lease table for expired dynamic leases
+const LEASE_REAP_INTERVAL_SECS: u64 = 60;
+
+/// Conflict probing: ICMP timeout, and how many addresses to try before giving up on an OFFER
+const CONFLICT_PING_TIMEOUT_MS: u64 = 500;
+const MAX_CONFLICT_PROBES: usize = 3;
+
+lazy_static! {
+    // Loaded from leases.json on first use; every change is written back atomically
+    static ref LEASE_TABLE: RwLock<LeaseTable> = RwLock::new(LeaseTable::load());
+    // Clients whose OFFER waits on a conflict probe; DISCOVERs they retransmit meanwhile are dropped
+    static ref PROBING: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Lease {
+    pub ip: String,
+    pub mac: String,
+    pub hostname: String,
+    #[serde(default, deserialize_with = "deserialize_expiry")]
+    pub expires_at: Option<u64>, // Unix seconds, None = never expires
+    pub static_lease: bool,
+    #[serde(default)]
+    pub state: LeaseState,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, String>, // Per-host DHCP option overrides (static leases)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay_info: Option<RelayAgentInfo>, // Option 82 seen from the client; on a static lease, the port it is reserved for
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaseState {
+    #[default]
+    Active,
+    Declined, // Client reported the address in use; held out of the pool until expires_at
+    Conflict, // Our own probe found the address in use; held out of the pool until expires_at
+}
+
+impl Lease {
+    /// Static leases never expire; dynamic ones expire once `expires_at` has passed.
+    pub fn is_expired(&self, now: u64) -> bool {
+        !self.static_lease && self.expires_at.is_some_and(|t| t <= now)
+    }
+}
+
+/// Accepts plain Unix timestamps as well as the legacy string forms
+/// ("never", "1700000000", RFC 3339) found in older leases.json files.
+fn deserialize_expiry<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawExpiry {
+        Secs(u64),
+        Text(String),
+    }
+
+    Ok(match Option::<RawExpiry>::deserialize(deserializer)? {
+        None => None,
+        Some(RawExpiry::Secs(secs)) => Some(secs),
+        Some(RawExpiry::Text(text)) => parse_expiry_text(&text),
+    })
+}
+
+fn parse_expiry_text(text: &str) -> Option<u64> {
+    let text = text.trim();
+    if text.is_empty() || text.eq_ignore_ascii_case("never") {
+        return None;
+    }
+    if let Ok(secs) = text.parse::<u64>() {
+        return Some(secs);
+    }
+    chrono::DateTime::parse_from_rfc3339(text)
+        .ok()
+        .map(|dt| dt.timestamp().max(0) as u64)
+}
+
+pub(crate) fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[derive(Deserialize)]
+pub struct LeaseInput {
+    pub ip: String,
+    pub mac: String,
+    pub hostname: String,
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+    #[serde(default)]
+    pub relay_info: Option<RelayAgentInfo>,
+}
+
+fn get_leases_file() -> String {
+    paths::get_config_path("leases.json").to_string_lossy().to_string()
+}
+
+// --- Lease Table ---
+
+/// In-memory lease database backed by leases.json.
+/// The DHCP server and the DNS ACL check read from here instead of the disk.
+#[derive(Default)]
+pub struct LeaseTable {
+    leases: Vec<Lease>,
+}
+
+impl LeaseTable {
+    fn load() -> Self {
+        let leases = match fetch::read_file(&get_leases_file()) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                logging::log_error(&format!("Failed to parse leases file, starting with an empty table: {}", e));
+                vec![]
+            }),
+            Err(_) => vec![],
+        };
+        Self { leases }
+    }
+
+    /// Every change to the table is persisted, so this is also where DNS learns the leases changed
+    fn persist(&self) -> Result<(), String> {
+        dns::dhcp_names::invalidate();
+        let serialized = serde_json::to_string_pretty(&self.leases).map_err(|e| e.to_string())?;
+        post::write_file_atomic(&get_leases_file(), &serialized)
+    }
+
+    pub fn all(&self) -> &[Lease] {
+        &self.leases
+    }
+
+    pub fn by_ip(&self, ip: &str) -> Option<&Lease> {
+        self.leases.iter().find(|l| l.ip == ip)
+    }
+
+    /// Find the active lease a MAC holds inside the scope's subnet, preferring a static reservation over a dynamic lease.
+    pub fn by_mac_in_scope(&self, mac: &str, config: &config::DhcpConfig) -> Option<&Lease> {
+        let held = |l: &&Lease| {
+            l.state == LeaseState::Active
+                && l.mac.eq_ignore_ascii_case(mac)
+                && l.ip.parse().is_ok_and(|ip| in_subnet(ip, config))
+        };
+        self.leases.iter()
+            .find(|l| l.static_lease && held(l))
+            .or_else(|| self.leases.iter().find(held))
+    }
+
+    /// Find a static lease in the scope reserved for the relay port a client sits behind
+    pub fn reservation_for_relay(&self, relay: &RelayAgentInfo, config: &config::DhcpConfig) -> Option<&Lease> {
+        self.leases.iter().find(|l| {
+            l.static_lease
+                && l.relay_info.as_ref().is_some_and(|r| r.matches(relay))
+                && l.ip.parse().is_ok_and(|ip| in_subnet(ip, config))
+        })
+    }
+
+    /// True if `mac` holds an unexpired active lease on `ip`
+    pub fn is_held_by(&self, ip: &str, mac: &str, now: u64) -> bool {
+        self.by_ip(ip).is_some_and(|l| {
+            l.state == LeaseState::Active && !l.is_expired(now) && l.mac.eq_ignore_ascii_case(mac)
+        })
+    }
+
+    /// True if `ip` is held by an unexpired lease that does not belong to `mac`.
+    /// Quarantined addresses are taken for everyone.
+    pub fn is_taken(&self, ip: &str, mac: Option<&str>, now: u64) -> bool {
+        self.by_ip(ip).is_some_and(|l| {
+            !l.is_expired(now)
+                && (l.state != LeaseState::Active || mac.is_none_or(|m| !l.mac.eq_ignore_ascii_case(m)))
+        })
+    }
+
+    /// Insert a lease, replacing any lease on the same IP and any dynamic lease held by the same MAC.
+    fn insert(&mut self, lease: Lease) {
+        let replaces_binding = lease.state == LeaseState::Active;
+        self.leases.retain(|l| {
+            l.ip != lease.ip
+                && !(replaces_binding && !l.static_lease && l.state == LeaseState::Active && l.mac.eq_ignore_ascii_case(&lease.mac))
+        });
+        self.leases.push(lease);
+    }
+
+    fn remove(&mut self, ip: &str) -> Option<Lease> {
+        let pos = self.leases.iter().position(|l| l.ip == ip)?;
+        Some(self.leases.remove(pos))
+    }
+
+    /// Drop every expired dynamic lease and return what was reclaimed.
+    fn reap_expired(&mut self, now: u64) -> Vec<Lease> {
+        let (expired, active): (Vec<Lease>, Vec<Lease>) = std::mem::take(&mut self.leases)
+            .into_iter()
+            .partition(|l| l.is_expired(now));
+        self.leases = active;
+        expired
+    }
+
+    /// Pick an address for a new lease: never-leased addresses first, then ones whose lease has expired.
+    fn pick_free_ip(&self, config: &config::DhcpConfig, now: u64) -> Option<String> {
+        pool_addresses(config)
+            .find(|ip| self.by_ip(ip).is_none())
+            .or_else(|| pool_addresses(config).find(|ip| !self.is_taken(ip, None, now)))
+    }
+}
+
+/// Iterate the configured pool, skipping the gateway and the server's own address.
+fn pool_addresses(config: &config::DhcpConfig) -> impl Iterator<Item = String> {
+    let start = config.range_start.parse::<Ipv4Addr>().map(u32::from);
+    let end = config.range_end.parse::<Ipv4Addr>().map(u32::from);
+    // A malformed pool yields nothing
+    let range = start.ok().zip(end.ok()).map(|(s, e)| s..=e);
+    let gateway = config.gateway.clone();
+    let bind_address = config.bind_address.clone();
+    range
+        .into_iter()
+        .flatten()
+        .map(|n| Ipv4Addr::from(n).to_string())
+        .filter(move |ip| *ip != gateway && *ip != bind_address)
+}
+
+/// Reload the lease table from disk (e.g. after networking is re-initialized)
+pub fn reload_lease_table() {
+    let table = LeaseTable::load();
+    let count = table.leases.len();
+    *LEASE_TABLE.write().unwrap() = table;
+    dns::dhcp_names::invalidate();
+    logging::log_info(&format!("DHCP lease table loaded ({} leases)", count));
+}
+
+fn persist_or_log(table: &LeaseTable) {
+    if let Err(e) = table.persist() {
+        logging::log_error(&format!("Failed to persist DHCP leases: {}", e));
+    }
+}
+
+/// Reclaim expired dynamic leases. Returns the number of leases removed.
+pub fn reap_expired_leases() -> usize {
+    let mut table = LEASE_TABLE.write().unwrap();
+    let expired = table.reap_expired(now_secs());
+    if !expired.is_empty() {
+        persist_or_log(&table);
+        for lease in &expired {
+            // Quarantined addresses going back to the pool are not client leases
+            if lease.state != LeaseState::Active {
+                continue;
+            }
+            logging::log_info(&format!("DHCP lease expired: {} ({}) - {}", lease.hostname, lease.ip, lease.mac));
+            hooks::fire(LeaseEvent::LeaseExpired, lease);
+        }
+    }
+    expired.len()
+}
+
+fn spawn_lease_reaper() {
+    if REAPER_RUNNING.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    thread::spawn(|| {
+        let mut elapsed = 0;
+        while DHCP_RUNNING.load(Ordering::Relaxed) {
+            thread::sleep(Duration::from_secs(1));
+            elapsed += 1;
+            if elapsed >= LEASE_REAP_INTERVAL_SECS {
+                elapsed = 0;
+                let reclaimed = reap_expired_leases();
+                if reclaimed > 0 {
+                    logging::log_debug(&format!("Lease reaper reclaimed {} addresses", reclaimed));
+                }
+            }
+        }
+        REAPER_RUNNING.store(false, Ordering::Relaxed);
+    });
+}
+
+/// List all DHCP leases, IPv4 and IPv6
+pub fn list_leases() -> Vec<Lease> {
+    let mut leases = LEASE_TABLE.read().unwrap().all().to_vec();
+    leases.extend(dhcpv6::list_leases());
+    leases
+}
+
+/// Check if an IP is actively leased
+pub fn is_ip_leased(ip: &str) -> bool {
+    if let Ok(v6) = ip.parse::<Ipv6Addr>() {
+        return dhcpv6::is_ip_leased(&v6);
+    }
+    let table = LEASE_TABLE.read().unwrap();
+    table.by_ip(ip).is_some_and(|l| l.state == LeaseState::Active && !l.is_expired(now_secs()))
+}
+
+/// Retrieve the MAC address for a given IP from the active leases
+pub fn get_mac_from_ip(target_ip: &str) -> Option<String> {
+    let table = LEASE_TABLE.read().unwrap();
+    table.by_ip(target_ip)
+        .filter(|l| l.state == LeaseState::Active && !l.is_expired(now_secs()))
+        .map(|l| l.mac.clone())
+}
+
+/// Retrieve the relay agent information (option 82) a leased client was last seen with
+pub fn get_relay_info_from_ip(target_ip: &str) -> Option<RelayAgentInfo> {
+    let table = LEASE_TABLE.read().unwrap();
+    table.by_ip(target_ip)
+        .filter(|l| l.state == LeaseState::Active && !l.is_expired(now_secs()))
+        .and_then(|l| l.relay_info.clone())
+}
+
+#[tauri::command]
+pub fn get_next_free_ip() -> Option<String> {
+    let cfg = match config::load_setup_config() {
+        Ok(c) => c,
+        Err(_) => return None,
+    };
+    find_free_ip(&cfg.dhcp, None)
+}
+
+/// Add a static lease
+pub fn add_static_lease(input: LeaseInput) -> Result<(), String> {
+    let ip_for_log = input.ip.clone();
+    let mut table = LEASE_TABLE.write().unwrap();
+
+    table.insert(Lease {
+        ip: input.ip,
+        mac: input.mac,
+        hostname: input.hostname,
+        expires_at: None,
+        static_lease: true,
+        state: LeaseState::Active,
+        options: input.options,
+        relay_info: input.relay_info.filter(|r| !r.is_empty()),
+    });
+
+    table.persist()?;
+    logging::log_event("system".into(), "add_static_lease".into(), ip_for_log);
+    Ok(())
+}
+
+/// Remove a lease by IP
+pub fn remove_lease(ip: String) -> Result<(), String> {
+    if let Ok(v6) = ip.parse::<Ipv6Addr>() {
+        dhcpv6::remove_lease(&v6)?;
+        logging::log_event("system".into(), "remove_lease".into(), ip);
+        return Ok(());
+    }
+    let mut table = LEASE_TABLE.write().unwrap();
+    table.remove(&ip);
+
+    table.persist()?;
+    logging::log_event("system".into(), "remove_lease".into(), ip);
+    Ok(())
+}
+
+// --- DHCP Server Implementation ---
+
+pub fn stop_dhcp_server() {
+    if DHCP_RUNNING.load(Ordering::Relaxed) {
+        logging::log_info("Stopping DHCP Server...");
+        DHCP_RUNNING.store(false, Ordering::Relaxed);
+    }
+}
+
+pub fn is_server_running() -> bool {
+    DHCP_RUNNING.load(Ordering::Relaxed)
+}
+
+pub fn start_dhcp_server(app_handle: Option<AppHandle>) {
+    if DHCP_RUNNING.load(Ordering::Relaxed) {
+        logging::log_info("DHCP Server is already running.");
+        return;
+    }
+
+    // Load config to check if enabled and get bind address
+    let config = match config::load_setup_config() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            logging::log_error(&format!("Failed to load config for DHCP: {}", e));
+            return;
+        }
+    };
+
+    if !config.dhcp.enabled {
+        logging::log_info("DHCP Server is disabled in config. Skipping start.");
+        return;
+    }
+
+    DHCP_RUNNING.store(true, Ordering::Relaxed);
+
+    // Reclaim addresses whose leases ran out while we were stopped, then keep sweeping
+    reap_expired_leases();
+    spawn_lease_reaper();
+
+    // Determine server MAC for the bind address to avoid leasing ourselves
+    let server_mac = {
+        let mut nets = sysinfo::Networks::new_with_refreshed_list();
+        nets.refresh();
+        let mut found: Option<String> = None;
+        for (_name, data) in nets.iter() {
+            let mac = data.mac_address().to_string();
+            if !mac.is_empty() && found.is_none() {
+                found = Some(mac.clone());
+            }
+        }
+        found
+    };
+
+    for scope in config.dhcp_scope_configs() {
+        logging::log_info(&format!("DHCP scope {}-{} on {}", scope.range_start, scope.range_end, scope.bind_address));
+    }
+    thread::spawn(move || run_listener(app_handle, server_mac));
+}
+
+/// One wildcard listener serves every scope; requests are matched to scopes by the interface they arrive on
+fn run_listener(app: Option<AppHandle>, server_mac: Option<String>) {
+    match DhcpSocket::bind() {
+        Ok(socket) => {
+            let socket = Arc::new(socket);
+            logging::log_info("DHCP Server started on 0.0.0.0:67");
+            if let Some(h) = &app {
+                notify::send_notification(h, "DHCP Started", "Listening on 0.0.0.0:67", "success");
+            }
+
+            let mut buf = [0u8; 1500];
+            while DHCP_RUNNING.load(Ordering::Relaxed) {
+                match socket.recv(&mut buf) {
+                    Ok((amt, src, ingress)) => {
+                        let packet = &buf[..amt];
+                        let via = ingress.as_ref().map_or("unknown interface", |i| i.interface.as_str());
+                        logging::log_debug(&format!("DHCP packet received from {} on {} ({} bytes)", src, via, amt));
+                        handle_dhcp_packet(&Responder::new(socket.clone(), ingress), packet, &app, server_mac.clone());
+                    }
+                    Err(e) => {
+                        if e.kind() != std::io::ErrorKind::WouldBlock && e.kind() != std::io::ErrorKind::TimedOut {
+                            logging::log_error(&format!("Error receiving DHCP packet: {}", e));
+                        }
+                    }
+                }
+            }
+            logging::log_info("DHCP Server stopped.");
+        }
+        Err(e) => {
+            let err_msg = format!("Failed to bind DHCP server to 0.0.0.0:67: {}", e);
+            logging::log_error(&err_msg);
+            if let Some(h) = &app {
+                notify::send_notification(h, "DHCP Error", &err_msg, "error");
+            }
+        }
+    }
+    DHCP_RUNNING.store(false, Ordering::Relaxed);
+}
+
+/// Fields of a client BOOTREQUEST that drive the DHCP state machine
+#[derive(Clone)]
+struct DhcpMessage {
+    msg_type: u8,
+    xid: [u8; 4],
+    flags: [u8; 2],
+    ciaddr: Ipv4Addr,
+    giaddr: Ipv4Addr,
+    chaddr: [u8; 6],
+    mac: String,
+    hostname: String,
+    requested_ip: Option<String>,
+    server_id: Option<Ipv4Addr>,
+    param_request_list: Vec<u8>,
+    vendor_class: Option<String>,
+    max_message_size: Option<u16>,
+    relay_agent_info: Option<Vec<u8>>, // Raw option 82, echoed back in replies
+}
+
+/// Client state implied by a DHCPREQUEST (RFC 2131 section 4.3.2).
+/// RENEWING and REBINDING only differ in whether the request was unicast or
+/// broadcast, which a socket bound to the wildcard address cannot see, so
+/// both are handled as `Renewing`.
+#[derive(Debug, PartialEq)]
+enum RequestState {
+    Selecting,
+    InitReboot,
+    Renewing,
+}
+
+impl DhcpMessage {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        // Basic validation (Op=1 BootRequest, Hlen=6, Magic Cookie)
+        if packet.len() < 240 || packet[0] != 1 || packet[2] != 6 {
+            return None;
+        }
+
+        // Check Magic Cookie (99, 130, 83, 99)
+        if packet[236..240] != [99, 130, 83, 99] {
+            return None;
+        }
+
+        let mut chaddr = [0u8; 6];
+        chaddr.copy_from_slice(&packet[28..34]);
+        let mut msg = DhcpMessage {
+            msg_type: 0,
+            xid: [packet[4], packet[5], packet[6], packet[7]],
+            flags: [packet[10], packet[11]],
+            ciaddr: Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]),
+            giaddr: Ipv4Addr::new(packet[24], packet[25], packet[26], packet[27]),
+            chaddr,
+            mac: format!("{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+                chaddr[0], chaddr[1], chaddr[2], chaddr[3], chaddr[4], chaddr[5]),
+            hostname: "Unknown".to_string(),
+            requested_ip: None,
+            server_id: None,
+            param_request_list: Vec::new(),
+            vendor_class: None,
+            max_message_size: None,
+            relay_agent_info: None,
+        };
+
+        for opt in options::decode_options(&packet[240..]) {
+            match opt {
+                DhcpOption::MessageType(t) => msg.msg_type = t,
+                DhcpOption::Hostname(name) => msg.hostname = name,
+                DhcpOption::RequestedIp(ip) => msg.requested_ip = Some(ip.to_string()),
+                DhcpOption::ServerId(ip) => msg.server_id = Some(ip),
+                DhcpOption::ParameterRequestList(list) => msg.param_request_list = list,
+                DhcpOption::VendorClass(class) => msg.vendor_class = Some(class),
+                DhcpOption::MaxMessageSize(size) => msg.max_message_size = Some(size),
+                DhcpOption::RelayAgentInfo(data) => msg.relay_agent_info = Some(data),
+                _ => {}
+            }
+        }
+
+        Some(msg)
+    }
+
+    fn relay_info(&self) -> Option<RelayAgentInfo> {
+        self.relay_agent_info.as_deref()
+            .map(RelayAgentInfo::parse)
+            .filter(|r| !r.is_empty())
+    }
+
+    fn is_relayed(&self) -> bool {
+        !self.giaddr.is_unspecified()
+    }
+
+    fn request_state(&self) -> RequestState {
+        if self.server_id.is_some() {
+            RequestState::Selecting
+        } else if self.ciaddr.is_unspecified() {
+            RequestState::InitReboot
+        } else {
+            RequestState::Renewing
+        }
+    }
+}
+
+// DHCP message types (option 53)
+const DHCPDISCOVER: u8 = 1;
+const DHCPOFFER: u8 = 2;
+const DHCPREQUEST: u8 = 3;
+const DHCPDECLINE: u8 = 4;
+const DHCPACK: u8 = 5;
+const DHCPNAK: u8 = 6;
+const DHCPRELEASE: u8 = 7;
+const DHCPINFORM: u8 = 8;
+
+fn handle_dhcp_packet(socket: &Responder, packet: &[u8], app: &Option<AppHandle>, server_mac: Option<String>) {
+    let msg = match DhcpMessage::parse(packet) {
+        Some(m) => m,
+        None => return,
+    };
+
+    // Load Config
+    let setup = match fetch::fetch_setup() {
+        Ok(s) => s,
+        Err(_) => return, // Can't serve without config
+    };
+    
+    if !setup.dhcp.enabled {
+        return;
+    }
+
+    // If the packet is from the server's own MAC, ignore it (prevents the server getting a lease)
+    if let Some(ref smac) = server_mac {
+        if !smac.is_empty() && msg.mac.eq_ignore_ascii_case(smac) {
+            logging::log_debug(&format!("Ignoring DHCP packet from server MAC {}", smac));
+            return;
+        }
+    }
+
+    logging::log_debug(&format!("DHCP message type {} received from MAC {}", msg.msg_type, msg.mac));
+
+    let scopes = setup.dhcp_scope_configs();
+    let scope = match select_scope(&scopes, &msg, socket.ingress.as_ref()) {
+        Some(s) => s,
+        None => {
+            logging::log_debug(&format!("No DHCP scope serves {} (giaddr {}), ignoring", msg.mac, msg.giaddr));
+            return;
+        }
+    };
+
+    if msg.msg_type == DHCPDISCOVER || msg.msg_type == DHCPREQUEST {
+        claim_relay_reservation(&msg, scope);
+    }
+
+    match msg.msg_type {
+        DHCPDISCOVER => handle_discover(socket, &msg, scope, app),
+        DHCPREQUEST => handle_request(socket, &msg, scope, app),
+        DHCPDECLINE => handle_decline(&msg, scope, socket.ingress.as_ref(), app),
+        DHCPRELEASE => handle_release(&msg, scope, socket.ingress.as_ref()),
+        DHCPINFORM => {
+            // INFORM: client already has an address, it only wants configuration
+            send_dhcp_reply(socket, &msg, None, DHCPACK, scope);
+            logging::log_debug(&format!("DHCP INFORM answered for {} ({})", msg.ciaddr, msg.mac));
+        }
+        other => logging::log_debug(&format!("Ignoring unsupported DHCP message type {} from {}", other, msg.mac)),
+    }
+}
+
+/// Pick the scope a message belongs to: a relayed message by its giaddr, otherwise the scope
+/// bound to the interface it arrived on, then a scope bound to 0.0.0.0. Interfaces without a
+/// scope (e.g. the WAN) are not served. Where the interface is unknown, every scope is a
+/// candidate. Among candidates a renewing client's own address decides, then the first one.
+fn select_scope<'a>(scopes: &'a [config::DhcpConfig], msg: &DhcpMessage, ingress: Option<&Ingress>) -> Option<&'a config::DhcpConfig> {
+    if msg.is_relayed() {
+        return scopes.iter().find(|s| in_subnet(msg.giaddr, s));
+    }
+
+    let candidates: Vec<&config::DhcpConfig> = match ingress {
+        Some(ingress) => {
+            let on_interface: Vec<&config::DhcpConfig> = scopes.iter().filter(|s| ingress.serves(&s.bind_address)).collect();
+            if on_interface.is_empty() {
+                scopes.iter().filter(|s| is_wildcard(&s.bind_address)).collect()
+            } else {
+                on_interface
+            }
+        }
+        None => scopes.iter().collect(),
+    };
+    candidates.iter()
+        .find(|s| !msg.ciaddr.is_unspecified() && in_subnet(msg.ciaddr, s))
+        .or_else(|| candidates.first())
+        .copied()
+}
+
+/// A static lease reserved for a relay port (option 82) follows whichever device sits on
+/// that port: rebind it to the requesting MAC so the normal MAC-based checks apply.
+fn claim_relay_reservation(msg: &DhcpMessage, config: &config::DhcpConfig) {
+    let relay = match msg.relay_info() {
+        Some(r) => r,
+        None => return,
+    };
+    let mut table = LEASE_TABLE.write().unwrap();
+    let reservation = match table.reservation_for_relay(&relay, config) {
+        Some(l) if !l.mac.eq_ignore_ascii_case(&msg.mac) => l.clone(),
+        _ => return,
+    };
+    logging::log_info(&format!("DHCP reservation {} for relay port {:?} moved from {} to {}",
+        reservation.ip, relay.circuit_id, reservation.mac, msg.mac));
+    table.insert(Lease { mac: msg.mac.clone(), ..reservation });
+    persist_or_log(&table);
+}
+
+/// DISCOVER -> Offer an IP but do NOT persist the lease yet
+fn handle_discover(socket: &Responder, msg: &DhcpMessage, config: &config::DhcpConfig, app: &Option<AppHandle>) {
+    let offered_ip = {
+        let table = LEASE_TABLE.read().unwrap();
+        let has_lease = table.by_mac_in_scope(&msg.mac, config).is_some();
+        // Honour a requested address (option 50) from a client without a binding if we can
+        msg.requested_ip.clone()
+            .filter(|ip| !has_lease && validate_requested_ip(&table, ip, &msg.mac, config, now_secs()).is_ok())
+    }.or_else(|| find_free_ip(config, Some(&msg.mac)));
+
+    if config.conflict_probe == config::ConflictProbe::Off {
+        send_offer(socket, msg, offered_ip, config);
+        return;
+    }
+
+    // A probe can take seconds, so it runs on a worker and the listener keeps serving other clients
+    let probing = match ProbeGuard::begin(&msg.mac) {
+        Some(guard) => guard,
+        None => {
+            logging::log_debug(&format!("DHCP DISCOVER from {} ignored, its conflict probe is still running", msg.mac));
+            return;
+        }
+    };
+    let (socket, msg, config, app) = (socket.clone(), msg.clone(), config.clone(), app.clone());
+    thread::spawn(move || {
+        let _probing = probing;
+        let (offered_ip, conflicts) = probe_offer(offered_ip, &msg.mac, &config, &LEASE_TABLE, |ip| probe_address(ip, &msg.mac, &config));
+        if !conflicts.is_empty() {
+            persist_or_log(&LEASE_TABLE.read().unwrap());
+        }
+        for (ip, responder) in conflicts {
+            report_conflict(&ip, &responder, &config, &app);
+        }
+        send_offer(&socket, &msg, offered_ip, &config);
+    });
+}
+
+/// Marks a client's conflict probe as running until dropped
+struct ProbeGuard(String);
+
+impl ProbeGuard {
+    /// None if a probe for `mac` is already running
+    fn begin(mac: &str) -> Option<Self> {
+        PROBING.lock().unwrap().insert(mac.to_string()).then(|| Self(mac.to_string()))
+    }
+}
+
+impl Drop for ProbeGuard {
+    fn drop(&mut self) {
+        PROBING.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Make sure nobody with a manually configured address already sits on the address we picked,
+/// quarantining it and moving on to another free one (up to `MAX_CONFLICT_PROBES` in all) when
+/// `probe` finds somebody. Returns the address to offer and the conflicts as (address, responder MAC).
+fn probe_offer(
+    mut offered_ip: Option<String>,
+    mac: &str,
+    config: &config::DhcpConfig,
+    table: &RwLock<LeaseTable>,
+    mut probe: impl FnMut(&str) -> Option<String>,
+) -> (Option<String>, Vec<(String, String)>) {
+    let mut conflicts = Vec::new();
+    while let Some(ip) = offered_ip.clone() {
+        let responder = match probe(&ip) {
+            Some(r) => r,
+            None => break,
+        };
+        table.write().unwrap().insert(conflict_lease(&ip, &responder, config));
+        conflicts.push((ip, responder));
+        offered_ip = if conflicts.len() < MAX_CONFLICT_PROBES { free_ip_in(&table.read().unwrap(), config, Some(mac)) } else { None };
+    }
+    (offered_ip, conflicts)
+}
+
+fn send_offer(socket: &Responder, msg: &DhcpMessage, offered_ip: Option<String>, config: &config::DhcpConfig) {
+    match offered_ip {
+        Some(ip) => {
+            send_dhcp_reply(socket, msg, Some(&ip), DHCPOFFER, config);
+            logging::log_info(&format!("DHCP OFFER sent to {} ({})", ip, msg.mac));
+        }
+        None => logging::log_warn(&format!("DHCP pool exhausted, no address to offer {}", msg.mac)),
+    }
+}
+
+/// REQUEST -> ACK the address the client asks for, or NAK it if we can't give it out
+fn handle_request(socket: &Responder, msg: &DhcpMessage, config: &config::DhcpConfig, app: &Option<AppHandle>) {
+    let state = msg.request_state();
+    let requested = match state {
+        RequestState::Selecting => {
+            // The client broadcasts its choice; if it picked another server, drop our offer
+            if !is_our_server_id(msg, config, socket.ingress.as_ref()) {
+                logging::log_debug(&format!("Client {} selected another DHCP server", msg.mac));
+                return;
+            }
+            msg.requested_ip.clone()
+        }
+        RequestState::InitReboot => msg.requested_ip.clone(),
+        RequestState::Renewing => Some(msg.ciaddr.to_string()),
+    };
+    let requested = match requested {
+        Some(ip) => ip,
+        None => {
+            logging::log_debug(&format!("Malformed DHCP REQUEST from {} ({:?} without an address)", msg.mac, state));
+            return;
+        }
+    };
+    // A rebooting client on the right subnet that we have no record of may belong to another server (RFC 2131 4.3.2)
+    if state == RequestState::InitReboot && !init_reboot_is_ours(&LEASE_TABLE.read().unwrap(), &requested, &msg.mac, config) {
+        logging::log_debug(&format!("Ignoring DHCP INIT-REBOOT from {} for {}: no record of this client", msg.mac, requested));
+        return;
+    }
+
+    let verdict = {
+        let table = LEASE_TABLE.read().unwrap();
+        validate_requested_ip(&table, &requested, &msg.mac, config, now_secs())
+    };
+    if let Err(reason) = verdict {
+        send_dhcp_nak(socket, msg, reason, config);
+        logging::log_info(&format!("DHCP NAK sent to {} for {} ({:?}): {}", msg.mac, requested, state, reason));
+        return;
+    }
+
+    let renewal = LEASE_TABLE.read().unwrap().is_held_by(&requested, &msg.mac, now_secs());
+
+    match allocate_ip_for_requested(msg, config, &requested) {
+        Some(ip) => {
+            send_dhcp_reply(socket, msg, Some(&ip), DHCPACK, config);
+            let text = format!("New Lease: {} ({}) - {}", msg.hostname, ip, msg.mac);
+            logging::log_info(&text);
+            if let Some(h) = app {
+                notify::send_notification(h, "DHCP Lease", &text, "info");
+            }
+            if let Some(lease) = LEASE_TABLE.read().unwrap().by_ip(&ip) {
+                hooks::fire(if renewal { LeaseEvent::LeaseRenewed } else { LeaseEvent::LeaseGranted }, lease);
+            }
+        }
+        None => {
+            // Lost a race with another client between validation and allocation
+            send_dhcp_nak(socket, msg, "address no longer available", config);
+            logging::log_info(&format!("DHCP NAK sent to {} for {}: address no longer available", msg.mac, requested));
+        }
+    }
+}
+
+/// RELEASE -> The client gives its address back
+fn handle_release(msg: &DhcpMessage, config: &config::DhcpConfig, ingress: Option<&Ingress>) {
+    let ip = msg.ciaddr.to_string();
+    if !is_our_server_id(msg, config, ingress) {
+        logging::log_debug(&format!("Ignoring DHCP RELEASE of {} from {} addressed to another server", ip, msg.mac));
+        return;
+    }
+    let mut table = LEASE_TABLE.write().unwrap();
+    let owned = table.by_ip(&ip).is_some_and(|l| {
+        !l.static_lease && l.state == LeaseState::Active && l.mac.eq_ignore_ascii_case(&msg.mac)
+    });
+    if owned {
+        if let Some(lease) = table.remove(&ip) {
+            hooks::fire(LeaseEvent::LeaseReleased, &lease);
+        }
+        persist_or_log(&table);
+        logging::log_info(&format!("DHCP RELEASE: {} freed by {}", ip, msg.mac));
+    } else {
+        logging::log_debug(&format!("Ignoring DHCP RELEASE of {} from {} (no matching dynamic lease)", ip, msg.mac));
+    }
+}
+
+/// DECLINE -> The client found the address already in use (ARP probe); quarantine it
+fn handle_decline(msg: &DhcpMessage, config: &config::DhcpConfig, ingress: Option<&Ingress>, app: &Option<AppHandle>) {
+    let ip = match &msg.requested_ip {
+        Some(ip) => ip.clone(),
+        None => return,
+    };
+
+    {
+        let mut table = LEASE_TABLE.write().unwrap();
+        if table.by_ip(&ip).is_some_and(|l| l.static_lease) {
+            logging::log_warn(&format!("DHCP DECLINE for reserved address {} from {}", ip, msg.mac));
+            return;
+        }
+        // Otherwise any client could take any address out of the pool
+        if !may_decline(&table, msg, &ip, config, ingress, now_secs()) {
+            logging::log_debug(&format!("Ignoring DHCP DECLINE of {} from {}: not its lease from this server", ip, msg.mac));
+            return;
+        }
+        let lease = Lease {
+            ip: ip.clone(),
+            mac: msg.mac.clone(),
+            hostname: msg.hostname.clone(),
+            expires_at: Some(now_secs() + config.decline_quarantine_secs),
+            static_lease: false,
+            state: LeaseState::Declined,
+            options: BTreeMap::new(),
+            relay_info: msg.relay_info(),
+        };
+        hooks::fire(LeaseEvent::LeaseDeclined, &lease);
+        table.insert(lease);
+        persist_or_log(&table);
+    }
+
+    let text = format!("{} declined {} (address in use); quarantined for {}s", msg.mac, ip, config.decline_quarantine_secs);
+    logging::log_warn(&format!("DHCP DECLINE: {}", text));
+    if let Some(h) = app {
+        notify::send_notification(h, "DHCP Address Conflict", &text, "warning");
+    }
+}
+
+/// A DECLINE counts only if it names us as the server (option 54) and comes from the client the address was leased to
+fn may_decline(table: &LeaseTable, msg: &DhcpMessage, ip: &str, config: &config::DhcpConfig, ingress: Option<&Ingress>, now: u64) -> bool {
+    is_our_server_id(msg, config, ingress) && table.is_held_by(ip, &msg.mac, now)
+}
+
+/// Whether we answer an INIT-REBOOT request: always with a NAK for the wrong subnet, otherwise only for clients with a lease here
+fn init_reboot_is_ours(table: &LeaseTable, requested: &str, mac: &str, config: &config::DhcpConfig) -> bool {
+    match requested.parse::<Ipv4Addr>() {
+        Ok(addr) if in_subnet(addr, config) => table.by_mac_in_scope(mac, config).is_some(),
+        _ => true,
+    }
+}
+
+/// Check whether `ip` is already in use by someone other than `client_mac`, using the
+/// configured probe. Returns the responder's MAC (empty if unknown) when it is.
+fn probe_address(ip: &str, client_mac: &str, config: &config::DhcpConfig) -> Option<String> {
+    if config.conflict_probe == config::ConflictProbe::Off {
+        return None;
+    }
+    // The client may already be using an address it holds a lease for
+    let held = LEASE_TABLE.read().unwrap().by_ip(ip)
+        .is_some_and(|l| l.state == LeaseState::Active && l.mac.eq_ignore_ascii_case(client_mac));
+    if held {
+        return None;
+    }
+
+    if config.conflict_probe == config::ConflictProbe::Ping && !monitor::ping_host(ip, CONFLICT_PING_TIMEOUT_MS) {
+        return None;
+    }
+    // After a successful ping the responder is in the ARP cache too
+    let arp_mac = monitor::list_arp_impl(false).into_iter()
+        .find(|e| e.ip == ip)
+        .map(|e| e.mac.to_lowercase().replace('-', ":"))
+        .filter(|mac| mac != "ff:ff:ff:ff:ff:ff" && mac != "00:00:00:00:00:00");
+    match (config.conflict_probe, arp_mac) {
+        (_, Some(mac)) if mac.eq_ignore_ascii_case(client_mac) => None,
+        (_, Some(mac)) => Some(mac),
+        (config::ConflictProbe::Ping, None) => Some(String::new()),
+        _ => None,
+    }
+}
+
+/// Holds an address found in use out of the pool for `conflict_quarantine_secs`
+fn conflict_lease(ip: &str, responder_mac: &str, config: &config::DhcpConfig) -> Lease {
+    Lease {
+        ip: ip.to_string(),
+        mac: responder_mac.to_string(),
+        hostname: "Unknown".to_string(),
+        expires_at: Some(now_secs() + config.conflict_quarantine_secs),
+        static_lease: false,
+        state: LeaseState::Conflict,
+        options: BTreeMap::new(),
+        relay_info: None,
+    }
+}
+
+fn report_conflict(ip: &str, responder_mac: &str, config: &config::DhcpConfig, app: &Option<AppHandle>) {
+    let who = if responder_mac.is_empty() { "an unknown device".to_string() } else { responder_mac.to_string() };
+    let text = format!("{} is already in use by {}; quarantined for {}s", ip, who, config.conflict_quarantine_secs);
+    logging::log_warn(&format!("DHCP conflict: {}", text));
+    if let Some(h) = app {
+        notify::send_notification(h, "DHCP Address Conflict", &text, "warning");
+    }
+}
+
+/// A scope bound to 0.0.0.0 serves interfaces that have no scope of their own
+fn is_wildcard(bind_address: &str) -> bool {
+    bind_address.trim().parse::<Ipv4Addr>().is_ok_and(|ip| ip.is_unspecified())
+}
+
+/// Our own address as the scope's clients reach it, sent as the Server Identifier (option 54) and SIADDR:
+/// the scope's address on the interface the request came in on, else its bind address, else the address
+/// we route to the relay from. The gateway is only a last resort, since on a relayed scope it is the relay.
+fn server_identifier(config: &config::DhcpConfig, req: &DhcpMessage, ingress: Option<&Ingress>) -> Ipv4Addr {
+    let bound = bound_address(config);
+    if let Some(ingress) = ingress.filter(|i| !i.addresses.is_empty()) {
+        let own = ingress.addresses.iter()
+            .find(|a| Some(**a) == bound)
+            .or_else(|| ingress.addresses.iter().find(|a| !req.is_relayed() && in_subnet(**a, config)));
+        return *own.unwrap_or(&ingress.addresses[0]);
+    }
+    bound.or_else(|| if req.is_relayed() { route_source(req.giaddr) } else { None })
+        .unwrap_or_else(|| config.gateway.parse().unwrap_or(Ipv4Addr::UNSPECIFIED))
+}
+
+/// The scope's bind address, if it is a specific IPv4 address
+fn bound_address(config: &config::DhcpConfig) -> Option<Ipv4Addr> {
+    config.bind_address.trim().parse::<Ipv4Addr>().ok().filter(|ip| !ip.is_unspecified())
+}
+
+/// The local address the kernel would use to reach `dest`; connecting a UDP socket sends nothing
+fn route_source(dest: Ipv4Addr) -> Option<Ipv4Addr> {
+    let probe = UdpSocket::bind("0.0.0.0:0").ok()?;
+    probe.connect(SocketAddrV4::new(dest, 67)).ok()?;
+    match probe.local_addr().ok()?.ip() {
+        IpAddr::V4(ip) if !ip.is_unspecified() => Some(ip),
+        _ => None,
+    }
+}
+
+/// Whether option 54 names us. Clients of a local scope may still quote the gateway from before
+/// the server identifier stopped being the gateway.
+fn is_our_server_id(msg: &DhcpMessage, config: &config::DhcpConfig, ingress: Option<&Ingress>) -> bool {
+    match msg.server_id {
+        Some(id) => id == server_identifier(config, msg, ingress)
+            || bound_address(config) == Some(id)
+            || ingress.is_some_and(|i| i.addresses.contains(&id))
+            || (!msg.is_relayed() && config.gateway.parse::<Ipv4Addr>() == Ok(id)),
+        None => false,
+    }
+}
+
+fn in_subnet(addr: Ipv4Addr, config: &config::DhcpConfig) -> bool {
+    let mask = config.subnet_mask.parse::<Ipv4Addr>().map(u32::from).unwrap_or(0);
+    let gateway = config.gateway.parse::<Ipv4Addr>().unwrap_or(Ipv4Addr::UNSPECIFIED);
+    let network = u32::from(gateway) & mask;
+    u32::from(addr) & mask == network
+}
+
+fn in_pool(addr: Ipv4Addr, config: &config::DhcpConfig) -> bool {
+    let start = config.range_start.parse::<Ipv4Addr>();
+    let end = config.range_end.parse::<Ipv4Addr>();
+    let ip = addr.to_string();
+    match (start, end) {
+        (Ok(s), Ok(e)) => addr >= s && addr <= e && ip != config.gateway && ip != config.bind_address,
+        _ => false,
+    }
+}
+
+/// Decide whether `mac` may have `ip`. The error explains the NAK.
+fn validate_requested_ip(table: &LeaseTable, ip: &str, mac: &str, config: &config::DhcpConfig, now: u64) -> Result<(), &'static str> {
+    let addr: Ipv4Addr = ip.parse().map_err(|_| "malformed address")?;
+
+    if let Some(existing) = table.by_ip(ip) {
+        if existing.static_lease {
+            return if existing.mac.eq_ignore_ascii_case(mac) { Ok(()) } else { Err("address reserved for another client") };
+        }
+    }
+    if let Some(own) = table.by_mac_in_scope(mac, config) {
+        if own.static_lease && own.ip != ip {
+            return Err("client has a reservation for a different address");
+        }
+    }
+    if !in_subnet(addr, config) {
+        return Err("address is on the wrong subnet");
+    }
+    if !in_pool(addr, config) {
+        return Err("address is outside the pool");
+    }
+    if table.is_taken(ip, Some(mac), now) {
+        return Err("address is leased to another client");
+    }
+    Ok(())
+}
+
+/// Find a free IP in the pool without persisting a lease. If the MAC already has a lease, return it.
+fn find_free_ip(config: &config::DhcpConfig, mac_opt: Option<&str>) -> Option<String> {
+    free_ip_in(&LEASE_TABLE.read().unwrap(), config, mac_opt)
+}
+
+fn free_ip_in(table: &LeaseTable, config: &config::DhcpConfig, mac_opt: Option<&str>) -> Option<String> {
+    // If MAC already has a lease in this scope, return that IP
+    if let Some(mac) = mac_opt {
+        if let Some(l) = table.by_mac_in_scope(mac, config) {
+            return Some(l.ip.clone());
+        }
+    }
+
+    table.pick_free_ip(config, now_secs())
+}
+
+/// Allocate (or renew) a specific requested IP for a MAC and persist the lease. Returns the IP if successful.
+fn allocate_ip_for_requested(msg: &DhcpMessage, config: &config::DhcpConfig, requested_ip: &str) -> Option<String> {
+    let (mac, hostname) = (msg.mac.as_str(), msg.hostname.as_str());
+    let now = now_secs();
+    let mut table = LEASE_TABLE.write().unwrap();
+
+    // A client's own reservation is handed out as-is
+    if table.by_ip(requested_ip).is_some_and(|l| l.static_lease && l.mac.eq_ignore_ascii_case(mac)) {
+        return Some(requested_ip.to_string());
+    }
+
+    // If requested IP is still leased to another MAC, fail
+    if table.is_taken(requested_ip, Some(mac), now) {
+        return None;
+    }
+
+    table.insert(Lease {
+        ip: requested_ip.to_string(),
+        mac: mac.to_string(),
+        hostname: hostname.to_string(),
+        expires_at: Some(now + config.lease_time),
+        static_lease: false,
+        state: LeaseState::Active,
+        options: BTreeMap::new(),
+        relay_info: msg.relay_info(),
+    });
+    persist_or_log(&table);
+    logging::log_debug(&format!("Allocated IP {} to MAC {} (hostname={})", requested_ip, mac, hostname));
+    Some(requested_ip.to_string())
+}
+
+/// Options that go out whether or not the client listed them in its Parameter Request List
+const ALWAYS_SENT: [u8; 3] = [options::OPT_SUBNET_MASK, options::OPT_ROUTER, options::OPT_DNS_SERVERS];
+
+/// Build the option list for an OFFER/ACK: built-in network settings, overlaid by the
+/// pool's `options`, then matching vendor classes (option 60), then the client's static lease.
+fn reply_options(req: &DhcpMessage, yiaddr: Option<&str>, msg_type: u8, config: &config::DhcpConfig, server_id: Ipv4Addr) -> Vec<DhcpOption> {
+    let mut head = vec![DhcpOption::MessageType(msg_type), DhcpOption::ServerId(server_id)];
+
+    // Lease times are never sent in reply to INFORM
+    if yiaddr.is_some() {
+        let lease = config.lease_time.min(u32::MAX as u64) as u32;
+        head.push(DhcpOption::LeaseTime(lease));
+        head.push(DhcpOption::RenewalTime(lease / 2));
+        head.push(DhcpOption::RebindingTime((lease as u64 * 7 / 8) as u32));
+    }
+
+    // If Captive Portal is enabled, force DNS to be us
+    let dns_list: Vec<Ipv4Addr> = if config.captive_portal {
+        vec![server_id]
+    } else {
+        config.dns_servers.iter().filter_map(|d| d.parse().ok()).collect()
+    };
+    let mut body = vec![
+        DhcpOption::SubnetMask(config.subnet_mask.parse().unwrap_or(Ipv4Addr::UNSPECIFIED)),
+        DhcpOption::Router(vec![config.gateway.parse().unwrap_or(Ipv4Addr::UNSPECIFIED)]),
+    ];
+    if !dns_list.is_empty() {
+        body.push(DhcpOption::DnsServers(dns_list));
+    }
+
+    let host_options = LEASE_TABLE.read().unwrap()
+        .by_mac_in_scope(&req.mac, config)
+        .filter(|l| l.static_lease)
+        .map(|l| l.options.clone())
+        .unwrap_or_default();
+    let mut layers: Vec<&BTreeMap<String, String>> = vec![&config.options];
+    if let Some(class) = &req.vendor_class {
+        layers.extend(config.vendor_classes.iter()
+            .filter(|vc| !vc.vendor_class.is_empty() && class.starts_with(&vc.vendor_class))
+            .map(|vc| &vc.options));
+    }
+    layers.push(&host_options);
+
+    let (configured, errors) = resolve_options(&layers);
+    for e in errors {
+        logging::log_warn(&e);
+    }
+    for opt in configured {
+        match body.iter_mut().find(|o| o.code() == opt.code()) {
+            Some(existing) => *existing = opt,
+            None => body.push(opt),
+        }
+    }
+
+    // Honour the Parameter Request List (55): only send what was asked for, in the order asked
+    let prl = &req.param_request_list;
+    if !prl.is_empty() {
+        body.retain(|o| ALWAYS_SENT.contains(&o.code()) || prl.contains(&o.code()));
+        body.sort_by_key(|o| prl.iter().position(|c| *c == o.code()).unwrap_or(usize::MAX));
+    }
+
+    head.extend(body);
+    head
+}
+
+fn send_dhcp_reply(socket: &Responder, req: &DhcpMessage, yiaddr: Option<&str>, msg_type: u8, config: &config::DhcpConfig) {
+    let server_id = server_identifier(config, req, socket.ingress.as_ref());
+    let opts = reply_options(req, yiaddr, msg_type, config, server_id);
+    transmit_reply(socket, req, msg_type, &build_reply(req, yiaddr, msg_type, &opts, server_id));
+}
+
+fn send_dhcp_nak(socket: &Responder, req: &DhcpMessage, reason: &str, config: &config::DhcpConfig) {
+    let server_id = server_identifier(config, req, socket.ingress.as_ref());
+    let opts = vec![
+        DhcpOption::MessageType(DHCPNAK),
+        DhcpOption::ServerId(server_id),
+        DhcpOption::Message(reason.to_string()),
+    ];
+    transmit_reply(socket, req, DHCPNAK, &build_reply(req, None, DHCPNAK, &opts, server_id));
+}
+
+fn build_reply(req: &DhcpMessage, yiaddr: Option<&str>, msg_type: u8, opts: &[DhcpOption], server_id: Ipv4Addr) -> Vec<u8> {
+    let mut packet = vec![0u8; 240];
+    
+    packet[0] = 2; // BootReply
+    packet[1] = 1; // Ethernet
+    packet[2] = 6; // Hlen
+    packet[3] = 0; // Hops
+    
+    // XID
+    packet[4..8].copy_from_slice(&req.xid);
+
+    // FLAGS (keep the client's broadcast bit; a relay must broadcast NAKs onto the client's segment)
+    packet[10..12].copy_from_slice(&req.flags);
+    if msg_type == DHCPNAK && req.is_relayed() {
+        packet[10] |= 0x80;
+    }
+
+    // CIADDR (echoed back on ACKs to clients that already have an address)
+    if msg_type == DHCPACK {
+        packet[12..16].copy_from_slice(&req.ciaddr.octets());
+    }
+    
+    // YIADDR (Your IP)
+    if let Some(ip) = yiaddr.and_then(|ip| ip.parse::<Ipv4Addr>().ok()) {
+        packet[16..20].copy_from_slice(&ip.octets());
+    }
+    
+    // SIADDR (Server IP - us)
+    if msg_type != DHCPNAK {
+        packet[20..24].copy_from_slice(&server_id.octets());
+    }
+    
+    // GIADDR (Relay agent, so it knows which segment to forward the reply to)
+    packet[24..28].copy_from_slice(&req.giaddr.octets());
+
+    // CHADDR (Client MAC)
+    packet[28..34].copy_from_slice(&req.chaddr);
+    
+    // Magic Cookie
+    packet[236] = 99; packet[237] = 130; packet[238] = 83; packet[239] = 99;
+
+    // Option 82 goes back to the relay verbatim, as the last option (RFC 3046)
+    let relay_echo = req.relay_agent_info.clone().map(DhcpOption::RelayAgentInfo);
+    let reserved = relay_echo.as_ref().map_or(0, encoded_len);
+
+    // Options, bounded by the client's maximum message size (option 57, minus IP/UDP headers)
+    let max_len = req.max_message_size.map_or(576, |m| (m as usize).max(576)) - 28;
+    for opt in opts {
+        if packet.len() + encoded_len(opt) + reserved + 1 > max_len {
+            logging::log_debug(&format!("Dropping DHCP option {} for {}: reply too large", opt.code(), req.mac));
+            continue;
+        }
+        encode_option(opt, &mut packet);
+    }
+    if let Some(opt) = &relay_echo {
+        encode_option(opt, &mut packet);
+    }
+    packet.push(options::OPT_END);
+
+    // Pad to the 300-byte BOOTP minimum some clients insist on
+    if packet.len() < 300 {
+        packet.resize(300, 0);
+    }
+    packet
+}
+
+fn transmit_reply(socket: &Responder, req: &DhcpMessage, msg_type: u8, packet: &[u8]) {
+    // Relayed requests are answered to the relay's server port. Otherwise NAKs are always
+    // broadcast and clients that already own an address get a unicast reply.
+    let dest = if req.is_relayed() {
+        SocketAddrV4::new(req.giaddr, 67)
+    } else if msg_type != DHCPNAK && !req.ciaddr.is_unspecified() {
+        SocketAddrV4::new(req.ciaddr, 68)
+    } else {
+        SocketAddrV4::new(Ipv4Addr::BROADCAST, 68)
+    };
+    
+    match socket.send_to(packet, dest) {
+        Ok(n) => logging::log_debug(&format!("DHCP reply (type {}) sent to {} ({} bytes)", msg_type, dest, n)),
+        Err(e) => logging::log_error(&format!("Failed sending DHCP reply: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lease(ip: &str, mac: &str, expires_at: Option<u64>, static_lease: bool) -> Lease {
+        Lease { ip: ip.into(), mac: mac.into(), hostname: "host".into(), expires_at, static_lease, state: LeaseState::Active, options: BTreeMap::new(), relay_info: None }
+    }
+
+    #[test]
+    fn test_legacy_expiry_formats() {
+        let json = r#"[
+            {"ip":"10.0.0.100","mac":"aa","hostname":"a","expires_at":"never","static_lease":true},
+            {"ip":"10.0.0.101","mac":"bb","hostname":"b","expires_at":"1700000000","static_lease":false},
+            {"ip":"10.0.0.102","mac":"cc","hostname":"c","expires_at":1700000500,"static_lease":false}
+        ]"#;
+        let leases: Vec<Lease> = serde_json::from_str(json).unwrap();
+        assert_eq!(leases[0].expires_at, None);
+        assert_eq!(leases[1].expires_at, Some(1700000000));
+        assert_eq!(leases[2].expires_at, Some(1700000500));
+    }
+
+    #[test]
+    fn test_reap_and_reuse_expired() {
+        let mut table = LeaseTable {
+            leases: vec![
+                lease("10.0.0.100", "aa", None, true),
+                lease("10.0.0.101", "bb", Some(100), false),
+                lease("10.0.0.102", "cc", Some(500), false),
+            ],
+        };
+        assert!(!table.is_taken("10.0.0.101", None, 200));
+        assert!(table.is_taken("10.0.0.102", None, 200));
+        assert!(!table.is_taken("10.0.0.102", Some("CC"), 200));
+
+        let reclaimed = table.reap_expired(200);
+        assert_eq!(reclaimed.len(), 1);
+        assert_eq!(reclaimed[0].ip, "10.0.0.101");
+        assert_eq!(table.all().len(), 2);
+
+        let cfg = config::DhcpConfig::default();
+        assert_eq!(table.pick_free_ip(&cfg, 200).as_deref(), Some("10.0.0.101"));
+    }
+
+    #[test]
+    fn test_validate_requested_ip() {
+        let cfg = config::DhcpConfig::default();
+        let mut table = LeaseTable { leases: vec![lease("10.0.0.150", "bb", Some(500), false)] };
+        assert!(validate_requested_ip(&table, "10.0.0.120", "aa", &cfg, 200).is_ok());
+        assert!(validate_requested_ip(&table, "192.168.1.20", "aa", &cfg, 200).is_err()); // wrong subnet
+        assert!(validate_requested_ip(&table, "10.0.0.250", "aa", &cfg, 200).is_err()); // outside pool
+        assert!(validate_requested_ip(&table, "10.0.0.150", "aa", &cfg, 200).is_err()); // stolen
+        assert!(validate_requested_ip(&table, "10.0.0.150", "bb", &cfg, 200).is_ok()); // renewal
+
+        table.insert(Lease { state: LeaseState::Declined, ..lease("10.0.0.120", "aa", Some(900), false) });
+        assert!(validate_requested_ip(&table, "10.0.0.120", "aa", &cfg, 200).is_err()); // quarantined
+        assert_eq!(table.by_mac_in_scope("bb", &cfg).map(|l| l.ip.as_str()), Some("10.0.0.150"));
+    }
+
+    #[test]
+    fn test_decline_and_init_reboot() {
+        let cfg = config::DhcpConfig::default();
+        let table = LeaseTable { leases: vec![lease("10.0.0.150", "aa:aa:aa:aa:aa:aa", Some(500), false)] };
+        let mut packet = vec![0u8; 250];
+        packet[0] = 1;
+        packet[2] = 6;
+        packet[28..34].copy_from_slice(&[0xaa; 6]);
+        packet[236..240].copy_from_slice(&[99, 130, 83, 99]);
+        packet[240..243].copy_from_slice(&[53, 1, DHCPDECLINE]);
+        packet[243..249].copy_from_slice(&[54, 4, 10, 0, 0, 1]);
+        packet[249] = 255;
+        let msg = DhcpMessage::parse(&packet).unwrap();
+        assert!(may_decline(&table, &msg, "10.0.0.150", &cfg, None, 200));
+        assert!(!may_decline(&table, &msg, "10.0.0.151", &cfg, None, 200)); // Not its lease
+        assert!(!may_decline(&table, &msg, "10.0.0.150", &cfg, None, 600)); // Expired
+
+        packet[28..34].copy_from_slice(&[0xbb; 6]);
+        assert!(!may_decline(&table, &DhcpMessage::parse(&packet).unwrap(), "10.0.0.150", &cfg, None, 200)); // Someone else's lease
+        packet[28..34].copy_from_slice(&[0xaa; 6]);
+        packet[245..249].copy_from_slice(&[10, 0, 0, 9]);
+        assert!(!may_decline(&table, &DhcpMessage::parse(&packet).unwrap(), "10.0.0.150", &cfg, None, 200)); // Another server
+
+        assert!(init_reboot_is_ours(&table, "10.0.0.150", "aa:aa:aa:aa:aa:aa", &cfg));
+        assert!(!init_reboot_is_ours(&table, "10.0.0.160", "cc:cc:cc:cc:cc:cc", &cfg)); // Unknown client: silent
+        assert!(init_reboot_is_ours(&table, "192.168.1.20", "cc:cc:cc:cc:cc:cc", &cfg)); // Wrong subnet: NAK
+    }
+
+    #[test]
+    fn test_request_state() {
+        let mut packet = vec![0u8; 244];
+        packet[0] = 1;
+        packet[2] = 6;
+        packet[236..240].copy_from_slice(&[99, 130, 83, 99]);
+        packet[240..243].copy_from_slice(&[53, 1, DHCPREQUEST]);
+        packet[243] = 255;
+        let msg = DhcpMessage::parse(&packet).unwrap();
+        assert_eq!(msg.request_state(), RequestState::InitReboot);
+
+        packet[12..16].copy_from_slice(&[10, 0, 0, 120]);
+        assert_eq!(DhcpMessage::parse(&packet).unwrap().request_state(), RequestState::Renewing);
+    }
+
+    #[test]
+    fn test_select_scope() {
+        let staff = config::DhcpConfig::default();
+        let guest = staff.for_scope(&config::DhcpScope {
+            name: "guest".into(),
+            interface: "192.168.50.1".into(),
+            range_start: "192.168.50.100".into(),
+            range_end: "192.168.50.200".into(),
+            subnet_mask: "255.255.255.0".into(),
+            gateway: "192.168.50.1".into(),
+            dns_servers: vec![],
+            lease_time: 3600,
+            captive_portal: true,
+            options: BTreeMap::new(),
+        });
+        let scopes = vec![staff, guest];
+
+        let mut packet = vec![0u8; 241];
+        packet[0] = 1;
+        packet[2] = 6;
+        packet[236..240].copy_from_slice(&[99, 130, 83, 99]);
+        packet[240] = 255;
+
+        let msg = DhcpMessage::parse(&packet).unwrap();
+        let on = |interface: &str, address: [u8; 4]| Ingress { ifindex: 2, interface: interface.into(), addresses: vec![Ipv4Addr::from(address)] };
+        let (lan, guest_vlan, wan) = (on("eth0", [10, 0, 0, 1]), on("eth0.50", [192, 168, 50, 1]), on("eth1", [203, 0, 113, 5]));
+        assert_eq!(select_scope(&scopes, &msg, Some(&guest_vlan)).unwrap().gateway, "192.168.50.1");
+        assert_eq!(select_scope(&scopes, &msg, Some(&lan)).unwrap().gateway, "10.0.0.1");
+        assert!(select_scope(&scopes, &msg, Some(&wan)).is_none()); // No scope on the WAN
+        assert_eq!(select_scope(&scopes, &msg, None).unwrap().gateway, "10.0.0.1"); // Interface unknown
+
+        // Scopes can name their interface, and a 0.0.0.0 scope takes interfaces without one
+        let mut by_name = scopes.clone();
+        by_name[1].bind_address = "eth0.50".into();
+        by_name[0].bind_address = "0.0.0.0".into();
+        assert_eq!(select_scope(&by_name, &msg, Some(&guest_vlan)).unwrap().gateway, "192.168.50.1");
+        assert_eq!(select_scope(&by_name, &msg, Some(&wan)).unwrap().gateway, "10.0.0.1");
+
+        // A relayed request is matched by its giaddr, whatever interface it came in on
+        packet[24..28].copy_from_slice(&[10, 0, 0, 2]);
+        let relayed = DhcpMessage::parse(&packet).unwrap();
+        assert_eq!(select_scope(&scopes, &relayed, Some(&guest_vlan)).unwrap().gateway, "10.0.0.1");
+        packet[24..28].copy_from_slice(&[172, 16, 0, 1]);
+        assert!(select_scope(&scopes, &DhcpMessage::parse(&packet).unwrap(), Some(&guest_vlan)).is_none());
+    }
+
+    #[test]
+    fn test_conflict_probe() {
+        let cfg = config::DhcpConfig::default();
+        let table = RwLock::new(LeaseTable { leases: vec![] });
+
+        // The first address answers the probe: it is quarantined and the next free one offered
+        let mut probed = Vec::new();
+        let (offered, conflicts) = probe_offer(Some("10.0.0.100".into()), "aa", &cfg, &table, |ip| {
+            probed.push(ip.to_string());
+            (ip == "10.0.0.100").then(|| "de:ad:be:ef:00:01".to_string())
+        });
+        assert_eq!(offered.as_deref(), Some("10.0.0.101"));
+        assert_eq!(probed, vec!["10.0.0.100", "10.0.0.101"]);
+        assert_eq!(conflicts, vec![("10.0.0.100".to_string(), "de:ad:be:ef:00:01".to_string())]);
+        assert_eq!(table.read().unwrap().by_ip("10.0.0.100").map(|l| l.state), Some(LeaseState::Conflict));
+        assert!(table.read().unwrap().is_taken("10.0.0.100", Some("aa"), now_secs()));
+
+        // Every address answering gives up after MAX_CONFLICT_PROBES
+        let (offered, conflicts) = probe_offer(Some("10.0.0.101".into()), "aa", &cfg, &table, |_| Some(String::new()));
+        assert_eq!(offered, None);
+        assert_eq!(conflicts.len(), MAX_CONFLICT_PROBES);
+
+        // A retransmitted DISCOVER doesn't start a second probe while the first runs
+        let probing = ProbeGuard::begin("bb:bb:bb:bb:bb:bb").unwrap();
+        assert!(ProbeGuard::begin("bb:bb:bb:bb:bb:bb").is_none());
+        assert!(ProbeGuard::begin("cc:cc:cc:cc:cc:cc").is_some());
+        drop(probing);
+        assert!(ProbeGuard::begin("bb:bb:bb:bb:bb:bb").is_some());
+    }
+
+    #[test]
+    fn test_relay_scope_server_identifier() {
+        let base = config::DhcpConfig::default();
+        let remote = base.for_scope(&config::DhcpScope {
+            name: "branch".into(),
+            interface: "0.0.0.0".into(),
+            range_start: "10.20.0.100".into(),
+            range_end: "10.20.0.200".into(),
+            subnet_mask: "255.255.255.0".into(),
+            gateway: "10.20.0.1".into(), // The relay router
+            dns_servers: vec![],
+            lease_time: 3600,
+            captive_portal: true,
+            options: BTreeMap::new(),
+        });
+
+        let mut packet = vec![0u8; 244];
+        packet[0] = 1;
+        packet[2] = 6;
+        packet[24..28].copy_from_slice(&[10, 20, 0, 1]);
+        packet[236..240].copy_from_slice(&[99, 130, 83, 99]);
+        packet[240..243].copy_from_slice(&[53, 1, DHCPDISCOVER]);
+        packet[243] = 255;
+        let msg = DhcpMessage::parse(&packet).unwrap();
+        let uplink = Ingress { ifindex: 3, interface: "eth1".into(), addresses: vec![Ipv4Addr::new(192, 168, 1, 1)] };
+
+        let server_id = server_identifier(&remote, &msg, Some(&uplink));
+        assert_eq!(server_id, Ipv4Addr::new(192, 168, 1, 1));
+        let opts = reply_options(&msg, Some("10.20.0.100"), DHCPOFFER, &remote, server_id);
+        assert!(opts.iter().any(|o| matches!(o, DhcpOption::ServerId(ip) if *ip == server_id)));
+        assert!(opts.iter().any(|o| matches!(o, DhcpOption::Router(r) if r == &[Ipv4Addr::new(10, 20, 0, 1)])));
+        assert!(opts.iter().any(|o| matches!(o, DhcpOption::DnsServers(d) if d == &[server_id]))); // Captive DNS is us
+        let reply = build_reply(&msg, Some("10.20.0.100"), DHCPOFFER, &opts, server_id);
+        assert_eq!(&reply[20..24], &[192, 168, 1, 1]); // SIADDR
+        assert_eq!(&reply[24..28], &[10, 20, 0, 1]); // GIADDR
+
+        // A local scope bound to one of our addresses identifies itself by it
+        assert_eq!(server_identifier(&base, &msg, None), Ipv4Addr::new(10, 0, 0, 1));
+        packet[240..243].copy_from_slice(&[53, 1, DHCPRELEASE]);
+        let mut release = packet.clone();
+        release.splice(243..243, [54, 4, 10, 20, 0, 1]);
+        assert!(!is_our_server_id(&DhcpMessage::parse(&release).unwrap(), &remote, Some(&uplink))); // The relay isn't us
+        release[245..249].copy_from_slice(&[192, 168, 1, 1]);
+        assert!(is_our_server_id(&DhcpMessage::parse(&release).unwrap(), &remote, Some(&uplink)));
+    }
+}
@@ -0,0 +1,487 @@
+// src-tauri/src/network/dhcp/options.rs
+// Typed encoder/decoder for DHCP options (RFC 2132, RFC 3442 static routes, RFC 3046 relay info, WPAD)
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+
+// Option codes with a dedicated meaning in the server
+pub const OPT_PAD: u8 = 0;
+pub const OPT_SUBNET_MASK: u8 = 1;
+pub const OPT_ROUTER: u8 = 3;
+pub const OPT_DNS_SERVERS: u8 = 6;
+pub const OPT_HOSTNAME: u8 = 12;
+pub const OPT_DOMAIN_NAME: u8 = 15;
+pub const OPT_INTERFACE_MTU: u8 = 26;
+pub const OPT_BROADCAST: u8 = 28;
+pub const OPT_NTP_SERVERS: u8 = 42;
+pub const OPT_VENDOR_SPECIFIC: u8 = 43;
+pub const OPT_REQUESTED_IP: u8 = 50;
+pub const OPT_LEASE_TIME: u8 = 51;
+pub const OPT_MESSAGE_TYPE: u8 = 53;
+pub const OPT_SERVER_ID: u8 = 54;
+pub const OPT_PARAMETER_REQUEST_LIST: u8 = 55;
+pub const OPT_MESSAGE: u8 = 56;
+pub const OPT_MAX_MESSAGE_SIZE: u8 = 57;
+pub const OPT_RENEWAL_TIME: u8 = 58;
+pub const OPT_REBINDING_TIME: u8 = 59;
+pub const OPT_VENDOR_CLASS: u8 = 60;
+pub const OPT_CLIENT_ID: u8 = 61;
+pub const OPT_TFTP_SERVER: u8 = 66;
+pub const OPT_BOOT_FILE: u8 = 67;
+pub const OPT_RELAY_AGENT_INFO: u8 = 82;
+pub const OPT_CLASSLESS_ROUTES: u8 = 121;
+pub const OPT_WPAD: u8 = 252;
+pub const OPT_END: u8 = 255;
+
+/// Friendly names accepted as keys in the `options` maps of the DHCP config.
+/// Any other option can be configured by its decimal code.
+const OPTION_NAMES: &[(&str, u8)] = &[
+    ("subnet_mask", OPT_SUBNET_MASK),
+    ("router", OPT_ROUTER),
+    ("dns_servers", OPT_DNS_SERVERS),
+    ("domain_name", OPT_DOMAIN_NAME),
+    ("mtu", OPT_INTERFACE_MTU),
+    ("broadcast", OPT_BROADCAST),
+    ("ntp_servers", OPT_NTP_SERVERS),
+    ("vendor_specific", OPT_VENDOR_SPECIFIC),
+    ("tftp_server", OPT_TFTP_SERVER),
+    ("boot_file", OPT_BOOT_FILE),
+    ("static_routes", OPT_CLASSLESS_ROUTES),
+    ("wpad", OPT_WPAD),
+];
+
+/// A classless static route (RFC 3442)
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticRoute {
+    pub destination: Ipv4Addr,
+    pub prefix_len: u8,
+    pub gateway: Ipv4Addr,
+}
+
+/// Relay Agent Information (option 82, RFC 3046) as inserted by a relay or switch.
+/// Printable sub-option values are kept as text, anything else as lowercase hex.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct RelayAgentInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_id: Option<String>, // Sub-option 1, usually the switch port / VLAN
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_id: Option<String>, // Sub-option 2, usually the relay's own identity
+}
+
+impl RelayAgentInfo {
+    const CIRCUIT_ID: u8 = 1;
+    const REMOTE_ID: u8 = 2;
+
+    /// Parse the sub-options of an option 82 payload. Unknown sub-options are ignored.
+    pub fn parse(data: &[u8]) -> RelayAgentInfo {
+        let mut info = RelayAgentInfo::default();
+        let mut i = 0;
+        while i + 1 < data.len() {
+            let (sub, len) = (data[i], data[i + 1] as usize);
+            if i + 2 + len > data.len() { break; }
+            let value = suboption_text(&data[i + 2..i + 2 + len]);
+            match sub {
+                Self::CIRCUIT_ID => info.circuit_id = Some(value),
+                Self::REMOTE_ID => info.remote_id = Some(value),
+                _ => {}
+            }
+            i += 2 + len;
+        }
+        info
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.circuit_id.is_none() && self.remote_id.is_none()
+    }
+
+    /// True if every field set here (used as a match key) equals the client's value
+    pub fn matches(&self, client: &RelayAgentInfo) -> bool {
+        let field = |want: &Option<String>, got: &Option<String>| {
+            want.as_ref().is_none_or(|w| got.as_ref().is_some_and(|g| w.eq_ignore_ascii_case(g)))
+        };
+        !self.is_empty() && field(&self.circuit_id, &client.circuit_id) && field(&self.remote_id, &client.remote_id)
+    }
+}
+
+fn suboption_text(data: &[u8]) -> String {
+    if !data.is_empty() && data.iter().all(|b| b.is_ascii_graphic() || *b == b' ') {
+        String::from_utf8_lossy(data).to_string()
+    } else {
+        data.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// A single decoded DHCP option
+#[derive(Debug, Clone, PartialEq)]
+pub enum DhcpOption {
+    SubnetMask(Ipv4Addr),
+    Router(Vec<Ipv4Addr>),
+    DnsServers(Vec<Ipv4Addr>),
+    Hostname(String),
+    DomainName(String),
+    InterfaceMtu(u16),
+    Broadcast(Ipv4Addr),
+    NtpServers(Vec<Ipv4Addr>),
+    VendorSpecific(Vec<u8>),
+    RequestedIp(Ipv4Addr),
+    LeaseTime(u32),
+    MessageType(u8),
+    ServerId(Ipv4Addr),
+    ParameterRequestList(Vec<u8>),
+    Message(String),
+    MaxMessageSize(u16),
+    RenewalTime(u32),
+    RebindingTime(u32),
+    VendorClass(String),
+    ClientId(Vec<u8>),
+    TftpServer(String),
+    BootFile(String),
+    RelayAgentInfo(Vec<u8>), // Raw sub-options, echoed back to the relay untouched
+    ClasslessRoutes(Vec<StaticRoute>),
+    Wpad(String),
+    Raw(u8, Vec<u8>),
+}
+
+impl DhcpOption {
+    pub fn code(&self) -> u8 {
+        match self {
+            DhcpOption::SubnetMask(_) => OPT_SUBNET_MASK,
+            DhcpOption::Router(_) => OPT_ROUTER,
+            DhcpOption::DnsServers(_) => OPT_DNS_SERVERS,
+            DhcpOption::Hostname(_) => OPT_HOSTNAME,
+            DhcpOption::DomainName(_) => OPT_DOMAIN_NAME,
+            DhcpOption::InterfaceMtu(_) => OPT_INTERFACE_MTU,
+            DhcpOption::Broadcast(_) => OPT_BROADCAST,
+            DhcpOption::NtpServers(_) => OPT_NTP_SERVERS,
+            DhcpOption::VendorSpecific(_) => OPT_VENDOR_SPECIFIC,
+            DhcpOption::RequestedIp(_) => OPT_REQUESTED_IP,
+            DhcpOption::LeaseTime(_) => OPT_LEASE_TIME,
+            DhcpOption::MessageType(_) => OPT_MESSAGE_TYPE,
+            DhcpOption::ServerId(_) => OPT_SERVER_ID,
+            DhcpOption::ParameterRequestList(_) => OPT_PARAMETER_REQUEST_LIST,
+            DhcpOption::Message(_) => OPT_MESSAGE,
+            DhcpOption::MaxMessageSize(_) => OPT_MAX_MESSAGE_SIZE,
+            DhcpOption::RenewalTime(_) => OPT_RENEWAL_TIME,
+            DhcpOption::RebindingTime(_) => OPT_REBINDING_TIME,
+            DhcpOption::VendorClass(_) => OPT_VENDOR_CLASS,
+            DhcpOption::ClientId(_) => OPT_CLIENT_ID,
+            DhcpOption::TftpServer(_) => OPT_TFTP_SERVER,
+            DhcpOption::BootFile(_) => OPT_BOOT_FILE,
+            DhcpOption::RelayAgentInfo(_) => OPT_RELAY_AGENT_INFO,
+            DhcpOption::ClasslessRoutes(_) => OPT_CLASSLESS_ROUTES,
+            DhcpOption::Wpad(_) => OPT_WPAD,
+            DhcpOption::Raw(code, _) => *code,
+        }
+    }
+
+    /// Encode the option payload (without code and length)
+    pub fn value_bytes(&self) -> Vec<u8> {
+        match self {
+            DhcpOption::SubnetMask(ip)
+            | DhcpOption::Broadcast(ip)
+            | DhcpOption::RequestedIp(ip)
+            | DhcpOption::ServerId(ip) => ip.octets().to_vec(),
+            DhcpOption::Router(ips) | DhcpOption::DnsServers(ips) | DhcpOption::NtpServers(ips) => {
+                ips.iter().flat_map(|ip| ip.octets()).collect()
+            }
+            DhcpOption::Hostname(s)
+            | DhcpOption::DomainName(s)
+            | DhcpOption::Message(s)
+            | DhcpOption::VendorClass(s)
+            | DhcpOption::TftpServer(s)
+            | DhcpOption::BootFile(s)
+            | DhcpOption::Wpad(s) => s.as_bytes().to_vec(),
+            DhcpOption::InterfaceMtu(v) | DhcpOption::MaxMessageSize(v) => v.to_be_bytes().to_vec(),
+            DhcpOption::LeaseTime(v) | DhcpOption::RenewalTime(v) | DhcpOption::RebindingTime(v) => {
+                v.to_be_bytes().to_vec()
+            }
+            DhcpOption::MessageType(v) => vec![*v],
+            DhcpOption::VendorSpecific(b)
+            | DhcpOption::ParameterRequestList(b)
+            | DhcpOption::ClientId(b)
+            | DhcpOption::RelayAgentInfo(b)
+            | DhcpOption::Raw(_, b) => b.clone(),
+            DhcpOption::ClasslessRoutes(routes) => {
+                let mut out = Vec::new();
+                for r in routes {
+                    let significant = (r.prefix_len as usize).div_ceil(8);
+                    out.push(r.prefix_len);
+                    out.extend_from_slice(&r.destination.octets()[..significant]);
+                    out.extend_from_slice(&r.gateway.octets());
+                }
+                out
+            }
+        }
+    }
+
+    /// Decode one option. Payloads that don't fit the expected shape are kept as `Raw`.
+    pub fn decode(code: u8, data: &[u8]) -> DhcpOption {
+        let raw = || DhcpOption::Raw(code, data.to_vec());
+        let text = || String::from_utf8_lossy(data).trim_end_matches('\0').to_string();
+        match code {
+            OPT_SUBNET_MASK => ipv4(data).map(DhcpOption::SubnetMask).unwrap_or_else(raw),
+            OPT_BROADCAST => ipv4(data).map(DhcpOption::Broadcast).unwrap_or_else(raw),
+            OPT_REQUESTED_IP => ipv4(data).map(DhcpOption::RequestedIp).unwrap_or_else(raw),
+            OPT_SERVER_ID => ipv4(data).map(DhcpOption::ServerId).unwrap_or_else(raw),
+            OPT_ROUTER => ipv4_list(data).map(DhcpOption::Router).unwrap_or_else(raw),
+            OPT_DNS_SERVERS => ipv4_list(data).map(DhcpOption::DnsServers).unwrap_or_else(raw),
+            OPT_NTP_SERVERS => ipv4_list(data).map(DhcpOption::NtpServers).unwrap_or_else(raw),
+            OPT_HOSTNAME => DhcpOption::Hostname(text()),
+            OPT_DOMAIN_NAME => DhcpOption::DomainName(text()),
+            OPT_MESSAGE => DhcpOption::Message(text()),
+            OPT_VENDOR_CLASS => DhcpOption::VendorClass(text()),
+            OPT_TFTP_SERVER => DhcpOption::TftpServer(text()),
+            OPT_BOOT_FILE => DhcpOption::BootFile(text()),
+            OPT_WPAD => DhcpOption::Wpad(text()),
+            OPT_INTERFACE_MTU if data.len() == 2 => DhcpOption::InterfaceMtu(u16::from_be_bytes([data[0], data[1]])),
+            OPT_MAX_MESSAGE_SIZE if data.len() == 2 => DhcpOption::MaxMessageSize(u16::from_be_bytes([data[0], data[1]])),
+            OPT_LEASE_TIME if data.len() == 4 => DhcpOption::LeaseTime(be_u32(data)),
+            OPT_RENEWAL_TIME if data.len() == 4 => DhcpOption::RenewalTime(be_u32(data)),
+            OPT_REBINDING_TIME if data.len() == 4 => DhcpOption::RebindingTime(be_u32(data)),
+            OPT_MESSAGE_TYPE if data.len() == 1 => DhcpOption::MessageType(data[0]),
+            OPT_VENDOR_SPECIFIC => DhcpOption::VendorSpecific(data.to_vec()),
+            OPT_PARAMETER_REQUEST_LIST => DhcpOption::ParameterRequestList(data.to_vec()),
+            OPT_CLIENT_ID => DhcpOption::ClientId(data.to_vec()),
+            OPT_RELAY_AGENT_INFO => DhcpOption::RelayAgentInfo(data.to_vec()),
+            OPT_CLASSLESS_ROUTES => decode_routes(data).map(DhcpOption::ClasslessRoutes).unwrap_or_else(raw),
+            _ => raw(),
+        }
+    }
+
+    /// Build an option from its textual form in the config (`"domain_name": "lan"`, `"42": "10.0.0.1"`).
+    /// Addresses are comma separated; routes are `"10.8.0.0/16 10.0.0.2, ..."`; unknown codes take hex.
+    pub fn from_config(key: &str, value: &str) -> Result<DhcpOption, String> {
+        let code = option_code(key).ok_or_else(|| format!("Unknown DHCP option '{}'", key))?;
+        let value = value.trim();
+        let bad = |what: &str| format!("Invalid value for DHCP option {} ({}): {}", key, what, value);
+
+        Ok(match code {
+            OPT_SUBNET_MASK => DhcpOption::SubnetMask(value.parse().map_err(|_| bad("address"))?),
+            OPT_BROADCAST => DhcpOption::Broadcast(value.parse().map_err(|_| bad("address"))?),
+            OPT_ROUTER => DhcpOption::Router(parse_ip_list(value).ok_or_else(|| bad("address list"))?),
+            OPT_DNS_SERVERS => DhcpOption::DnsServers(parse_ip_list(value).ok_or_else(|| bad("address list"))?),
+            OPT_NTP_SERVERS => DhcpOption::NtpServers(parse_ip_list(value).ok_or_else(|| bad("address list"))?),
+            OPT_DOMAIN_NAME => DhcpOption::DomainName(value.to_string()),
+            OPT_TFTP_SERVER => DhcpOption::TftpServer(value.to_string()),
+            OPT_BOOT_FILE => DhcpOption::BootFile(value.to_string()),
+            OPT_WPAD => DhcpOption::Wpad(value.to_string()),
+            OPT_INTERFACE_MTU => {
+                let mtu: u16 = value.parse().map_err(|_| bad("integer"))?;
+                if mtu < 68 {
+                    return Err(bad("MTU below 68"));
+                }
+                DhcpOption::InterfaceMtu(mtu)
+            }
+            OPT_VENDOR_SPECIFIC => DhcpOption::VendorSpecific(parse_hex(value).ok_or_else(|| bad("hex"))?),
+            OPT_CLASSLESS_ROUTES => DhcpOption::ClasslessRoutes(parse_routes(value).ok_or_else(|| bad("routes"))?),
+            OPT_PAD | OPT_END | OPT_MESSAGE_TYPE | OPT_SERVER_ID | OPT_LEASE_TIME | OPT_PARAMETER_REQUEST_LIST
+            | OPT_RELAY_AGENT_INFO => {
+                return Err(format!("DHCP option {} is managed by the server and can't be configured", key));
+            }
+            _ => DhcpOption::Raw(code, parse_hex(value).ok_or_else(|| bad("hex"))?),
+        })
+    }
+}
+
+/// Resolve a config key (friendly name or decimal code) to an option code
+pub fn option_code(key: &str) -> Option<u8> {
+    let key = key.trim();
+    OPTION_NAMES.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, code)| *code)
+        .or_else(|| key.parse().ok())
+}
+
+/// Parse every option in a packet's options area (the bytes after the magic cookie).
+/// Options split over several instances (RFC 3396) are concatenated before decoding.
+pub fn decode_options(buf: &[u8]) -> Vec<DhcpOption> {
+    let mut raw: Vec<(u8, Vec<u8>)> = Vec::new();
+    let mut i = 0;
+    while i < buf.len() {
+        let code = buf[i];
+        if code == OPT_END { break; }
+        if code == OPT_PAD { i += 1; continue; }
+        if i + 1 >= buf.len() { break; }
+
+        let len = buf[i + 1] as usize;
+        if i + 2 + len > buf.len() { break; } // Truncated option
+        let data = &buf[i + 2..i + 2 + len];
+        match raw.iter_mut().find(|(c, _)| *c == code) {
+            Some((_, existing)) => existing.extend_from_slice(data),
+            None => raw.push((code, data.to_vec())),
+        }
+        i += 2 + len;
+    }
+    raw.into_iter().map(|(code, data)| DhcpOption::decode(code, &data)).collect()
+}
+
+/// Append one option to `out`, splitting values longer than 255 bytes (RFC 3396)
+pub fn encode_option(opt: &DhcpOption, out: &mut Vec<u8>) {
+    let value = opt.value_bytes();
+    if value.is_empty() {
+        out.extend_from_slice(&[opt.code(), 0]);
+        return;
+    }
+    for chunk in value.chunks(255) {
+        out.push(opt.code());
+        out.push(chunk.len() as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+/// Encoded size of an option, including code and length bytes
+pub fn encoded_len(opt: &DhcpOption) -> usize {
+    let len = opt.value_bytes().len();
+    len + 2 * len.div_ceil(255).max(1)
+}
+
+/// Merge option maps in priority order (later maps override earlier ones) and parse them.
+/// Invalid entries are returned separately so the caller can log them.
+pub fn resolve_options(layers: &[&BTreeMap<String, String>]) -> (Vec<DhcpOption>, Vec<String>) {
+    let mut merged: BTreeMap<u8, DhcpOption> = BTreeMap::new();
+    let mut errors = Vec::new();
+    for layer in layers {
+        for (key, value) in layer.iter() {
+            match DhcpOption::from_config(key, value) {
+                Ok(opt) => { merged.insert(opt.code(), opt); }
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+    (merged.into_values().collect(), errors)
+}
+
+fn ipv4(data: &[u8]) -> Option<Ipv4Addr> {
+    if data.len() == 4 { Some(Ipv4Addr::new(data[0], data[1], data[2], data[3])) } else { None }
+}
+
+fn ipv4_list(data: &[u8]) -> Option<Vec<Ipv4Addr>> {
+    if data.is_empty() || !data.len().is_multiple_of(4) {
+        return None;
+    }
+    Some(data.chunks(4).map(|c| Ipv4Addr::new(c[0], c[1], c[2], c[3])).collect())
+}
+
+fn be_u32(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn decode_routes(data: &[u8]) -> Option<Vec<StaticRoute>> {
+    let mut routes = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let prefix_len = data[i];
+        if prefix_len > 32 { return None; }
+        let significant = (prefix_len as usize).div_ceil(8);
+        if i + 1 + significant + 4 > data.len() { return None; }
+        let mut dest = [0u8; 4];
+        dest[..significant].copy_from_slice(&data[i + 1..i + 1 + significant]);
+        let gw = &data[i + 1 + significant..i + 5 + significant];
+        routes.push(StaticRoute {
+            destination: Ipv4Addr::from(dest),
+            prefix_len,
+            gateway: Ipv4Addr::new(gw[0], gw[1], gw[2], gw[3]),
+        });
+        i += 5 + significant;
+    }
+    Some(routes)
+}
+
+fn parse_ip_list(value: &str) -> Option<Vec<Ipv4Addr>> {
+    let ips: Option<Vec<Ipv4Addr>> = value.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse().ok())
+        .collect();
+    ips.filter(|v| !v.is_empty())
+}
+
+fn parse_routes(value: &str) -> Option<Vec<StaticRoute>> {
+    value.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|entry| {
+            let mut parts = entry.split_whitespace();
+            let (net, prefix) = parts.next()?.split_once('/')?;
+            let gateway = parts.next()?.parse().ok()?;
+            let prefix_len: u8 = prefix.parse().ok().filter(|p| *p <= 32)?;
+            let mask = if prefix_len == 0 { 0 } else { !0u32 << (32 - prefix_len) };
+            let destination = Ipv4Addr::from(u32::from(net.parse::<Ipv4Addr>().ok()?) & mask);
+            Some(StaticRoute { destination, prefix_len, gateway })
+        })
+        .collect()
+}
+
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    let cleaned: String = value.chars().filter(|c| !matches!(c, ':' | ' ' | '-')).collect();
+    let cleaned = cleaned.trim_start_matches("0x");
+    if !cleaned.len().is_multiple_of(2) {
+        return None;
+    }
+    // Walk bytes rather than slicing the str, so a non-ASCII value fails instead of panicking mid-char
+    let digit = |b: u8| (b as char).to_digit(16);
+    cleaned.as_bytes()
+        .chunks(2)
+        .map(|pair| Some((digit(pair[0])? << 4 | digit(pair[1])?) as u8))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_routes_roundtrip() {
+        let opt = DhcpOption::from_config("static_routes", "10.8.0.0/16 10.0.0.2, 0.0.0.0/0 10.0.0.1").unwrap();
+        let mut buf = Vec::new();
+        encode_option(&opt, &mut buf);
+        assert_eq!(buf, vec![121, 12, 16, 10, 8, 10, 0, 0, 2, 0, 10, 0, 0, 1]);
+        assert_eq!(decode_options(&buf), vec![opt]);
+    }
+
+    #[test]
+    fn test_resolve_options_layers() {
+        let pool: BTreeMap<String, String> = [("domain_name".to_string(), "lan".to_string()), ("mtu".to_string(), "1500".to_string())].into();
+        let host: BTreeMap<String, String> = [("26".to_string(), "1400".to_string()), ("67".to_string(), "pxelinux.0".to_string())].into();
+        let (opts, errors) = resolve_options(&[&pool, &host]);
+        assert!(errors.is_empty());
+        assert_eq!(opts, vec![
+            DhcpOption::DomainName("lan".into()),
+            DhcpOption::InterfaceMtu(1400),
+            DhcpOption::BootFile("pxelinux.0".into()),
+        ]);
+    }
+
+    #[test]
+    fn test_long_option_split_and_merge() {
+        let opt = DhcpOption::VendorSpecific(vec![7u8; 300]);
+        let mut buf = Vec::new();
+        encode_option(&opt, &mut buf);
+        assert_eq!(buf.len(), encoded_len(&opt));
+        assert_eq!(decode_options(&buf), vec![opt]);
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("0x01:ff-0a 10"), Some(vec![0x01, 0xff, 0x0a, 0x10]));
+        assert_eq!(parse_hex("abc"), None);
+        assert_eq!(parse_hex("aé"), None); // Non-ASCII must not panic
+        assert_eq!(parse_hex("éé"), None);
+        assert!(DhcpOption::from_config("224", "aé").is_err());
+    }
+
+    #[test]
+    fn test_relay_agent_info() {
+        // circuit-id "eth0/1/3", remote-id as a MAC
+        let data = [1, 8, b'e', b't', b'h', b'0', b'/', b'1', b'/', b'3', 2, 6, 0, 0x1b, 0x21, 0xaa, 0xbb, 0xcc];
+        let info = RelayAgentInfo::parse(&data);
+        assert_eq!(info.circuit_id.as_deref(), Some("eth0/1/3"));
+        assert_eq!(info.remote_id.as_deref(), Some("001b21aabbcc"));
+
+        let by_port = RelayAgentInfo { circuit_id: Some("ETH0/1/3".into()), remote_id: None };
+        assert!(by_port.matches(&info));
+        assert!(!RelayAgentInfo::default().matches(&info));
+        assert!(!by_port.matches(&RelayAgentInfo::default()));
+    }
+}
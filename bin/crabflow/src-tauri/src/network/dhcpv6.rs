@@ -0,0 +1,903 @@
+// src-tauri/src/network/dhcpv6.rs
+// Stateful DHCPv6 (RFC 8415 IA_NA + DNS options) and SLAAC router advertisements (RFC 4861)
+
+use serde::{Serialize, Deserialize};
+use crate::sysmodules::{fetch, post, logging, config, notify, paths};
+use crate::network::dhcp::{self, Lease, LeaseState};
+use crate::network::dhcp::hooks::{self, LeaseEvent};
+use tauri::AppHandle;
+use std::net::{Ipv6Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::collections::BTreeMap;
+use lazy_static::lazy_static;
+
+static DHCPV6_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// All_DHCP_Relay_Agents_and_Servers (RFC 8415 section 7.1)
+const ALL_DHCP_AGENTS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 1, 2);
+
+/// How often expired IPv6 leases are swept, in listener ticks (1s each)
+const LEASE_REAP_INTERVAL_SECS: u64 = 60;
+
+/// Upper bound on addresses scanned when looking for a free one in a large pool
+const MAX_POOL_SCAN: u128 = 65536;
+
+// DHCPv6 message types
+const SOLICIT: u8 = 1;
+const ADVERTISE: u8 = 2;
+const REQUEST: u8 = 3;
+const CONFIRM: u8 = 4;
+const RENEW: u8 = 5;
+const REBIND: u8 = 6;
+const REPLY: u8 = 7;
+const RELEASE: u8 = 8;
+const DECLINE: u8 = 9;
+const INFORMATION_REQUEST: u8 = 11;
+
+// DHCPv6 option codes
+const OPT_CLIENTID: u16 = 1;
+const OPT_SERVERID: u16 = 2;
+const OPT_IA_NA: u16 = 3;
+const OPT_IAADDR: u16 = 5;
+const OPT_ORO: u16 = 6;
+const OPT_STATUS_CODE: u16 = 13;
+const OPT_RAPID_COMMIT: u16 = 14;
+const OPT_DNS_SERVERS: u16 = 23;
+const OPT_DOMAIN_LIST: u16 = 24;
+const OPT_CLIENT_FQDN: u16 = 39;
+
+// Status codes (option 13)
+const STATUS_SUCCESS: u16 = 0;
+const STATUS_NO_ADDRS_AVAIL: u16 = 2;
+const STATUS_NO_BINDING: u16 = 3;
+const STATUS_NOT_ON_LINK: u16 = 4;
+
+lazy_static! {
+    static ref LEASE_TABLE: RwLock<LeaseTable6> = RwLock::new(LeaseTable6::load());
+}
+
+/// An IPv6 address bound to one IA_NA of a client, identified by its DUID and IAID
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Lease6 {
+    pub ip: String,
+    pub duid: String, // Hex
+    pub iaid: u32,
+    #[serde(default)]
+    pub mac: Option<String>, // Recovered from DUID-LL/LLT when possible
+    pub hostname: String,
+    pub expires_at: Option<u64>, // Unix seconds, None = never expires
+    pub static_lease: bool,
+    #[serde(default)]
+    pub state: LeaseState, // Declined addresses are held out of the pool until expires_at
+}
+
+impl Lease6 {
+    pub fn is_expired(&self, now: u64) -> bool {
+        !self.static_lease && self.expires_at.is_some_and(|t| t <= now)
+    }
+
+    /// Present the lease in the same shape as IPv4 leases for `list_leases`
+    pub fn to_lease(&self) -> Lease {
+        Lease {
+            ip: self.ip.clone(),
+            mac: self.mac.clone().unwrap_or_else(|| self.duid.clone()),
+            hostname: self.hostname.clone(),
+            expires_at: self.expires_at,
+            static_lease: self.static_lease,
+            state: self.state,
+            options: BTreeMap::new(),
+            relay_info: None,
+        }
+    }
+}
+
+fn get_leases_file() -> String {
+    paths::get_config_path("leases6.json").to_string_lossy().to_string()
+}
+
+// --- Lease Table ---
+
+/// In-memory IPv6 lease database backed by leases6.json, keyed by address so pool scans don't re-parse every lease
+#[derive(Default)]
+pub struct LeaseTable6 {
+    leases: BTreeMap<Ipv6Addr, Lease6>,
+}
+
+impl LeaseTable6 {
+    fn load() -> Self {
+        let leases: Vec<Lease6> = match fetch::read_file(&get_leases_file()) {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_else(|e| {
+                logging::log_error(&format!("Failed to parse IPv6 leases file, starting with an empty table: {}", e));
+                vec![]
+            }),
+            Err(_) => vec![],
+        };
+        let mut table = Self::default();
+        for lease in leases {
+            match lease.ip.parse::<Ipv6Addr>() {
+                Ok(ip) => { table.leases.insert(ip, lease); }
+                Err(_) => logging::log_warn(&format!("Dropping IPv6 lease with invalid address {}", lease.ip)),
+            }
+        }
+        table
+    }
+
+    fn persist(&self) -> Result<(), String> {
+        let leases: Vec<&Lease6> = self.leases.values().collect();
+        let serialized = serde_json::to_string_pretty(&leases).map_err(|e| e.to_string())?;
+        post::write_file_atomic(&get_leases_file(), &serialized)
+    }
+
+    fn by_ip(&self, ip: &Ipv6Addr) -> Option<&Lease6> {
+        self.leases.get(ip)
+    }
+
+    /// The address an IA is bound to; quarantined addresses are no binding
+    fn binding(&self, duid: &str, iaid: u32) -> Option<&Lease6> {
+        self.leases.values().find(|l| l.state == LeaseState::Active && l.duid == duid && l.iaid == iaid)
+    }
+
+    /// True if `ip` is held by an unexpired lease of another IA. Quarantined addresses are taken for everyone.
+    fn is_taken(&self, ip: &Ipv6Addr, duid: &str, iaid: u32, now: u64) -> bool {
+        self.by_ip(ip).is_some_and(|l| {
+            !l.is_expired(now) && (l.state != LeaseState::Active || !(l.duid == duid && l.iaid == iaid))
+        })
+    }
+
+    /// Insert a lease, replacing any lease on the same address and any binding of the same IA
+    fn insert(&mut self, ip: Ipv6Addr, lease: Lease6) {
+        self.leases.retain(|_, l| !(l.state == LeaseState::Active && l.duid == lease.duid && l.iaid == lease.iaid && !l.static_lease));
+        self.leases.insert(ip, lease);
+    }
+
+    fn remove(&mut self, ip: &Ipv6Addr) -> Option<Lease6> {
+        self.leases.remove(ip)
+    }
+
+    fn reap_expired(&mut self, now: u64) -> Vec<Lease6> {
+        let expired: Vec<Ipv6Addr> = self.leases.iter().filter(|(_, l)| l.is_expired(now)).map(|(ip, _)| *ip).collect();
+        expired.iter().filter_map(|ip| self.leases.remove(ip)).collect()
+    }
+
+    fn pick_free_ip(&self, config: &config::Dhcpv6Config, duid: &str, iaid: u32, now: u64) -> Option<Ipv6Addr> {
+        let (start, end) = pool_bounds(config)?;
+        (start..=end)
+            .take(MAX_POOL_SCAN as usize)
+            .map(Ipv6Addr::from)
+            .find(|ip| !self.is_taken(ip, duid, iaid, now))
+    }
+}
+
+fn pool_bounds(config: &config::Dhcpv6Config) -> Option<(u128, u128)> {
+    let start = config.range_start.parse::<Ipv6Addr>().ok()?;
+    let end = config.range_end.parse::<Ipv6Addr>().ok()?;
+    Some((u128::from(start), u128::from(end)))
+}
+
+fn in_pool(ip: &Ipv6Addr, config: &config::Dhcpv6Config) -> bool {
+    pool_bounds(config).is_some_and(|(s, e)| (s..=e).contains(&u128::from(*ip)))
+}
+
+/// Parse "2001:db8:1::/64" into the network address and prefix length
+pub fn parse_prefix(prefix: &str) -> Option<(Ipv6Addr, u8)> {
+    let (addr, len) = prefix.trim().split_once('/')?;
+    let len: u8 = len.parse().ok().filter(|l| *l <= 128)?;
+    let addr: Ipv6Addr = addr.parse().ok()?;
+    let mask = if len == 0 { 0 } else { u128::MAX << (128 - len as u32) };
+    Some((Ipv6Addr::from(u128::from(addr) & mask), len))
+}
+
+fn on_link(ip: &Ipv6Addr, config: &config::Dhcpv6Config) -> bool {
+    match parse_prefix(&config.prefix) {
+        Some((net, len)) => {
+            let mask = if len == 0 { 0 } else { u128::MAX << (128 - len as u32) };
+            u128::from(*ip) & mask == u128::from(net)
+        }
+        None => false,
+    }
+}
+
+fn persist_or_log(table: &LeaseTable6) {
+    if let Err(e) = table.persist() {
+        logging::log_error(&format!("Failed to persist DHCPv6 leases: {}", e));
+    }
+}
+
+/// Reload the IPv6 lease table from disk
+pub fn reload_lease_table() {
+    let table = LeaseTable6::load();
+    let count = table.leases.len();
+    *LEASE_TABLE.write().unwrap() = table;
+    logging::log_info(&format!("DHCPv6 lease table loaded ({} leases)", count));
+}
+
+fn reap_expired_leases() {
+    let mut table = LEASE_TABLE.write().unwrap();
+    let expired = table.reap_expired(dhcp::now_secs());
+    if !expired.is_empty() {
+        persist_or_log(&table);
+        for lease in &expired {
+            logging::log_info(&format!("DHCPv6 lease expired: {} ({}) - {}", lease.hostname, lease.ip, lease.duid));
+            hooks::fire(LeaseEvent::LeaseExpired, &lease.to_lease());
+        }
+    }
+}
+
+/// IPv6 leases, in the same shape as IPv4 ones
+pub fn list_leases() -> Vec<Lease> {
+    LEASE_TABLE.read().unwrap().leases.values().map(Lease6::to_lease).collect()
+}
+
+pub fn is_ip_leased(ip: &Ipv6Addr) -> bool {
+    let table = LEASE_TABLE.read().unwrap();
+    table.by_ip(ip).is_some_and(|l| l.state == LeaseState::Active && !l.is_expired(dhcp::now_secs()))
+}
+
+pub fn remove_lease(ip: &Ipv6Addr) -> Result<(), String> {
+    let mut table = LEASE_TABLE.write().unwrap();
+    table.remove(ip);
+    table.persist()
+}
+
+// --- DHCPv6 Server Implementation ---
+
+pub fn stop_dhcpv6_server() {
+    if DHCPV6_RUNNING.load(Ordering::Relaxed) {
+        logging::log_info("Stopping DHCPv6 Server...");
+        DHCPV6_RUNNING.store(false, Ordering::Relaxed);
+    }
+}
+
+pub fn start_dhcpv6_server(app_handle: Option<AppHandle>) {
+    if DHCPV6_RUNNING.load(Ordering::Relaxed) {
+        logging::log_info("DHCPv6 Server is already running.");
+        return;
+    }
+
+    let config = match config::load_setup_config() {
+        Ok(cfg) => cfg.dhcpv6,
+        Err(e) => {
+            logging::log_error(&format!("Failed to load config for DHCPv6: {}", e));
+            return;
+        }
+    };
+
+    if !config.enabled {
+        logging::log_info("DHCPv6 Server is disabled in config. Skipping start.");
+        return;
+    }
+    if parse_prefix(&config.prefix).is_none_or(|(_, len)| len != 64) {
+        logging::log_error(&format!("DHCPv6 prefix {} is not a valid /64; SLAAC needs a /64. Skipping start.", config.prefix));
+        return;
+    }
+
+    DHCPV6_RUNNING.store(true, Ordering::Relaxed);
+    let if_index = interface_index(&config.interface);
+    let server_mac = interface_mac(&config.interface);
+
+    let ra_config = config.clone();
+    thread::spawn(move || run_router_advertisements(ra_config, if_index, server_mac));
+
+    let app = app_handle.clone();
+    thread::spawn(move || {
+        let socket = match UdpSocket::bind("[::]:547") {
+            Ok(s) => s,
+            Err(e) => {
+                let err_msg = format!("Failed to bind DHCPv6 server to [::]:547: {}", e);
+                logging::log_error(&err_msg);
+                if let Some(h) = &app {
+                    notify::send_notification(h, "DHCPv6 Error", &err_msg, "error");
+                }
+                DHCPV6_RUNNING.store(false, Ordering::Relaxed);
+                return;
+            }
+        };
+        if let Err(e) = socket.join_multicast_v6(&ALL_DHCP_AGENTS, if_index) {
+            logging::log_error(&format!("Failed to join {} on {}: {}", ALL_DHCP_AGENTS, config.interface, e));
+        }
+        socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap_or_else(|e| logging::log_error(&format!("Failed to set read timeout: {}", e)));
+
+        let server_duid = duid_ll(server_mac.unwrap_or_default());
+        logging::log_info(&format!("DHCPv6 Server started on {} (prefix {})", config.interface, config.prefix));
+        if let Some(h) = &app {
+            notify::send_notification(h, "DHCPv6 Started", &format!("Serving {} on {}", config.prefix, config.interface), "success");
+        }
+
+        reap_expired_leases();
+        let mut last_reap = Instant::now();
+        let mut buf = [0u8; 1500];
+        while DHCPV6_RUNNING.load(Ordering::Relaxed) {
+            match socket.recv_from(&mut buf) {
+                Ok((amt, src)) => {
+                    logging::log_debug(&format!("DHCPv6 packet received from {} ({} bytes)", src, amt));
+                    handle_packet(&socket, &buf[..amt], src, &server_duid, &app);
+                }
+                Err(e) => {
+                    if e.kind() != std::io::ErrorKind::WouldBlock && e.kind() != std::io::ErrorKind::TimedOut {
+                        logging::log_error(&format!("Error receiving DHCPv6 packet: {}", e));
+                    }
+                }
+            }
+            // Checked on every packet too, so steady traffic can't hold the reaper off
+            if last_reap.elapsed() >= Duration::from_secs(LEASE_REAP_INTERVAL_SECS) {
+                last_reap = Instant::now();
+                reap_expired_leases();
+            }
+        }
+        logging::log_info("DHCPv6 Server stopped.");
+    });
+}
+
+#[cfg(unix)]
+fn interface_index(name: &str) -> u32 {
+    match std::ffi::CString::new(name) {
+        Ok(c) => unsafe { libc::if_nametoindex(c.as_ptr()) },
+        Err(_) => 0,
+    }
+}
+
+#[cfg(not(unix))]
+fn interface_index(_name: &str) -> u32 {
+    0 // Let the stack pick the interface
+}
+
+fn interface_mac(name: &str) -> Option<[u8; 6]> {
+    let nets = sysinfo::Networks::new_with_refreshed_list();
+    nets.iter()
+        .find(|(n, _)| n.as_str() == name)
+        .map(|(_, data)| data.mac_address().0)
+        .filter(|mac| mac.iter().any(|b| *b != 0))
+}
+
+/// DUID-LL (RFC 8415 section 11.4): type 3, hardware type 1 (Ethernet), link-layer address
+fn duid_ll(mac: [u8; 6]) -> Vec<u8> {
+    let mut duid = vec![0, 3, 0, 1];
+    duid.extend_from_slice(&mac);
+    duid
+}
+
+/// Recover the client's MAC from a DUID-LLT or DUID-LL over Ethernet
+fn mac_from_duid(duid: &[u8]) -> Option<String> {
+    let mac = match duid {
+        [0, 1, 0, 1, _, _, _, _, mac @ ..] if mac.len() == 6 => mac,
+        [0, 3, 0, 1, mac @ ..] if mac.len() == 6 => mac,
+        _ => return None,
+    };
+    Some(mac.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"))
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// One IA_NA from a client message
+#[derive(Debug, PartialEq)]
+struct IaNa {
+    iaid: u32,
+    addresses: Vec<Ipv6Addr>,
+}
+
+/// Fields of a client message that drive the server
+#[derive(Debug)]
+struct Dhcpv6Message {
+    msg_type: u8,
+    xid: [u8; 3],
+    client_id: Option<Vec<u8>>,
+    server_id: Option<Vec<u8>>,
+    ia_na: Vec<IaNa>,
+    oro: Vec<u16>,
+    rapid_commit: bool,
+    hostname: String,
+}
+
+/// Walk a buffer of DHCPv6 options (2-byte code, 2-byte length)
+fn iter_options(buf: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut i = 0;
+    std::iter::from_fn(move || {
+        if i + 4 > buf.len() {
+            return None;
+        }
+        let code = u16::from_be_bytes([buf[i], buf[i + 1]]);
+        let len = u16::from_be_bytes([buf[i + 2], buf[i + 3]]) as usize;
+        if i + 4 + len > buf.len() {
+            return None;
+        }
+        let data = &buf[i + 4..i + 4 + len];
+        i += 4 + len;
+        Some((code, data))
+    })
+}
+
+impl Dhcpv6Message {
+    fn parse(packet: &[u8]) -> Option<Self> {
+        if packet.len() < 4 {
+            return None;
+        }
+        let mut msg = Dhcpv6Message {
+            msg_type: packet[0],
+            xid: [packet[1], packet[2], packet[3]],
+            client_id: None,
+            server_id: None,
+            ia_na: Vec::new(),
+            oro: Vec::new(),
+            rapid_commit: false,
+            hostname: "Unknown".to_string(),
+        };
+
+        for (code, data) in iter_options(&packet[4..]) {
+            match code {
+                OPT_CLIENTID => msg.client_id = Some(data.to_vec()),
+                OPT_SERVERID => msg.server_id = Some(data.to_vec()),
+                OPT_ORO => msg.oro = data.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect(),
+                OPT_RAPID_COMMIT => msg.rapid_commit = true,
+                OPT_IA_NA if data.len() >= 12 => {
+                    let iaid = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+                    let addresses = iter_options(&data[12..])
+                        .filter(|(c, d)| *c == OPT_IAADDR && d.len() >= 24)
+                        .map(|(_, d)| {
+                            let mut octets = [0u8; 16];
+                            octets.copy_from_slice(&d[..16]);
+                            Ipv6Addr::from(octets)
+                        })
+                        .collect();
+                    msg.ia_na.push(IaNa { iaid, addresses });
+                }
+                OPT_CLIENT_FQDN if !data.is_empty() => {
+                    if let Some(name) = decode_dns_name(&data[1..]) {
+                        msg.hostname = name.split('.').next().unwrap_or(&name).to_string();
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some(msg)
+    }
+
+    fn wants(&self, code: u16) -> bool {
+        self.oro.is_empty() || self.oro.contains(&code)
+    }
+}
+
+/// Decode an uncompressed DNS wire-format name (as used in DHCPv6 options)
+fn decode_dns_name(data: &[u8]) -> Option<String> {
+    let mut labels = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let len = data[i] as usize;
+        if len == 0 {
+            break;
+        }
+        let label = data.get(i + 1..i + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        i += 1 + len;
+    }
+    if labels.is_empty() { None } else { Some(labels.join(".")) }
+}
+
+fn encode_dns_name(name: &str, out: &mut Vec<u8>) {
+    for label in name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+        let bytes = &label.as_bytes()[..label.len().min(63)];
+        out.push(bytes.len() as u8);
+        out.extend_from_slice(bytes);
+    }
+    out.push(0);
+}
+
+fn push_option(out: &mut Vec<u8>, code: u16, data: &[u8]) {
+    out.extend_from_slice(&code.to_be_bytes());
+    out.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    out.extend_from_slice(data);
+}
+
+fn status_option(code: u16, message: &str) -> Vec<u8> {
+    let mut data = code.to_be_bytes().to_vec();
+    data.extend_from_slice(message.as_bytes());
+    data
+}
+
+/// Build an IA_NA option: either the bound address with its lifetimes, or a status code
+fn ia_na_option(iaid: u32, addr: Result<Ipv6Addr, (u16, &str)>, config: &config::Dhcpv6Config) -> Vec<u8> {
+    let preferred = config.preferred_lifetime.min(u32::MAX as u64) as u32;
+    let valid = config.valid_lifetime.min(u32::MAX as u64) as u32;
+    let mut data = iaid.to_be_bytes().to_vec();
+    data.extend_from_slice(&(preferred / 2).to_be_bytes()); // T1
+    data.extend_from_slice(&((preferred as u64 * 4 / 5) as u32).to_be_bytes()); // T2
+    match addr {
+        Ok(ip) => {
+            let mut iaaddr = ip.octets().to_vec();
+            iaaddr.extend_from_slice(&preferred.to_be_bytes());
+            iaaddr.extend_from_slice(&valid.to_be_bytes());
+            push_option(&mut data, OPT_IAADDR, &iaaddr);
+        }
+        Err((code, message)) => push_option(&mut data, OPT_STATUS_CODE, &status_option(code, message)),
+    }
+    data
+}
+
+/// DNS servers and search list, sent whenever the client asks for them (or sends no ORO)
+fn push_dns_options(out: &mut Vec<u8>, msg: &Dhcpv6Message, config: &config::Dhcpv6Config) {
+    let servers: Vec<u8> = config.dns_servers.iter()
+        .filter_map(|d| d.parse::<Ipv6Addr>().ok())
+        .flat_map(|ip| ip.octets())
+        .collect();
+    if !servers.is_empty() && msg.wants(OPT_DNS_SERVERS) {
+        push_option(out, OPT_DNS_SERVERS, &servers);
+    }
+    if !config.domain_search.is_empty() && msg.wants(OPT_DOMAIN_LIST) {
+        let mut names = Vec::new();
+        for domain in &config.domain_search {
+            encode_dns_name(domain, &mut names);
+        }
+        push_option(out, OPT_DOMAIN_LIST, &names);
+    }
+}
+
+fn handle_packet(socket: &UdpSocket, packet: &[u8], src: SocketAddr, server_duid: &[u8], app: &Option<AppHandle>) {
+    let msg = match Dhcpv6Message::parse(packet) {
+        Some(m) => m,
+        None => return,
+    };
+    let config = match fetch::fetch_setup() {
+        Ok(s) if s.dhcpv6.enabled => s.dhcpv6,
+        _ => return,
+    };
+
+    // Messages addressed to another server are none of our business, and REQUEST, RENEW,
+    // RELEASE and DECLINE must name a server (RFC 8415 section 16)
+    match &msg.server_id {
+        Some(sid) if sid != server_duid => return,
+        None if matches!(msg.msg_type, REQUEST | RENEW | RELEASE | DECLINE) => {
+            logging::log_debug(&format!("Discarding DHCPv6 message type {} from {} without a Server Identifier", msg.msg_type, src));
+            return;
+        }
+        _ => {}
+    }
+    let client_id = match &msg.client_id {
+        Some(id) => id.clone(),
+        None if msg.msg_type == INFORMATION_REQUEST => Vec::new(),
+        None => return,
+    };
+
+    let (reply_type, body) = match msg.msg_type {
+        SOLICIT => {
+            let commit = msg.rapid_commit;
+            (if commit { REPLY } else { ADVERTISE }, bind_addresses(&msg, &client_id, &config, commit, false, app))
+        }
+        REQUEST | RENEW | REBIND => (REPLY, bind_addresses(&msg, &client_id, &config, true, msg.msg_type != REQUEST, app)),
+        RELEASE => (REPLY, release_addresses(&msg, &client_id)),
+        DECLINE => (REPLY, decline_addresses(&msg, &client_id, &config, app)),
+        CONFIRM => {
+            let all_on_link = msg.ia_na.iter().flat_map(|ia| &ia.addresses).all(|a| on_link(a, &config));
+            let mut body = Vec::new();
+            let status = if all_on_link { (STATUS_SUCCESS, "All addresses on link") } else { (STATUS_NOT_ON_LINK, "Not on link") };
+            push_option(&mut body, OPT_STATUS_CODE, &status_option(status.0, status.1));
+            (REPLY, body)
+        }
+        INFORMATION_REQUEST => (REPLY, Vec::new()),
+        other => {
+            logging::log_debug(&format!("Ignoring unsupported DHCPv6 message type {} from {}", other, src));
+            return;
+        }
+    };
+
+    let mut reply = vec![reply_type, msg.xid[0], msg.xid[1], msg.xid[2]];
+    push_option(&mut reply, OPT_SERVERID, server_duid);
+    if !client_id.is_empty() {
+        push_option(&mut reply, OPT_CLIENTID, &client_id);
+    }
+    if msg.msg_type == SOLICIT && msg.rapid_commit {
+        push_option(&mut reply, OPT_RAPID_COMMIT, &[]);
+    }
+    reply.extend_from_slice(&body);
+    push_dns_options(&mut reply, &msg, &config);
+
+    match socket.send_to(&reply, src) {
+        Ok(n) => logging::log_debug(&format!("DHCPv6 reply (type {}) sent to {} ({} bytes)", reply_type, src, n)),
+        Err(e) => logging::log_error(&format!("Failed sending DHCPv6 reply: {}", e)),
+    }
+}
+
+/// Pick (and with `commit`, persist) an address for every IA_NA in the message.
+/// Renewals keep the client's current address if it is still valid for it.
+fn bind_addresses(msg: &Dhcpv6Message, client_id: &[u8], config: &config::Dhcpv6Config, commit: bool, renewing: bool, app: &Option<AppHandle>) -> Vec<u8> {
+    let duid = hex(client_id);
+    let now = dhcp::now_secs();
+    let mut body = Vec::new();
+    let mut table = LEASE_TABLE.write().unwrap();
+
+    for ia in &msg.ia_na {
+        let requested = ia.addresses.iter()
+            .find(|a| in_pool(a, config) && !table.is_taken(a, &duid, ia.iaid, now))
+            .copied();
+        let existing = table.binding(&duid, ia.iaid)
+            .and_then(|l| l.ip.parse::<Ipv6Addr>().ok())
+            .filter(|ip| on_link(ip, config));
+        let chosen = existing
+            .or(requested)
+            .or_else(|| if renewing { None } else { table.pick_free_ip(config, &duid, ia.iaid, now) });
+
+        let addr = match chosen {
+            Some(ip) => Ok(ip),
+            None if renewing => Err((STATUS_NO_BINDING, "No binding for this IA")),
+            None => Err((STATUS_NO_ADDRS_AVAIL, "No addresses available")),
+        };
+        if let Ok(ip) = addr {
+            if commit && !table.binding(&duid, ia.iaid).is_some_and(|l| l.static_lease) {
+                let is_new = existing != Some(ip);
+                let lease = Lease6 {
+                    ip: ip.to_string(),
+                    duid: duid.clone(),
+                    iaid: ia.iaid,
+                    mac: mac_from_duid(client_id),
+                    hostname: msg.hostname.clone(),
+                    expires_at: Some(now + config.valid_lifetime),
+                    static_lease: false,
+                    state: LeaseState::Active,
+                };
+                hooks::fire(if is_new { LeaseEvent::LeaseGranted } else { LeaseEvent::LeaseRenewed }, &lease.to_lease());
+                table.insert(ip, lease);
+                if is_new {
+                    let text = format!("New IPv6 Lease: {} ({}) - {}", msg.hostname, ip, duid);
+                    logging::log_info(&text);
+                    if let Some(h) = app {
+                        notify::send_notification(h, "DHCPv6 Lease", &text, "info");
+                    }
+                }
+            }
+        } else {
+            logging::log_warn(&format!("DHCPv6: no address for IA {} of {}", ia.iaid, duid));
+        }
+        push_option(&mut body, OPT_IA_NA, &ia_na_option(ia.iaid, addr, config));
+    }
+    if commit {
+        persist_or_log(&table);
+    }
+    body
+}
+
+/// The IA's own dynamic lease on `addr`, if it has one
+fn owns(table: &LeaseTable6, addr: &Ipv6Addr, duid: &str, iaid: u32) -> bool {
+    table.by_ip(addr).is_some_and(|l| l.state == LeaseState::Active && l.duid == duid && l.iaid == iaid && !l.static_lease)
+}
+
+fn release_addresses(msg: &Dhcpv6Message, client_id: &[u8]) -> Vec<u8> {
+    let duid = hex(client_id);
+    let mut table = LEASE_TABLE.write().unwrap();
+    for ia in &msg.ia_na {
+        for addr in &ia.addresses {
+            if owns(&table, addr, &duid, ia.iaid) {
+                if let Some(lease) = table.remove(addr) {
+                    hooks::fire(LeaseEvent::LeaseReleased, &lease.to_lease());
+                }
+                logging::log_info(&format!("DHCPv6 RELEASE: {} freed by {}", addr, duid));
+            }
+        }
+    }
+    persist_or_log(&table);
+
+    let mut body = Vec::new();
+    push_option(&mut body, OPT_STATUS_CODE, &status_option(STATUS_SUCCESS, "Released"));
+    body
+}
+
+/// DECLINE -> The client found its address already in use (DAD); quarantine it like a declined IPv4 address
+fn decline_addresses(msg: &Dhcpv6Message, client_id: &[u8], config: &config::Dhcpv6Config, app: &Option<AppHandle>) -> Vec<u8> {
+    let duid = hex(client_id);
+    let now = dhcp::now_secs();
+    let mut declined = Vec::new();
+    {
+        let mut table = LEASE_TABLE.write().unwrap();
+        for ia in &msg.ia_na {
+            for addr in &ia.addresses {
+                let lease = match table.by_ip(addr) {
+                    Some(l) if owns(&table, addr, &duid, ia.iaid) => Lease6 {
+                        expires_at: Some(now + config.decline_quarantine_secs),
+                        state: LeaseState::Declined,
+                        ..l.clone()
+                    },
+                    _ => {
+                        logging::log_debug(&format!("Ignoring DHCPv6 DECLINE of {} from {}: not its lease", addr, duid));
+                        continue;
+                    }
+                };
+                hooks::fire(LeaseEvent::LeaseDeclined, &lease.to_lease());
+                table.insert(*addr, lease);
+                declined.push(*addr);
+            }
+        }
+        persist_or_log(&table);
+    }
+
+    for addr in &declined {
+        let text = format!("{} declined {} (address in use); quarantined for {}s", duid, addr, config.decline_quarantine_secs);
+        logging::log_warn(&format!("DHCPv6 DECLINE: {}", text));
+        if let Some(h) = app {
+            notify::send_notification(h, "DHCPv6 Address Conflict", &text, "warning");
+        }
+    }
+
+    let mut body = Vec::new();
+    push_option(&mut body, OPT_STATUS_CODE, &status_option(STATUS_SUCCESS, "Declined"));
+    body
+}
+
+// --- Router Advertisements ---
+
+// ICMPv6 Neighbor Discovery option types
+const ND_OPT_SOURCE_LINK_ADDR: u8 = 1;
+const ND_OPT_PREFIX_INFO: u8 = 3;
+const ND_OPT_RDNSS: u8 = 25;
+
+/// Build a Router Advertisement announcing the configured /64 for SLAAC, with the
+/// "managed" flag set when addresses also come from DHCPv6 and the "other" flag so
+/// clients fetch DNS settings from us. The checksum is left to the kernel.
+pub fn build_router_advertisement(config: &config::Dhcpv6Config, source_mac: Option<[u8; 6]>) -> Option<Vec<u8>> {
+    let (prefix, prefix_len) = parse_prefix(&config.prefix)?;
+    let valid = config.valid_lifetime.min(u32::MAX as u64) as u32;
+    let preferred = config.preferred_lifetime.min(valid as u64) as u32;
+
+    let mut ra = vec![134, 0, 0, 0]; // Type, code, checksum
+    ra.push(64); // Cur hop limit
+    ra.push(if config.stateful { 0xc0 } else { 0x40 }); // M and O flags
+    ra.extend_from_slice(&1800u16.to_be_bytes()); // Router lifetime
+    ra.extend_from_slice(&[0; 8]); // Reachable time, retrans timer
+
+    if let Some(mac) = source_mac {
+        ra.extend_from_slice(&[ND_OPT_SOURCE_LINK_ADDR, 1]);
+        ra.extend_from_slice(&mac);
+    }
+
+    ra.extend_from_slice(&[ND_OPT_PREFIX_INFO, 4, prefix_len, 0xc0]); // On-link + autonomous
+    ra.extend_from_slice(&valid.to_be_bytes());
+    ra.extend_from_slice(&preferred.to_be_bytes());
+    ra.extend_from_slice(&[0; 4]);
+    ra.extend_from_slice(&prefix.octets());
+
+    let dns: Vec<Ipv6Addr> = config.dns_servers.iter().filter_map(|d| d.parse().ok()).collect();
+    if !dns.is_empty() {
+        ra.extend_from_slice(&[ND_OPT_RDNSS, (1 + 2 * dns.len()) as u8, 0, 0]);
+        let lifetime = (config.ra_interval_secs * 3).min(u32::MAX as u64) as u32;
+        ra.extend_from_slice(&lifetime.to_be_bytes());
+        for ip in dns {
+            ra.extend_from_slice(&ip.octets());
+        }
+    }
+    Some(ra)
+}
+
+#[cfg(unix)]
+fn run_router_advertisements(config: config::Dhcpv6Config, if_index: u32, source_mac: Option<[u8; 6]>) {
+    let ra = match build_router_advertisement(&config, source_mac) {
+        Some(ra) => ra,
+        None => return,
+    };
+
+    let fd = unsafe { libc::socket(libc::AF_INET6, libc::SOCK_RAW, libc::IPPROTO_ICMPV6) };
+    if fd < 0 {
+        logging::log_error(&format!("Failed to open ICMPv6 socket for router advertisements: {}", std::io::Error::last_os_error()));
+        return;
+    }
+    // Neighbor Discovery messages must be sent with a hop limit of 255
+    let hops: libc::c_int = 255;
+    let set = |opt: libc::c_int, val: &libc::c_int| unsafe {
+        libc::setsockopt(fd, libc::IPPROTO_IPV6, opt, val as *const _ as *const libc::c_void, std::mem::size_of::<libc::c_int>() as libc::socklen_t)
+    };
+    set(libc::IPV6_MULTICAST_HOPS, &hops);
+    set(libc::IPV6_UNICAST_HOPS, &hops);
+    set(libc::IPV6_MULTICAST_IF, &(if_index as libc::c_int));
+
+    let mut dest: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+    dest.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+    dest.sin6_addr.s6_addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1).octets(); // All nodes
+    dest.sin6_scope_id = if_index;
+
+    logging::log_info(&format!("Sending router advertisements for {} on {} every {}s", config.prefix, config.interface, config.ra_interval_secs));
+    let mut elapsed = config.ra_interval_secs; // Advertise right away
+    while DHCPV6_RUNNING.load(Ordering::Relaxed) {
+        if elapsed >= config.ra_interval_secs {
+            elapsed = 0;
+            let sent = unsafe {
+                libc::sendto(fd, ra.as_ptr() as *const libc::c_void, ra.len(), 0,
+                    &dest as *const _ as *const libc::sockaddr, std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t)
+            };
+            if sent < 0 {
+                logging::log_warn(&format!("Failed to send router advertisement: {}", std::io::Error::last_os_error()));
+            }
+        }
+        thread::sleep(Duration::from_secs(1));
+        elapsed += 1;
+    }
+    unsafe { libc::close(fd) };
+}
+
+#[cfg(not(unix))]
+fn run_router_advertisements(config: config::Dhcpv6Config, _if_index: u32, _source_mac: Option<[u8; 6]>) {
+    // Windows has no raw ICMPv6 sockets for us; have the stack advertise the prefix instead
+    use std::process::Command;
+    let iface = &config.interface;
+    let managed = if config.stateful { "enabled" } else { "disabled" };
+    let _ = Command::new("netsh")
+        .args(["interface", "ipv6", "set", "interface", iface, "advertise=enabled",
+            &format!("managedaddress={}", managed), "otherstateful=enabled"])
+        .output()
+        .map_err(|e| logging::log_error(&format!("Failed to enable router advertisements: {}", e))).ok();
+    let _ = Command::new("netsh")
+        .args(["interface", "ipv6", "add", "route", &config.prefix, iface, "publish=yes",
+            &format!("validlifetime={}", config.valid_lifetime), &format!("preferredlifetime={}", config.preferred_lifetime)])
+        .output()
+        .map_err(|e| logging::log_error(&format!("Failed to publish IPv6 prefix: {}", e))).ok();
+    logging::log_info(&format!("Router advertisements for {} delegated to the system on {}", config.prefix, config.interface));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solicit_parse() {
+        let mut packet = vec![SOLICIT, 0x12, 0x34, 0x56];
+        push_option(&mut packet, OPT_CLIENTID, &[0, 3, 0, 1, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        let mut ia = 7u32.to_be_bytes().to_vec();
+        ia.extend_from_slice(&[0; 8]);
+        push_option(&mut packet, OPT_IA_NA, &ia);
+        push_option(&mut packet, OPT_ORO, &[0, 23, 0, 24]);
+        push_option(&mut packet, OPT_RAPID_COMMIT, &[]);
+
+        let msg = Dhcpv6Message::parse(&packet).unwrap();
+        assert_eq!(msg.xid, [0x12, 0x34, 0x56]);
+        assert_eq!(msg.ia_na, vec![IaNa { iaid: 7, addresses: vec![] }]);
+        assert!(msg.rapid_commit && msg.wants(OPT_DNS_SERVERS));
+        assert_eq!(mac_from_duid(msg.client_id.as_deref().unwrap()).as_deref(), Some("aa:bb:cc:dd:ee:ff"));
+    }
+
+    #[test]
+    fn test_declined_address_is_quarantined() {
+        let cfg = config::Dhcpv6Config {
+            range_start: "fd00::10".into(),
+            range_end: "fd00::12".into(),
+            ..config::Dhcpv6Config::default()
+        };
+        let ip: Ipv6Addr = "fd00::10".parse().unwrap();
+        let lease = Lease6 {
+            ip: ip.to_string(),
+            duid: "0003".into(),
+            iaid: 1,
+            mac: None,
+            hostname: "host".into(),
+            expires_at: Some(1000),
+            static_lease: false,
+            state: LeaseState::Active,
+        };
+        let mut table = LeaseTable6::default();
+        table.insert(ip, lease.clone());
+        assert!(owns(&table, &ip, "0003", 1) && !owns(&table, &ip, "0004", 1));
+        assert!(!table.is_taken(&ip, "0003", 1, 500) && table.is_taken(&ip, "0004", 1, 500));
+        assert_eq!(table.pick_free_ip(&cfg, "0004", 1, 500), Some("fd00::11".parse().unwrap()));
+
+        table.insert(ip, Lease6 { state: LeaseState::Declined, ..lease });
+        assert!(table.binding("0003", 1).is_none());
+        assert!(table.is_taken(&ip, "0003", 1, 500));
+        assert_eq!(table.pick_free_ip(&cfg, "0003", 1, 500), Some("fd00::11".parse().unwrap()));
+        assert_eq!(table.reap_expired(1000).len(), 1);
+        assert_eq!(table.pick_free_ip(&cfg, "0003", 1, 1000), Some(ip));
+    }
+
+    #[test]
+    fn test_router_advertisement() {
+        let cfg = config::Dhcpv6Config {
+            prefix: "fd00:c0ab:1::/64".into(),
+            dns_servers: vec!["fd00:c0ab:1::1".into()],
+            ..config::Dhcpv6Config::default()
+        };
+        let ra = build_router_advertisement(&cfg, None).unwrap();
+        assert_eq!(ra[0], 134);
+        assert_eq!(ra[5], 0xc0); // Managed + other
+        assert_eq!(&ra[16..20], &[ND_OPT_PREFIX_INFO, 4, 64, 0xc0]);
+        assert_eq!(&ra[32..48], &"fd00:c0ab:1::".parse::<Ipv6Addr>().unwrap().octets());
+        assert_eq!(&ra[48..50], &[ND_OPT_RDNSS, 3]);
+        assert_eq!(ra.len(), 16 + 32 + 24);
+    }
+}
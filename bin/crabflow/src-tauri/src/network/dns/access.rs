@@ -0,0 +1,121 @@
+// src-tauri/src/network/dns/access.rs
+// Where the DNS server listens and who may query it, so a machine with a public uplink
+// doesn't become an open resolver or answer WAN-side queries for the captive portal
+
+use crate::sysmodules::{config, logging};
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Addresses to bind for the configured listen interfaces/addresses; all IPv4 interfaces when none are configured.
+/// Interface names stand for every address on the interface, except IPv6 link-local ones, which can't be bound without a scope.
+pub fn listen_addresses(dns_config: &config::DnsConfig) -> Vec<IpAddr> {
+    let mut addrs: Vec<IpAddr> = Vec::new();
+    if dns_config.listen_addresses.iter().all(|l| l.trim().is_empty()) {
+        addrs.push(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        return addrs;
+    }
+    let interfaces = get_if_addrs::get_if_addrs().unwrap_or_default();
+    for entry in dns_config.listen_addresses.iter().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        let resolved: Vec<IpAddr> = match entry.parse::<IpAddr>() {
+            Ok(ip) => vec![ip],
+            Err(_) => interfaces.iter()
+                .filter(|i| i.name == entry)
+                .map(|i| i.addr.ip())
+                .filter(|ip| !matches!(ip, IpAddr::V6(v6) if (v6.segments()[0] & 0xFFC0) == 0xFE80))
+                .collect(),
+        };
+        if resolved.is_empty() {
+            logging::log_warn(&format!("DNS listen interface {} has no usable address", entry));
+        }
+        for ip in resolved {
+            if !addrs.contains(&ip) {
+                addrs.push(ip);
+            }
+        }
+    }
+    addrs
+}
+
+/// An "address/prefix" network, or a single address
+fn parse_network(network: &str) -> Option<(IpAddr, u32)> {
+    let network = network.trim();
+    let (addr, prefix) = match network.split_once('/') {
+        Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u32>().ok()?)),
+        None => (network.parse::<IpAddr>().ok()?, None),
+    };
+    let max = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max);
+    (prefix <= max).then_some((addr, prefix))
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u32) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(net)) => {
+            let mask = if prefix == 0 { 0 } else { !0u32 << (32 - prefix) };
+            u32::from(ip) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(net)) => {
+            let mask = if prefix == 0 { 0 } else { !0u128 << (128 - prefix) };
+            u128::from(ip) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Whether a client may query the server: it is in one of the allowed networks, or none are configured.
+/// This machine is always allowed.
+pub fn client_allowed(src_ip: &str, dns_config: &config::DnsConfig) -> bool {
+    let Ok(ip) = src_ip.parse::<IpAddr>() else { return false };
+    // IPv4 clients show up as ::ffff:a.b.c.d on dual-stack sockets
+    let ip = match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    };
+    let mut networks = dns_config.allowed_client_networks.iter().filter(|n| !n.trim().is_empty()).peekable();
+    if networks.peek().is_none() || ip.is_loopback() {
+        return true;
+    }
+    networks.any(|n| match parse_network(n) {
+        Some((network, prefix)) => in_network(ip, network, prefix),
+        None => false,
+    })
+}
+
+/// Config problems worth logging: allowed networks that don't parse never match anything
+pub fn invalid_networks(dns_config: &config::DnsConfig) -> Vec<String> {
+    dns_config.allowed_client_networks.iter()
+        .filter(|n| !n.trim().is_empty() && parse_network(n).is_none())
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_allowed() {
+        let mut cfg = config::DnsConfig::default();
+        assert!(client_allowed("203.0.113.9", &cfg));
+
+        cfg.allowed_client_networks = vec!["192.168.1.0/24".into(), "10.8.0.5".into(), "fd00:1::/64".into(), "bogus/99".into()];
+        assert!(client_allowed("192.168.1.77", &cfg));
+        assert!(client_allowed("::ffff:192.168.1.77", &cfg));
+        assert!(client_allowed("10.8.0.5", &cfg));
+        assert!(client_allowed("fd00:1::abcd", &cfg));
+        assert!(client_allowed("127.0.0.1", &cfg));
+        assert!(client_allowed("::1", &cfg));
+        assert!(!client_allowed("192.168.2.1", &cfg));
+        assert!(!client_allowed("10.8.0.6", &cfg));
+        assert!(!client_allowed("fd00:2::1", &cfg));
+        assert!(!client_allowed("203.0.113.9", &cfg));
+        assert_eq!(invalid_networks(&cfg), vec!["bogus/99".to_string()]);
+    }
+
+    #[test]
+    fn test_listen_addresses() {
+        let mut cfg = config::DnsConfig::default();
+        assert_eq!(listen_addresses(&cfg), vec![IpAddr::V4(Ipv4Addr::UNSPECIFIED)]);
+        cfg.listen_addresses = vec!["192.168.1.1".into(), " ::1 ".into(), "192.168.1.1".into(), "no-such-interface0".into()];
+        assert_eq!(listen_addresses(&cfg), vec!["192.168.1.1".parse::<IpAddr>().unwrap(), "::1".parse().unwrap()]);
+    }
+}
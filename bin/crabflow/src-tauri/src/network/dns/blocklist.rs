@@ -0,0 +1,648 @@
+// src-tauri/src/network/dns/blocklist.rs
+// Domain blocking: the manual blacklist, subscribed lists (hosts, AdBlock, RPZ, plain domains) and an allowlist.
+// Every entry also covers its subdomains.
+
+use serde::{Serialize, Deserialize};
+use crate::sysmodules::{fetch, post, logging, paths, config};
+use crate::sysmodules::config::BlockMode;
+use crate::network::cportal;
+use super::policy::{self, FilterPolicy};
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+use lazy_static::lazy_static;
+use uuid::Uuid;
+
+const DOWNLOAD_TIMEOUT_SECS: u64 = 60;
+/// Largest list accepted; the big public lists are a few MB
+const MAX_LIST_BYTES: u64 = 64 * 1024 * 1024;
+/// Pending unblock requests kept; the oldest are dropped beyond this
+const MAX_UNBLOCK_REQUESTS: usize = 500;
+/// The block page is unauthenticated: each client may file one request per interval and keep this many open
+const UNBLOCK_REQUEST_INTERVAL_SECS: u64 = 10;
+const MAX_UNBLOCK_REQUESTS_PER_CLIENT: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ListFormat {
+    #[default]
+    Hosts,   // "0.0.0.0 ads.example.com"
+    Adblock, // "||ads.example.com^", with "@@||...^" exceptions
+    Rpz,     // Response policy zone: "ads.example.com CNAME ."
+    Domains, // One domain per line
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BlocklistSubscription {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub format: ListFormat,
+    #[serde(default = "default_category")]
+    pub category: String, // "ads", "adult", "gambling"...; policies can apply lists by category
+    #[serde(default)]
+    pub block_mode: Option<BlockMode>, // None answers with the global block mode
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_refresh_hours")]
+    pub refresh_hours: u64,
+    #[serde(default)]
+    pub last_updated: Option<u64>,
+    #[serde(default)]
+    pub domain_count: usize,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_refresh_hours() -> u64 {
+    24
+}
+
+fn default_category() -> String {
+    "ads".to_string()
+}
+
+/// Domains parsed from one list
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedList {
+    pub blocked: HashSet<String>,
+    pub allowed: HashSet<String>, // Exceptions the list itself declares (AdBlock @@, RPZ passthru)
+}
+
+/// Contents of an enabled subscription
+struct LoadedList {
+    id: String,
+    category: String,
+    block_mode: Option<BlockMode>,
+    name: String,
+    domains: ParsedList,
+}
+
+lazy_static! {
+    static ref SUBSCRIPTIONS: RwLock<Vec<BlocklistSubscription>> = RwLock::new(Vec::new());
+    static ref LOADED: RwLock<Vec<LoadedList>> = RwLock::new(Vec::new());
+    static ref ALLOWLIST: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+    static ref UNBLOCK_REQUESTS: RwLock<Vec<UnblockRequest>> = RwLock::new(Vec::new());
+    // When each client last filed an unblock request
+    static ref LAST_UNBLOCK_REQUEST: Mutex<HashMap<String, u64>> = Mutex::new(HashMap::new());
+}
+
+/// A client asking, from the block page, for a domain to be allowed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UnblockRequest {
+    pub id: String,
+    pub domain: String,
+    pub client_ip: String,
+    pub source: String, // List that blocked it when the request was made
+    #[serde(default)]
+    pub reason: String,
+    pub requested_at: u64,
+}
+
+fn get_subscriptions_file() -> String {
+    paths::get_config_path("blocklists.json").to_string_lossy().to_string()
+}
+
+fn get_allowlist_file() -> String {
+    paths::get_config_path("allowlist.json").to_string_lossy().to_string()
+}
+
+fn get_unblock_requests_file() -> String {
+    paths::get_config_path("unblock_requests.json").to_string_lossy().to_string()
+}
+
+/// Downloaded list contents, kept so lists work after a restart without network
+fn get_list_data_file(id: &str) -> String {
+    paths::get_config_path(&format!("blocklists/{}.txt", id)).to_string_lossy().to_string()
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Lowercase, no trailing dot, no wildcard prefix. None if it isn't a plausible domain.
+pub fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().trim_start_matches("*.").trim_end_matches('.').to_lowercase();
+    let valid = !domain.is_empty()
+        && domain.len() <= 253
+        && domain.split('.').all(|l| !l.is_empty() && l.len() <= 63 && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    if valid { Some(domain) } else { None }
+}
+
+/// True if `name` or any parent domain of it is in `set`
+pub fn matches_suffix(set: &HashSet<String>, name: &str) -> bool {
+    let name = name.trim_end_matches('.').to_lowercase();
+    let mut rest = name.as_str();
+    loop {
+        if set.contains(rest) {
+            return true;
+        }
+        match rest.find('.') {
+            Some(pos) => rest = &rest[pos + 1..],
+            None => return false,
+        }
+    }
+}
+
+// --- Parsers ---
+
+fn parse_hosts(data: &str) -> ParsedList {
+    let mut list = ParsedList::default();
+    for line in data.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut fields = line.split_whitespace();
+        let ip = match fields.next() {
+            Some(ip) => ip,
+            None => continue,
+        };
+        // Some lists are bare domains despite being published as hosts files
+        if ip.parse::<std::net::IpAddr>().is_err() {
+            list.blocked.extend(normalize_domain(ip));
+            continue;
+        }
+        for host in fields {
+            if matches!(host, "localhost" | "localhost.localdomain" | "local" | "broadcasthost" | "ip6-localhost" | "ip6-loopback") {
+                continue;
+            }
+            list.blocked.extend(normalize_domain(host));
+        }
+    }
+    list
+}
+
+/// DNS-level AdBlock rules only: "||domain^" and "@@||domain^". Rules with paths,
+/// wildcards or modifiers other than $important don't map to a domain and are skipped.
+fn parse_adblock(data: &str) -> ParsedList {
+    let mut list = ParsedList::default();
+    for line in data.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('!') || line.starts_with('[') || line.contains("##") {
+            continue;
+        }
+        let (rule, exception) = match line.strip_prefix("@@") {
+            Some(rest) => (rest, true),
+            None => (line, false),
+        };
+        let (rule, modifiers) = rule.split_once('$').unwrap_or((rule, ""));
+        if !modifiers.is_empty() && modifiers != "important" {
+            continue;
+        }
+        let domain = match rule.strip_prefix("||").and_then(|r| r.strip_suffix('^').or(Some(r))) {
+            Some(d) if !d.contains('/') && !d.contains('*') => d,
+            _ => continue,
+        };
+        if let Some(domain) = normalize_domain(domain) {
+            if exception { list.allowed.insert(domain); } else { list.blocked.insert(domain); }
+        }
+    }
+    list
+}
+
+/// CNAME "." (NXDOMAIN) and "*." (NODATA) block; CNAME "rpz-passthru." allows.
+/// Owner names are relative to the policy zone's $ORIGIN.
+fn parse_rpz(data: &str) -> ParsedList {
+    let mut list = ParsedList::default();
+    let mut origin = String::new();
+    for line in data.lines() {
+        let line = line.split(';').next().unwrap_or("").trim();
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        if fields[0].eq_ignore_ascii_case("$ORIGIN") {
+            origin = fields.get(1).map(|o| o.trim_end_matches('.').to_lowercase()).unwrap_or_default();
+            continue;
+        }
+        // owner [ttl] [class] CNAME target
+        let cname = match fields.iter().position(|f| f.eq_ignore_ascii_case("CNAME")) {
+            Some(pos) if pos > 0 && pos + 1 < fields.len() => pos,
+            _ => continue,
+        };
+        let owner = fields[0].to_lowercase();
+        let owner = match owner.strip_suffix('.') {
+            Some(absolute) => match absolute.strip_suffix(&format!(".{}", origin)) {
+                Some(relative) if !origin.is_empty() => relative.to_string(),
+                _ => absolute.to_string(),
+            },
+            None => owner.clone(),
+        };
+        let domain = match normalize_domain(&owner) {
+            Some(d) => d,
+            None => continue,
+        };
+        match fields[cname + 1].to_lowercase().as_str() {
+            "." | "*." => { list.blocked.insert(domain); }
+            "rpz-passthru." => { list.allowed.insert(domain); }
+            _ => {} // Local-data rewrites aren't supported
+        }
+    }
+    list
+}
+
+fn parse_domains(data: &str) -> ParsedList {
+    ParsedList {
+        blocked: data.lines()
+            .map(|l| l.split('#').next().unwrap_or("").trim())
+            .filter_map(normalize_domain)
+            .collect(),
+        allowed: HashSet::new(),
+    }
+}
+
+pub fn parse_list(format: ListFormat, data: &str) -> ParsedList {
+    match format {
+        ListFormat::Hosts => parse_hosts(data),
+        ListFormat::Adblock => parse_adblock(data),
+        ListFormat::Rpz => parse_rpz(data),
+        ListFormat::Domains => parse_domains(data),
+    }
+}
+
+// --- Lookup ---
+
+/// Why a name is blocked
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockMatch {
+    pub source: String, // List name, "Manual" for the blacklist
+    pub mode: Option<BlockMode>, // The list's own block mode, if it overrides the global one
+}
+
+/// The list blocking `name`, or None if it's allowed.
+/// Without a policy every enabled list applies; with one, only the lists it selects.
+pub fn blocked_by(name: &str, policy: Option<&FilterPolicy>) -> Option<BlockMatch> {
+    if matches_suffix(&ALLOWLIST.read().unwrap(), name) {
+        return None;
+    }
+    let loaded = LOADED.read().unwrap();
+    let applied: Vec<&LoadedList> = loaded.iter()
+        .filter(|l| policy.is_none_or(|p| p.uses_list(&l.id, &l.category)))
+        .collect();
+    if applied.iter().any(|l| matches_suffix(&l.domains.allowed, name)) {
+        return None;
+    }
+    if policy.is_none_or(|p| p.manual_blacklist) && matches_suffix(&super::BLACKLIST_CACHE.read().unwrap(), name) {
+        return Some(BlockMatch { source: "Manual".to_string(), mode: None });
+    }
+    applied.iter().find(|l| matches_suffix(&l.domains.blocked, name))
+        .map(|l| BlockMatch { source: l.name.clone(), mode: l.block_mode })
+}
+
+// --- Persistence ---
+
+fn save_subscriptions(subscriptions: &[BlocklistSubscription]) -> Result<(), String> {
+    let serialized = serde_json::to_string_pretty(subscriptions).map_err(|e| e.to_string())?;
+    post::write_file(&get_subscriptions_file(), &serialized)
+}
+
+/// Rebuild the in-memory lists from the enabled subscriptions' saved data
+fn reload_lists() {
+    let subscriptions = SUBSCRIPTIONS.read().unwrap().clone();
+    let loaded: Vec<LoadedList> = subscriptions.iter()
+        .filter(|s| s.enabled)
+        .filter_map(|s| {
+            let data = fetch::read_file(&get_list_data_file(&s.id)).ok()?;
+            Some(LoadedList { id: s.id.clone(), category: s.category.clone(), block_mode: s.block_mode, name: s.name.clone(), domains: parse_list(s.format, &data) })
+        })
+        .collect();
+    *LOADED.write().unwrap() = loaded;
+}
+
+pub fn load_blocklists() {
+    if let Ok(data) = fetch::read_file(&get_subscriptions_file()) {
+        *SUBSCRIPTIONS.write().unwrap() = serde_json::from_str(&data).unwrap_or_default();
+    }
+    if let Ok(data) = fetch::read_file(&get_allowlist_file()) {
+        let list: Vec<String> = serde_json::from_str(&data).unwrap_or_default();
+        *ALLOWLIST.write().unwrap() = list.iter().filter_map(|d| normalize_domain(d)).collect();
+    }
+    if let Ok(data) = fetch::read_file(&get_unblock_requests_file()) {
+        *UNBLOCK_REQUESTS.write().unwrap() = serde_json::from_str(&data).unwrap_or_default();
+    }
+    reload_lists();
+}
+
+fn download(subscription: &BlocklistSubscription) -> Result<String, String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client.get(&subscription.url).send().map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status.as_u16()));
+    }
+    let too_large = || format!("List is larger than {} MB", MAX_LIST_BYTES / (1024 * 1024));
+    if response.content_length().is_some_and(|len| len > MAX_LIST_BYTES) {
+        return Err(too_large());
+    }
+    let mut data = Vec::new();
+    response.take(MAX_LIST_BYTES + 1).read_to_end(&mut data).map_err(|e| e.to_string())?;
+    if data.len() as u64 > MAX_LIST_BYTES {
+        return Err(too_large());
+    }
+    Ok(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// Download one list and swap it in. The previous copy stays in use if the download fails.
+fn refresh(id: &str) -> Result<usize, String> {
+    let subscription = SUBSCRIPTIONS.read().unwrap().iter().find(|s| s.id == id).cloned()
+        .ok_or_else(|| format!("Blocklist {} not found", id))?;
+    let result = download(&subscription).and_then(|data| {
+        let count = parse_list(subscription.format, &data).blocked.len();
+        if count == 0 {
+            return Err("No domains found; check the list format".to_string());
+        }
+        post::write_file(&get_list_data_file(id), &data)?;
+        Ok(count)
+    });
+
+    let mut subscriptions = SUBSCRIPTIONS.write().unwrap();
+    if let Some(s) = subscriptions.iter_mut().find(|s| s.id == id) {
+        s.last_updated = Some(now_secs());
+        match &result {
+            Ok(count) => { s.domain_count = *count; s.last_error = None; }
+            Err(e) => s.last_error = Some(e.clone()),
+        }
+    }
+    save_subscriptions(&subscriptions)?;
+    drop(subscriptions);
+
+    match &result {
+        Ok(count) => {
+            reload_lists();
+            logging::log_info(&format!("Blocklist {} updated: {} domains", subscription.name, count));
+        }
+        Err(e) => logging::log_warn(&format!("Blocklist {} update failed: {}", subscription.name, e)),
+    }
+    result
+}
+
+/// Bumped by each `spawn_refresher`, so a refresher left over from before a restart exits
+static REFRESHER_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Refresh lists whose interval has passed. Runs until the DNS server stops or another refresher starts.
+pub fn spawn_refresher() {
+    let generation = REFRESHER_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    thread::spawn(move || {
+        while super::is_server_running() && REFRESHER_GENERATION.load(Ordering::SeqCst) == generation {
+            let due: Vec<String> = SUBSCRIPTIONS.read().unwrap().iter()
+                .filter(|s| s.enabled && s.last_updated.is_none_or(|t| now_secs() >= t + s.refresh_hours.max(1) * 3600))
+                .map(|s| s.id.clone())
+                .collect();
+            for id in due {
+                let _ = refresh(&id);
+            }
+            thread::sleep(Duration::from_secs(60));
+        }
+    });
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub fn list_blocklists() -> Vec<BlocklistSubscription> {
+    SUBSCRIPTIONS.read().unwrap().clone()
+}
+
+/// Add a subscription (empty id) or update an existing one. New lists are downloaded in the background.
+#[tauri::command]
+pub fn save_blocklist(mut subscription: BlocklistSubscription) -> Result<BlocklistSubscription, String> {
+    if subscription.name.trim().is_empty() {
+        return Err("Name is required".to_string());
+    }
+    if !subscription.url.starts_with("http://") && !subscription.url.starts_with("https://") {
+        return Err("URL must start with http:// or https://".to_string());
+    }
+    let is_new = subscription.id.is_empty();
+    {
+        let mut subscriptions = SUBSCRIPTIONS.write().unwrap();
+        if is_new {
+            subscription.id = Uuid::new_v4().to_string();
+            subscription.last_updated = None;
+            subscription.domain_count = 0;
+            subscription.last_error = None;
+            subscriptions.push(subscription.clone());
+        } else {
+            let existing = subscriptions.iter_mut().find(|s| s.id == subscription.id)
+                .ok_or_else(|| format!("Blocklist {} not found", subscription.id))?;
+            // Download state belongs to the server, not the editor
+            subscription.last_updated = existing.last_updated;
+            subscription.domain_count = existing.domain_count;
+            subscription.last_error = existing.last_error.clone();
+            *existing = subscription.clone();
+        }
+        save_subscriptions(&subscriptions)?;
+    }
+    logging::log_event("system".into(), "save_blocklist".into(), subscription.name.clone());
+    sync_http_redirect();
+
+    if is_new && subscription.enabled {
+        let id = subscription.id.clone();
+        thread::spawn(move || refresh(&id));
+    } else {
+        reload_lists();
+    }
+    SUBSCRIPTIONS.read().unwrap().iter().find(|s| s.id == subscription.id).cloned()
+        .ok_or_else(|| "Blocklist vanished while saving".to_string())
+}
+
+#[tauri::command]
+pub fn remove_blocklist(id: String) -> Result<(), String> {
+    let removed = {
+        let mut subscriptions = SUBSCRIPTIONS.write().unwrap();
+        let pos = subscriptions.iter().position(|s| s.id == id).ok_or_else(|| format!("Blocklist {} not found", id))?;
+        let removed = subscriptions.remove(pos);
+        save_subscriptions(&subscriptions)?;
+        removed
+    };
+    let _ = std::fs::remove_file(get_list_data_file(&id));
+    reload_lists();
+    sync_http_redirect();
+    logging::log_event("system".into(), "remove_blocklist".into(), removed.name);
+    Ok(())
+}
+
+/// Whether any blocked name is answered with the block page, globally or by a list's own mode
+pub fn block_page_in_use(dns_config: &config::DnsConfig) -> bool {
+    dns_config.block_mode == BlockMode::BlockPage
+        || SUBSCRIPTIONS.read().unwrap().iter().any(|s| s.enabled && s.block_mode == Some(BlockMode::BlockPage))
+}
+
+/// A list switching to the block page needs port 80 redirected to us
+fn sync_http_redirect() {
+    if let Ok(cfg) = config::load_setup_config() {
+        cportal::sync_http_redirect(&cfg);
+    }
+}
+
+/// Download a list now, returning how many domains it blocks
+#[tauri::command]
+pub async fn refresh_blocklist(id: String) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || refresh(&id)).await.map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub fn get_allowlist() -> Vec<String> {
+    let mut list: Vec<String> = ALLOWLIST.read().unwrap().iter().cloned().collect();
+    list.sort();
+    list
+}
+
+#[tauri::command]
+pub fn allow_domain(domain: String) -> Result<(), String> {
+    let domain = normalize_domain(&domain).ok_or_else(|| format!("Invalid domain {}", domain))?;
+    let mut allowlist = ALLOWLIST.write().unwrap();
+    if allowlist.insert(domain.clone()) {
+        let serialized = serde_json::to_string_pretty(&allowlist.iter().collect::<Vec<_>>()).map_err(|e| e.to_string())?;
+        post::write_file(&get_allowlist_file(), &serialized)?;
+        logging::log_event("system".into(), "allow_domain".into(), domain);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn disallow_domain(domain: String) -> Result<(), String> {
+    let domain = normalize_domain(&domain).unwrap_or(domain);
+    let mut allowlist = ALLOWLIST.write().unwrap();
+    if allowlist.remove(&domain) {
+        let serialized = serde_json::to_string_pretty(&allowlist.iter().collect::<Vec<_>>()).map_err(|e| e.to_string())?;
+        post::write_file(&get_allowlist_file(), &serialized)?;
+        logging::log_event("system".into(), "disallow_domain".into(), domain);
+    }
+    Ok(())
+}
+
+fn save_unblock_requests(requests: &[UnblockRequest]) -> Result<(), String> {
+    let serialized = serde_json::to_string_pretty(requests).map_err(|e| e.to_string())?;
+    post::write_file(&get_unblock_requests_file(), &serialized)
+}
+
+/// Per-client throttle for unblock requests, so one client can't rewrite the request file in a loop
+/// or push everyone else's requests out of the queue
+fn throttle_unblock_request(requests: &[UnblockRequest], client_ip: &str, now: u64) -> Result<(), String> {
+    if requests.iter().filter(|r| r.client_ip == client_ip).count() >= MAX_UNBLOCK_REQUESTS_PER_CLIENT {
+        return Err("You already have several requests waiting for the administrator.".to_string());
+    }
+    let mut last = LAST_UNBLOCK_REQUEST.lock().unwrap();
+    last.retain(|_, t| now < *t + UNBLOCK_REQUEST_INTERVAL_SECS);
+    if last.contains_key(client_ip) {
+        return Err("Please wait a few seconds before sending another request.".to_string());
+    }
+    last.insert(client_ip.to_string(), now);
+    Ok(())
+}
+
+/// Queue a block page request for review. Repeats from the same client for the same domain are ignored.
+pub fn request_unblock(domain: &str, client_ip: &str, reason: &str) -> Result<(), String> {
+    let domain = normalize_domain(domain).ok_or_else(|| format!("Invalid domain {}", domain))?;
+    let policy = policy::for_client(client_ip);
+    let source = blocked_by(&domain, policy.as_deref()).ok_or_else(|| format!("{} is not blocked", domain))?.source;
+    let mut requests = UNBLOCK_REQUESTS.write().unwrap();
+    if requests.iter().any(|r| r.domain == domain && r.client_ip == client_ip) {
+        return Ok(());
+    }
+    throttle_unblock_request(&requests, client_ip, now_secs())?;
+    if requests.len() >= MAX_UNBLOCK_REQUESTS {
+        requests.remove(0);
+    }
+    requests.push(UnblockRequest {
+        id: Uuid::new_v4().to_string(),
+        domain: domain.clone(),
+        client_ip: client_ip.to_string(),
+        source,
+        reason: reason.chars().take(500).collect(),
+        requested_at: now_secs(),
+    });
+    save_unblock_requests(&requests)?;
+    logging::log_event(client_ip.to_string(), "request_unblock".into(), domain);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_unblock_requests() -> Vec<UnblockRequest> {
+    UNBLOCK_REQUESTS.read().unwrap().clone()
+}
+
+/// Close an unblock request; approving it adds the domain to the allowlist
+#[tauri::command]
+pub fn resolve_unblock_request(id: String, approve: bool) -> Result<(), String> {
+    let request = {
+        let mut requests = UNBLOCK_REQUESTS.write().unwrap();
+        let pos = requests.iter().position(|r| r.id == id).ok_or_else(|| format!("Request {} not found", id))?;
+        let request = requests.remove(pos);
+        save_unblock_requests(&requests)?;
+        request
+    };
+    if approve {
+        allow_domain(request.domain.clone())?;
+    }
+    let action = if approve { "approve_unblock" } else { "deny_unblock" };
+    logging::log_event("system".into(), action.into(), request.domain);
+    Ok(())
+}
+
+/// Which list, if any, blocks `domain`, optionally for the client at `client` rather than by default
+#[tauri::command]
+pub fn check_domain_blocked(domain: String, client: Option<String>) -> Option<String> {
+    let policy = client.as_deref().and_then(policy::for_client);
+    blocked_by(&domain, policy.as_deref()).map(|m| m.source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_formats() {
+        let hosts = parse_list(ListFormat::Hosts, "# comment\n0.0.0.0 ads.example.com tracker.example.net\n127.0.0.1 localhost\n::1 ip6-localhost\n");
+        assert_eq!(hosts.blocked, ["ads.example.com", "tracker.example.net"].iter().map(|s| s.to_string()).collect());
+
+        let abp = parse_list(ListFormat::Adblock, "! Title\n||Ads.Example.com^\n||cdn.example.org^$third-party\n@@||good.example.com^\n##.banner\n||example.org/path^\n");
+        assert!(abp.blocked.contains("ads.example.com"));
+        assert_eq!(abp.blocked.len(), 1);
+        assert!(abp.allowed.contains("good.example.com"));
+
+        let rpz = parse_list(ListFormat::Rpz, "$ORIGIN rpz.local.\n@ SOA ns. admin. 1 3600 600 86400 300\nads.example.com CNAME .\n*.track.example.com 300 IN CNAME *.\nok.example.com CNAME rpz-passthru.\nabs.example.net.rpz.local. CNAME .\n");
+        assert!(rpz.blocked.contains("ads.example.com"));
+        assert!(rpz.blocked.contains("track.example.com"));
+        assert!(rpz.blocked.contains("abs.example.net"));
+        assert!(rpz.allowed.contains("ok.example.com"));
+    }
+
+    #[test]
+    fn test_suffix_matching() {
+        let set: HashSet<String> = ["ads.example.com".to_string()].into_iter().collect();
+        assert!(matches_suffix(&set, "ads.example.com"));
+        assert!(matches_suffix(&set, "X.Ads.Example.com."));
+        assert!(!matches_suffix(&set, "example.com"));
+        assert!(!matches_suffix(&set, "badads.example.com"));
+    }
+
+    #[test]
+    fn test_unblock_request_throttle() {
+        let request = |domain: &str| UnblockRequest {
+            id: domain.into(),
+            domain: domain.into(),
+            client_ip: "10.9.9.9".into(),
+            source: "Manual".into(),
+            reason: String::new(),
+            requested_at: 0,
+        };
+        assert!(throttle_unblock_request(&[], "10.9.9.9", 1000).is_ok());
+        assert!(throttle_unblock_request(&[], "10.9.9.9", 1005).is_err());
+        assert!(throttle_unblock_request(&[], "10.9.9.10", 1005).is_ok());
+        assert!(throttle_unblock_request(&[], "10.9.9.9", 1010).is_ok());
+
+        let full: Vec<UnblockRequest> = (0..MAX_UNBLOCK_REQUESTS_PER_CLIENT).map(|n| request(&format!("site{}.example", n))).collect();
+        assert!(throttle_unblock_request(&full, "10.9.9.9", 2000).is_err());
+        assert!(throttle_unblock_request(&full, "10.9.9.11", 2000).is_ok());
+    }
+}
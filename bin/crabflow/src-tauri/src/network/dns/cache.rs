@@ -0,0 +1,271 @@
+// src-tauri/src/network/dns/cache.rs
+// TTL-aware cache of upstream responses, with RFC 2308 negative caching and LRU eviction
+
+use serde::Serialize;
+use crate::sysmodules::{config, logging};
+use super::wire::{Message, RData, RCODE_NOERROR, RCODE_NXDOMAIN};
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use lazy_static::lazy_static;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct CacheKey {
+    name: String, // Lowercased
+    qtype: u16,
+    qclass: u16,
+    dnssec_ok: bool, // DO responses carry signatures, so they are cached apart
+}
+
+struct CacheEntry {
+    response: Message,
+    stored_at: Instant,
+    ttl: u32,
+    negative: bool,
+}
+
+struct DnsCache {
+    entries: LruCache<CacheKey, CacheEntry>,
+    enabled: bool,
+    max_ttl: u32,
+    negative_ttl: u32,
+    dnssec_validation: bool,
+}
+
+lazy_static! {
+    static ref CACHE: Mutex<DnsCache> = {
+        let defaults = config::DnsConfig::default();
+        Mutex::new(DnsCache {
+            entries: LruCache::new(capacity(defaults.cache_size)),
+            enabled: defaults.cache_enabled,
+            max_ttl: defaults.cache_max_ttl,
+            negative_ttl: defaults.cache_negative_ttl,
+            dnssec_validation: defaults.dnssec_validation,
+        })
+    };
+}
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+fn capacity(size: usize) -> NonZeroUsize {
+    NonZeroUsize::new(size.max(1)).unwrap_or(NonZeroUsize::MIN)
+}
+
+/// Apply cache settings; shrinking evicts the least recently used entries
+pub fn configure(dns_config: &config::DnsConfig) {
+    let mut cache = CACHE.lock().unwrap();
+    if cache.entries.cap() != capacity(dns_config.cache_size) {
+        cache.entries.resize(capacity(dns_config.cache_size));
+    }
+    // Entries cached before validation was switched on weren't checked; after, they carry AD bits
+    if (cache.enabled && !dns_config.cache_enabled) || cache.dnssec_validation != dns_config.dnssec_validation {
+        cache.entries.clear();
+    }
+    cache.enabled = dns_config.cache_enabled;
+    cache.max_ttl = dns_config.cache_max_ttl;
+    cache.negative_ttl = dns_config.cache_negative_ttl;
+    cache.dnssec_validation = dns_config.dnssec_validation;
+}
+
+fn key_for(request: &Message) -> Option<CacheKey> {
+    // Multi-question queries are rare enough not to be worth caching
+    match request.questions.as_slice() {
+        [q] => Some(CacheKey {
+            name: q.name.to_lowercase(),
+            qtype: q.qtype.to_u16(),
+            qclass: q.qclass,
+            dnssec_ok: request.edns.as_ref().is_some_and(|e| e.dnssec_ok),
+        }),
+        _ => None,
+    }
+}
+
+/// How long a response may be cached, and whether it is negative. None = don't cache.
+fn cache_ttl(response: &Message, max_ttl: u32, negative_ttl: u32) -> Option<(u32, bool)> {
+    if response.truncated {
+        return None;
+    }
+    let positive = response.rcode == RCODE_NOERROR && !response.answers.is_empty();
+    if positive {
+        let ttl = response.answers.iter().map(|rr| rr.ttl).min()?.min(max_ttl);
+        return if ttl > 0 { Some((ttl, false)) } else { None };
+    }
+    if response.rcode != RCODE_NOERROR && response.rcode != RCODE_NXDOMAIN {
+        return None; // SERVFAIL, REFUSED, ...
+    }
+    // NXDOMAIN / NODATA: the SOA in the authority section says how long (RFC 2308 section 5).
+    // Without one the answer must not be cached.
+    let ttl = response.authorities.iter().find_map(|rr| match &rr.rdata {
+        RData::Soa { minimum, .. } => Some(rr.ttl.min(*minimum)),
+        _ => None,
+    })?.min(negative_ttl);
+    if ttl > 0 { Some((ttl, true)) } else { None }
+}
+
+/// Cached answer for `request`, with TTLs counted down and the request's ID
+pub fn lookup(request: &Message) -> Option<Vec<u8>> {
+    let key = key_for(request)?;
+    let mut cache = CACHE.lock().unwrap();
+    if !cache.enabled {
+        return None;
+    }
+    let hit = cache.entries.get(&key).map(|entry| (entry.stored_at.elapsed().as_secs() as u32, entry.ttl, entry.response.clone()));
+    let (elapsed, ttl, mut response) = match hit {
+        Some((elapsed, ttl, response)) if elapsed < ttl => (elapsed, ttl, response),
+        Some(_) => {
+            cache.entries.pop(&key);
+            MISSES.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        None => {
+            MISSES.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+    };
+    drop(cache);
+    HITS.fetch_add(1, Ordering::Relaxed);
+
+    let template = Message::response_to(request);
+    response.id = request.id;
+    response.questions = template.questions; // Keep the client's capitalization
+    response.recursion_desired = template.recursion_desired;
+    response.edns = template.edns;
+    for rr in response.answers.iter_mut().chain(response.authorities.iter_mut()).chain(response.additionals.iter_mut()) {
+        rr.ttl = rr.ttl.min(ttl).saturating_sub(elapsed);
+    }
+    Some(response.to_bytes())
+}
+
+/// Remember an upstream response to `request`, if it is cacheable
+pub fn store(request: &Message, upstream: &[u8]) {
+    let key = match key_for(request) {
+        Some(k) => k,
+        None => return,
+    };
+    let response = match Message::parse(upstream) {
+        Ok(r) if r.id == request.id => r,
+        _ => return,
+    };
+    let mut cache = CACHE.lock().unwrap();
+    if !cache.enabled {
+        return;
+    }
+    if let Some((ttl, negative)) = cache_ttl(&response, cache.max_ttl, cache.negative_ttl) {
+        cache.entries.put(key, CacheEntry { response, stored_at: Instant::now(), ttl, negative });
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DnsCacheStats {
+    pub enabled: bool,
+    pub entries: usize,
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DnsCacheEntry {
+    pub name: String,
+    pub query_type: String,
+    pub status: String, // "NOERROR", "NXDOMAIN" or "NODATA"
+    pub ttl_remaining: u32,
+    pub answers: Vec<String>,
+}
+
+#[tauri::command]
+pub fn get_dns_cache_stats() -> DnsCacheStats {
+    let cache = CACHE.lock().unwrap();
+    DnsCacheStats {
+        enabled: cache.enabled,
+        entries: cache.entries.len(),
+        capacity: cache.entries.cap().get(),
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+    }
+}
+
+/// Cached entries, most recently used first
+#[tauri::command]
+pub fn list_dns_cache(limit: usize) -> Vec<DnsCacheEntry> {
+    let cache = CACHE.lock().unwrap();
+    cache.entries.iter()
+        .filter_map(|(key, entry)| {
+            let elapsed = entry.stored_at.elapsed().as_secs() as u32;
+            if elapsed >= entry.ttl {
+                return None;
+            }
+            let status = match (entry.negative, entry.response.rcode) {
+                (true, RCODE_NXDOMAIN) => "NXDOMAIN",
+                (true, _) => "NODATA",
+                _ => "NOERROR",
+            };
+            Some(DnsCacheEntry {
+                name: key.name.clone(),
+                query_type: super::wire::RecordType::from_u16(key.qtype).to_string(),
+                status: status.to_string(),
+                ttl_remaining: entry.ttl - elapsed,
+                answers: entry.response.answers.iter().map(|rr| format!("{} {}", rr.rtype, rr.rdata)).collect(),
+            })
+        })
+        .take(limit)
+        .collect()
+}
+
+/// Drop cached entries for `name` (any type), or everything when no name is given.
+/// Returns the number of entries removed.
+#[tauri::command]
+pub fn flush_dns_cache(name: Option<String>) -> usize {
+    let mut cache = CACHE.lock().unwrap();
+    let removed = match name.map(|n| n.trim_end_matches('.').to_lowercase()) {
+        Some(name) => {
+            let keys: Vec<CacheKey> = cache.entries.iter().map(|(k, _)| k.clone()).filter(|k| k.name == name).collect();
+            for key in &keys {
+                cache.entries.pop(key);
+            }
+            keys.len()
+        }
+        None => {
+            let count = cache.entries.len();
+            cache.entries.clear();
+            count
+        }
+    };
+    logging::log_info(&format!("Flushed {} DNS cache entries", removed));
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::wire::{RecordType, ResourceRecord};
+
+    #[test]
+    fn test_cache_ttl() {
+        let query = Message::query(7, "example.com", RecordType::A);
+        let mut response = Message::response_to(&query);
+        response.answers = vec![
+            ResourceRecord::new("example.com", RecordType::Cname, 3600, RData::Cname("edge.example.net".into())),
+            ResourceRecord::new("edge.example.net", RecordType::A, 120, RData::A("192.0.2.1".parse().unwrap())),
+        ];
+        assert_eq!(cache_ttl(&response, 86400, 3600), Some((120, false)));
+        assert_eq!(cache_ttl(&response, 60, 3600), Some((60, false)));
+
+        // Negative answers take min(SOA TTL, SOA MINIMUM), and are not cached without a SOA
+        response.answers.clear();
+        response.rcode = RCODE_NXDOMAIN;
+        assert_eq!(cache_ttl(&response, 86400, 3600), None);
+        response.authorities.push(ResourceRecord::new("example.com", RecordType::Soa, 900, RData::Soa {
+            mname: "ns.example.com".into(), rname: "hostmaster.example.com".into(),
+            serial: 1, refresh: 3600, retry: 600, expire: 86400, minimum: 300,
+        }));
+        assert_eq!(cache_ttl(&response, 86400, 3600), Some((300, true)));
+        assert_eq!(cache_ttl(&response, 86400, 60), Some((60, true)));
+
+        response.rcode = super::super::wire::RCODE_SERVFAIL;
+        assert_eq!(cache_ttl(&response, 86400, 3600), None);
+    }
+}
@@ -0,0 +1,227 @@
+// src-tauri/src/network/dns/dhcp_names.rs
+// Dynamic DNS for DHCP clients: A and PTR answers for active leases under the local domain
+
+use serde::Serialize;
+use crate::sysmodules::{config, logging};
+use crate::network::dhcp::{self, Lease, LeaseState};
+use super::wire::RecordType;
+use std::collections::{BTreeMap, HashSet};
+use std::net::Ipv4Addr;
+use std::sync::{RwLock, RwLockReadGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::lazy_static;
+
+/// Lease-derived records are short-lived so expiry and renames propagate quickly
+pub const DHCP_RECORD_TTL: u32 = 60;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DhcpName {
+    pub fqdn: String,
+    pub ip: String,
+    pub mac: String,
+    pub expires_at: Option<u64>,
+    pub renamed: bool, // Suffixed because another client or a manual record already held the hostname
+}
+
+/// What the local domain says about a query
+#[derive(Debug, PartialEq)]
+pub enum LocalAnswer {
+    A(Ipv4Addr),
+    Ptr(String),
+    NoData,   // Name exists, but not with the requested type
+    NxDomain, // Under the local domain, but no active lease has it
+}
+
+/// Set when leases or manual records change; the next lookup rebuilds the names
+static STALE: AtomicBool = AtomicBool::new(true);
+
+/// Names assigned under one local domain
+#[derive(Default)]
+struct NameTable {
+    domain: String,
+    names: BTreeMap<String, DhcpName>, // By fqdn
+}
+
+lazy_static! {
+    // Last assignment, kept so a name stays with the client that registered it first
+    static ref DHCP_NAMES: RwLock<NameTable> = RwLock::new(NameTable::default());
+}
+
+/// Mark the names out of date after a lease or a manual DNS record changed
+pub fn invalidate() {
+    STALE.store(true, Ordering::Release);
+}
+
+fn normalize_domain(domain: &str) -> String {
+    domain.trim_matches('.').to_lowercase()
+}
+
+/// Turn an option 12 hostname into a single DNS label, or None if nothing usable is left
+fn sanitize_label(hostname: &str) -> Option<String> {
+    let first = hostname.split('.').next().unwrap_or("").to_lowercase();
+    let label: String = first.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '-' }).collect();
+    let label = label.trim_matches('-');
+    let label = &label[..label.len().min(63)];
+    if label.is_empty() || label == "unknown" {
+        return None;
+    }
+    Some(label.to_string())
+}
+
+/// Name tried when the hostname is taken: the last two MAC octets, e.g. laptop-eeff
+fn collision_label(label: &str, lease: &Lease) -> String {
+    let hex: String = lease.mac.chars().filter(|c| c.is_ascii_hexdigit()).collect::<String>().to_lowercase();
+    let tag = if hex.len() >= 4 { hex[hex.len() - 4..].to_string() } else { lease.ip.rsplit('.').next().unwrap_or("0").to_string() };
+    format!("{}-{}", &label[..label.len().min(58)], tag)
+}
+
+/// Work out which active IPv4 lease owns which name. Names in `reserved` (manual records) are never handed out.
+fn assign(leases: &[Lease], domain: &str, reserved: &HashSet<String>, previous: &BTreeMap<String, DhcpName>, now: u64) -> BTreeMap<String, DhcpName> {
+    let fqdn = |label: &str| format!("{}.{}", label, domain);
+    let mut live: Vec<(&Lease, String, Ipv4Addr)> = leases.iter()
+        .filter(|l| l.state == LeaseState::Active && !l.is_expired(now))
+        .filter_map(|l| Some((l, sanitize_label(&l.hostname)?, l.ip.parse().ok()?)))
+        .collect();
+    // Static reservations claim names before dynamic clients; lowest IP first keeps the order stable
+    live.sort_by_key(|(l, _, ip)| (!l.static_lease, *ip));
+
+    let mut names: BTreeMap<String, DhcpName> = BTreeMap::new();
+    let entry = |lease: &Lease, name: String, renamed: bool| DhcpName {
+        fqdn: name,
+        ip: lease.ip.clone(),
+        mac: lease.mac.clone(),
+        expires_at: lease.expires_at,
+        renamed,
+    };
+
+    // First pass: clients keep a name they already hold, so a newcomer can't take it over
+    let mut pending = Vec::new();
+    for (lease, label, _) in live {
+        let candidates = [(fqdn(&label), false), (fqdn(&collision_label(&label, lease)), true)];
+        let held = candidates.iter().find(|(name, _)| {
+            !reserved.contains(name) && !names.contains_key(name)
+                && previous.get(name).is_some_and(|p| p.mac.eq_ignore_ascii_case(&lease.mac))
+        });
+        match held {
+            Some((name, renamed)) => { names.insert(name.clone(), entry(lease, name.clone(), *renamed)); }
+            None => pending.push((lease, candidates)),
+        }
+    }
+
+    // Second pass: the hostname if it is free, otherwise the MAC-suffixed fallback
+    for (lease, candidates) in pending {
+        match candidates.iter().find(|(name, _)| !reserved.contains(name) && !names.contains_key(name)) {
+            Some((name, renamed)) => { names.insert(name.clone(), entry(lease, name.clone(), *renamed)); }
+            None => logging::log_debug(&format!("No free DNS name for lease {} ({}): {} is taken", lease.ip, lease.mac, candidates[0].0)),
+        }
+    }
+    names
+}
+
+/// Rebuild the name table from the current leases
+fn refresh(domain: &str) {
+    let leases = dhcp::list_leases();
+    let reserved: HashSet<String> = super::list_records().iter().map(|r| r.name.trim_end_matches('.').to_lowercase()).collect();
+    let mut table = DHCP_NAMES.write().unwrap();
+    let names = assign(&leases, domain, &reserved, &table.names, dhcp::now_secs());
+
+    for name in names.values().filter(|n| n.renamed && !table.names.contains_key(&n.fqdn)) {
+        logging::log_warn(&format!("DNS name collision: {} ({}) registered as {}", name.ip, name.mac, name.fqdn));
+    }
+    *table = NameTable { domain: domain.to_string(), names };
+}
+
+/// The names for `domain`, rebuilt only if leases, records or the domain changed since the last lookup
+fn current(domain: &str) -> RwLockReadGuard<'static, NameTable> {
+    if STALE.swap(false, Ordering::AcqRel) || DHCP_NAMES.read().unwrap().domain != domain {
+        refresh(domain);
+    }
+    DHCP_NAMES.read().unwrap()
+}
+
+/// "4.3.2.1.in-addr.arpa" -> 1.2.3.4
+fn parse_reverse_name(name: &str) -> Option<Ipv4Addr> {
+    let rest = name.to_lowercase();
+    let rest = rest.strip_suffix(".in-addr.arpa")?;
+    let octets: Vec<u8> = rest.split('.').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    match octets.as_slice() {
+        [d, c, b, a] => Some(Ipv4Addr::new(*a, *b, *c, *d)),
+        _ => None,
+    }
+}
+
+/// Answer `name` from the lease table. None means the name isn't ours and should be forwarded.
+pub fn answer(name: &str, qtype: RecordType, domain: &str) -> Option<LocalAnswer> {
+    let domain = normalize_domain(domain);
+    let name = name.trim_end_matches('.').to_lowercase();
+    let reverse = parse_reverse_name(&name);
+    if domain.is_empty() || (reverse.is_none() && !name.ends_with(&format!(".{}", domain))) {
+        return None;
+    }
+
+    let table = current(&domain);
+    if let Some(ip) = reverse {
+        // Reverse names for addresses we never leased go upstream as before
+        let owner = table.names.values().find(|n| n.ip == ip.to_string())?;
+        return Some(if matches!(qtype, RecordType::Ptr | RecordType::Any) { LocalAnswer::Ptr(owner.fqdn.clone()) } else { LocalAnswer::NoData });
+    }
+    Some(match table.names.get(&name) {
+        Some(owner) if matches!(qtype, RecordType::A | RecordType::Any) => owner.ip.parse().map(LocalAnswer::A).unwrap_or(LocalAnswer::NoData),
+        Some(_) => LocalAnswer::NoData,
+        None => LocalAnswer::NxDomain,
+    })
+}
+
+/// Names currently registered from DHCP leases
+#[tauri::command]
+pub fn list_dhcp_names() -> Vec<DhcpName> {
+    let domain = config::load_setup_config().map(|c| c.dns.local_domain).unwrap_or_else(|_| "lan".to_string());
+    current(&normalize_domain(&domain)).names.values().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lease(ip: &str, mac: &str, hostname: &str, expires_at: Option<u64>, static_lease: bool) -> Lease {
+        Lease { ip: ip.into(), mac: mac.into(), hostname: hostname.into(), expires_at, static_lease, state: LeaseState::Active, options: BTreeMap::new(), relay_info: None }
+    }
+
+    #[test]
+    fn test_assign_names() {
+        let leases = vec![
+            lease("10.0.0.150", "aa:aa:aa:aa:11:22", "Laptop-42", Some(2000), false),
+            lease("10.0.0.20", "bb:bb:bb:bb:33:44", "laptop-42.home", None, true),
+            lease("10.0.0.151", "cc:cc:cc:cc:55:66", "old-pc", Some(500), false),
+            lease("10.0.0.152", "dd:dd:dd:dd:77:88", "printer", Some(2000), false),
+        ];
+        let reserved: HashSet<String> = ["printer.lan".to_string()].into_iter().collect();
+        let names = assign(&leases, "lan", &reserved, &BTreeMap::new(), 1000);
+
+        // The static reservation wins, the dynamic client is suffixed, expired leases disappear
+        assert_eq!(names["laptop-42.lan"].ip, "10.0.0.20");
+        assert_eq!(names["laptop-42-1122.lan"].ip, "10.0.0.150");
+        assert!(names["laptop-42-1122.lan"].renamed);
+        assert!(!names.contains_key("old-pc.lan"));
+        assert!(!names.contains_key("printer.lan"));
+        assert_eq!(names["printer-7788.lan"].ip, "10.0.0.152");
+
+        // A later client claiming a held name doesn't displace the current owner
+        let mut next = leases.clone();
+        next[1].static_lease = false;
+        next[1].ip = "10.0.0.160".into();
+        next[1].hostname = "laptop-42".into();
+        next[1].mac = "ee:ee:ee:ee:99:00".into();
+        let names = assign(&next, "lan", &HashSet::new(), &names, 1000);
+        assert_eq!(names["laptop-42-1122.lan"].ip, "10.0.0.150");
+        assert_eq!(names["laptop-42.lan"].ip, "10.0.0.160");
+    }
+
+    #[test]
+    fn test_reverse_name() {
+        assert_eq!(parse_reverse_name("20.0.0.10.in-addr.arpa"), Some(Ipv4Addr::new(10, 0, 0, 20)));
+        assert_eq!(parse_reverse_name("0.10.in-addr.arpa"), None);
+        assert_eq!(sanitize_label("My Phone_2.local"), Some("my-phone-2".to_string()));
+        assert_eq!(sanitize_label("Unknown"), None);
+    }
+}
@@ -0,0 +1,833 @@
+// src-tauri/src/network/dns/dnssec.rs
+// DNSSEC validation of forwarded answers (RFC 4033-4035, NSEC3 per RFC 5155): signatures are checked
+// along the chain of trust from the built-in root trust anchor down to the zone that signed the answer
+
+use super::upstream;
+use super::wire::{canonical_name, Edns, Message, RData, RecordType, ResourceRecord, EDNS_OPTION_EDE, EDNS_UDP_SIZE, RCODE_NOERROR, RCODE_NXDOMAIN, RCODE_SERVFAIL};
+use ring::{digest, signature};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use lazy_static::lazy_static;
+
+/// Root zone KSK-2017 and KSK-2024 as DS records: key tag, algorithm, digest type, digest
+const ROOT_ANCHORS: [(u16, u8, u8, &str); 2] = [
+    (20326, 8, 2, "e06d44b80b8f1d39a95c0b0d7c65d08458e880409bbc683457104237c7f8ec8d"),
+    (38696, 8, 2, "683d2d0acb8c9b712a1948b27f741219298d0a450d612c483af444a4c0fb2b16"),
+];
+/// Longest a zone's keys, or its lack of them, are trusted before being looked up again
+const MAX_TRUST_TTL: u32 = 3600;
+/// A broken chain is remembered this long, so it isn't looked up again for every client query
+const BOGUS_TTL: u32 = 60;
+const MAX_TRUST_ENTRIES: usize = 10000;
+/// NSEC3 records hashed more often than this prove nothing (RFC 9276)
+const MAX_NSEC3_ITERATIONS: u16 = 150;
+const FLAG_ZONE_KEY: u16 = 0x0100;
+const FLAG_REVOKED: u16 = 0x0080;
+/// Extended DNS Error info code for "DNSSEC Bogus"
+const EDE_DNSSEC_BOGUS: u16 = 6;
+const MAX_REASON_LEN: usize = 200;
+/// DNAME, which we don't decode otherwise
+const TYPE_DNAME: RecordType = RecordType::Other(39);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Validation {
+    Secure,
+    Insecure, // Unsigned zone, algorithms we can't check, or an upstream error with nothing to check
+    Bogus(String),
+}
+
+/// A forwarded response after validation, ready for the client
+pub struct Validated {
+    pub response: Vec<u8>,
+    pub upstream: String,
+    pub outcome: Validation,
+}
+
+#[derive(Clone, Debug)]
+struct Dnskey {
+    flags: u16,
+    algorithm: u8,
+    key_tag: u16,
+    public_key: Vec<u8>,
+    rdata: Vec<u8>,
+}
+
+struct Ds {
+    key_tag: u16,
+    algorithm: u8,
+    digest_type: u8,
+    digest: Vec<u8>,
+}
+
+struct Rrsig {
+    type_covered: u16,
+    algorithm: u8,
+    labels: u8,
+    original_ttl: u32,
+    expiration: u32,
+    inception: u32,
+    key_tag: u16,
+    signer: String,
+    signature: Vec<u8>,
+    signed_header: Vec<u8>, // RDATA before the signature with the signer lowercased: where the signed data starts
+}
+
+struct Nsec {
+    owner: String,
+    next: String,
+    types: Vec<u16>,
+}
+
+struct Nsec3 {
+    owner_hash: String, // First label of the owner name, base32hex
+    algorithm: u8,
+    opt_out: bool,
+    iterations: u16,
+    salt: Vec<u8>,
+    next_hash: String,
+    types: Vec<u16>,
+}
+
+/// What we know about the zone a name falls in
+#[derive(Clone, Debug)]
+enum Trust {
+    Secure { zone: String, keys: Vec<Dnskey> },
+    Insecure,
+}
+
+/// Records of one name and type from a section, with the signatures covering them
+struct RrSet<'a> {
+    name: String, // Lowercased
+    rtype: RecordType,
+    records: Vec<&'a ResourceRecord>,
+    sigs: Vec<Rrsig>,
+}
+
+/// Trust in a zone, or why its chain is bogus; with the expiry
+type TrustEntry = (Instant, Result<Trust, String>);
+
+lazy_static! {
+    // Lowercased name -> trust in the zone enclosing it
+    static ref TRUST_CACHE: Mutex<HashMap<String, TrustEntry>> = Mutex::new(HashMap::new());
+}
+
+fn now_serial() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as u32).unwrap_or(0)
+}
+
+fn query_id() -> u16 {
+    (SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0) & 0xFFFF) as u16
+}
+
+fn normalize(name: &str) -> String {
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn zone_label(zone: &str) -> &str {
+    if zone.is_empty() { "." } else { zone }
+}
+
+fn label_count(name: &str) -> usize {
+    name.split('.').filter(|l| !l.is_empty() && *l != "*").count()
+}
+
+/// "www.example.com" -> "example.com" -> "com" -> "" (the root) -> None
+fn parent_name(name: &str) -> Option<&str> {
+    if name.is_empty() {
+        return None;
+    }
+    Some(name.split_once('.').map_or("", |(_, parent)| parent))
+}
+
+fn is_subdomain(name: &str, zone: &str) -> bool {
+    let (name, zone) = (normalize(name), normalize(zone));
+    zone.is_empty() || name == zone || name.ends_with(&format!(".{}", zone))
+}
+
+/// Labels from the root down, lowercased: sorting these gives the canonical order (RFC 4034 6.1)
+fn canonical_key(name: &str) -> Vec<String> {
+    normalize(name).split('.').filter(|l| !l.is_empty()).rev().map(String::from).collect()
+}
+
+/// Whether `value` falls strictly between `owner` and `next`; the last record of a zone wraps around to the first
+fn covers<T: Ord + ?Sized>(owner: &T, next: &T, value: &T) -> bool {
+    if owner < next { owner < value && value < next } else { owner < value || value < next }
+}
+
+/// `a` is before `b` in serial number arithmetic (RFC 1982), as RRSIG validity times are
+fn serial_before(a: u32, b: u32) -> bool {
+    a != b && (b.wrapping_sub(a) as i32) > 0
+}
+
+fn hex_decode(hex: &str) -> Vec<u8> {
+    (0..hex.len()).step_by(2).filter_map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+}
+
+/// RFC 4648 base32 with the extended hex alphabet, lowercase and unpadded, as NSEC3 owner names use
+fn base32hex(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789abcdefghijklmnopqrstuv";
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in data {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+/// Cursor over RDATA; names in DNSSEC records are never compressed (RFC 4034)
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn name(&mut self) -> Option<String> {
+        let mut labels = Vec::new();
+        loop {
+            let len = self.u8()? as usize;
+            if len == 0 {
+                return Some(labels.join("."));
+            }
+            if len > 63 {
+                return None;
+            }
+            labels.push(String::from_utf8_lossy(self.take(len)?).into_owned());
+        }
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.0)
+    }
+
+    /// NSEC/NSEC3 type bitmap (RFC 4034 4.1.2)
+    fn type_bitmap(&mut self) -> Option<Vec<u16>> {
+        let mut types = Vec::new();
+        while !self.0.is_empty() {
+            let window = self.u8()? as u16;
+            let len = self.u8()? as usize;
+            for (i, byte) in self.take(len)?.iter().enumerate() {
+                types.extend((0..8).filter(|bit| byte & (0x80 >> bit) != 0).map(|bit| window * 256 + (i * 8 + bit) as u16));
+            }
+        }
+        Some(types)
+    }
+}
+
+fn raw(rr: &ResourceRecord) -> &[u8] {
+    match &rr.rdata {
+        RData::Raw(bytes) => bytes,
+        _ => &[],
+    }
+}
+
+/// RFC 4034 appendix B
+fn key_tag(rdata: &[u8]) -> u16 {
+    let mut acc: u32 = rdata.iter().enumerate().map(|(i, b)| if i & 1 == 0 { (*b as u32) << 8 } else { *b as u32 }).sum();
+    acc += (acc >> 16) & 0xFFFF;
+    (acc & 0xFFFF) as u16
+}
+
+impl Dnskey {
+    fn parse(rr: &ResourceRecord) -> Option<Dnskey> {
+        let rdata = raw(rr);
+        let mut f = Fields(rdata);
+        let flags = f.u16()?;
+        let protocol = f.u8()?;
+        let algorithm = f.u8()?;
+        if protocol != 3 {
+            return None;
+        }
+        Some(Dnskey { flags, algorithm, key_tag: key_tag(rdata), public_key: f.rest().to_vec(), rdata: rdata.to_vec() })
+    }
+}
+
+impl Ds {
+    fn parse(rr: &ResourceRecord) -> Option<Ds> {
+        let mut f = Fields(raw(rr));
+        Some(Ds { key_tag: f.u16()?, algorithm: f.u8()?, digest_type: f.u8()?, digest: f.rest().to_vec() })
+    }
+
+    fn usable(&self) -> bool {
+        supported_algorithm(self.algorithm) && matches!(self.digest_type, 1 | 2 | 4)
+    }
+
+    fn matches(&self, owner: &str, key: &Dnskey) -> bool {
+        let algorithm = match self.digest_type {
+            1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+            2 => &digest::SHA256,
+            4 => &digest::SHA384,
+            _ => return false,
+        };
+        let mut data = canonical_name(owner);
+        data.extend_from_slice(&key.rdata);
+        self.key_tag == key.key_tag && self.algorithm == key.algorithm && digest::digest(algorithm, &data).as_ref() == self.digest.as_slice()
+    }
+}
+
+impl Rrsig {
+    fn parse(rr: &ResourceRecord) -> Option<Rrsig> {
+        let rdata = raw(rr);
+        let mut f = Fields(rdata);
+        let (type_covered, algorithm, labels) = (f.u16()?, f.u8()?, f.u8()?);
+        let (original_ttl, expiration, inception, key_tag) = (f.u32()?, f.u32()?, f.u32()?, f.u16()?);
+        let signer = f.name()?;
+        let mut signed_header = rdata[..18].to_vec();
+        signed_header.extend(canonical_name(&signer));
+        Some(Rrsig { type_covered, algorithm, labels, original_ttl, expiration, inception, key_tag, signer, signature: f.rest().to_vec(), signed_header })
+    }
+}
+
+impl Nsec {
+    fn parse(rr: &ResourceRecord) -> Option<Nsec> {
+        let mut f = Fields(raw(rr));
+        Some(Nsec { owner: normalize(&rr.name), next: normalize(&f.name()?), types: f.type_bitmap()? })
+    }
+}
+
+impl Nsec3 {
+    fn parse(rr: &ResourceRecord) -> Option<Nsec3> {
+        let mut f = Fields(raw(rr));
+        let (algorithm, flags, iterations) = (f.u8()?, f.u8()?, f.u16()?);
+        let salt_len = f.u8()? as usize;
+        let salt = f.take(salt_len)?.to_vec();
+        let hash_len = f.u8()? as usize;
+        let next_hash = base32hex(f.take(hash_len)?);
+        let owner_hash = normalize(&rr.name).split('.').next()?.to_string();
+        Some(Nsec3 { owner_hash, algorithm, opt_out: flags & 1 != 0, iterations, salt, next_hash, types: f.type_bitmap()? })
+    }
+
+    /// Hashed owner name `name` would have in this record's chain; None if the parameters are unusable
+    fn hash(&self, name: &str) -> Option<String> {
+        (self.algorithm == 1 && self.iterations <= MAX_NSEC3_ITERATIONS).then(|| nsec3_hash(name, &self.salt, self.iterations))
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.hash(name).is_some_and(|h| h == self.owner_hash)
+    }
+
+    fn covers(&self, name: &str) -> bool {
+        self.hash(name).is_some_and(|h| covers(self.owner_hash.as_str(), self.next_hash.as_str(), h.as_str()))
+    }
+}
+
+/// RFC 5155 5: iterated SHA-1 over the canonical name and salt, base32hex encoded
+fn nsec3_hash(name: &str, salt: &[u8], iterations: u16) -> String {
+    let mut data = canonical_name(name);
+    data.extend_from_slice(salt);
+    let mut hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &data);
+    for _ in 0..iterations {
+        let mut data = hash.as_ref().to_vec();
+        data.extend_from_slice(salt);
+        hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &data);
+    }
+    base32hex(hash.as_ref())
+}
+
+fn supported_algorithm(algorithm: u8) -> bool {
+    matches!(algorithm, 5 | 7 | 8 | 10 | 13 | 14 | 15)
+}
+
+/// Check a signature with a DNSKEY's public key; false for algorithms we don't implement
+fn verify_signature(algorithm: u8, public_key: &[u8], message: &[u8], sig: &[u8]) -> bool {
+    // RSA keys are the exponent length, exponent and modulus (RFC 3110)
+    let rsa = |params: &signature::RsaParameters| {
+        let (exponent_len, rest) = match public_key.split_first() {
+            Some((0, rest)) if rest.len() >= 2 => (u16::from_be_bytes([rest[0], rest[1]]) as usize, &rest[2..]),
+            Some((len, rest)) => (*len as usize, rest),
+            None => return false,
+        };
+        if rest.len() <= exponent_len {
+            return false;
+        }
+        let (e, n) = rest.split_at(exponent_len);
+        let n = &n[n.iter().take_while(|b| **b == 0).count()..];
+        signature::RsaPublicKeyComponents { n, e }.verify(params, message, sig).is_ok()
+    };
+    // ECDSA keys are the bare point; ring wants it in uncompressed SEC1 form
+    let ecdsa = |algorithm: &'static signature::EcdsaVerificationAlgorithm| {
+        let mut point = vec![4];
+        point.extend_from_slice(public_key);
+        signature::UnparsedPublicKey::new(algorithm, point).verify(message, sig).is_ok()
+    };
+    match algorithm {
+        5 | 7 => rsa(&signature::RSA_PKCS1_1024_8192_SHA1_FOR_LEGACY_USE_ONLY),
+        8 => rsa(&signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY),
+        10 => rsa(&signature::RSA_PKCS1_1024_8192_SHA512_FOR_LEGACY_USE_ONLY),
+        13 => ecdsa(&signature::ECDSA_P256_SHA256_FIXED),
+        14 => ecdsa(&signature::ECDSA_P384_SHA384_FIXED),
+        15 => signature::UnparsedPublicKey::new(&signature::ED25519, public_key).verify(message, sig).is_ok(),
+        _ => false,
+    }
+}
+
+/// Group a section into RRsets and attach their signatures
+fn rrsets(section: &[ResourceRecord]) -> Vec<RrSet<'_>> {
+    let mut sets: Vec<RrSet> = Vec::new();
+    for rr in section.iter().filter(|rr| rr.rtype != RecordType::Rrsig) {
+        let name = normalize(&rr.name);
+        match sets.iter_mut().find(|s| s.name == name && s.rtype == rr.rtype) {
+            Some(set) => set.records.push(rr),
+            None => sets.push(RrSet { name, rtype: rr.rtype, records: vec![rr], sigs: Vec::new() }),
+        }
+    }
+    for rr in section.iter().filter(|rr| rr.rtype == RecordType::Rrsig) {
+        let Some(sig) = Rrsig::parse(rr) else { continue };
+        let name = normalize(&rr.name);
+        if let Some(set) = sets.iter_mut().find(|s| s.name == name && s.rtype.to_u16() == sig.type_covered) {
+            set.sigs.push(sig);
+        }
+    }
+    sets
+}
+
+/// The data an RRSIG signs: its own header, then the RRset in canonical form and order (RFC 4034 3.1.8.1)
+fn signed_data(set: &RrSet, sig: &Rrsig) -> Vec<u8> {
+    // A wildcard expansion is signed under the wildcard's name (RFC 4035 5.3.2)
+    let owner = if (sig.labels as usize) < label_count(&set.name) {
+        let labels: Vec<&str> = set.name.split('.').collect();
+        format!("*.{}", labels[labels.len() - sig.labels as usize..].join("."))
+    } else {
+        set.name.clone()
+    };
+    let owner = canonical_name(&owner);
+    let mut rdatas: Vec<Vec<u8>> = set.records.iter().map(|rr| rr.rdata.to_canonical_bytes()).collect();
+    rdatas.sort();
+    rdatas.dedup();
+
+    let mut data = sig.signed_header.clone();
+    for rdata in rdatas {
+        data.extend_from_slice(&owner);
+        data.extend_from_slice(&set.rtype.to_u16().to_be_bytes());
+        data.extend_from_slice(&set.records[0].class.to_be_bytes());
+        data.extend_from_slice(&sig.original_ttl.to_be_bytes());
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend_from_slice(&rdata);
+    }
+    data
+}
+
+fn check_signature(set: &RrSet, sig: &Rrsig, zone: &str, keys: &[Dnskey], now: u32) -> Result<(), String> {
+    if normalize(&sig.signer) != zone {
+        return Err(format!("signed by {} instead of {}", zone_label(&sig.signer), zone_label(zone)));
+    }
+    if serial_before(now, sig.inception) {
+        return Err("signature not yet valid".into());
+    }
+    if serial_before(sig.expiration, now) {
+        return Err("signature expired".into());
+    }
+    if sig.labels as usize > label_count(&set.name) {
+        return Err("signature has more labels than its owner".into());
+    }
+    let data = signed_data(set, sig);
+    let verified = keys.iter()
+        .filter(|k| k.key_tag == sig.key_tag && k.algorithm == sig.algorithm)
+        .any(|k| verify_signature(k.algorithm, &k.public_key, &data, &sig.signature));
+    if verified { Ok(()) } else { Err(format!("signature by key {} doesn't verify", sig.key_tag)) }
+}
+
+/// One of the set's signatures must be valid and made by one of `keys` of `zone`
+fn verify_rrset(set: &RrSet, zone: &str, keys: &[Dnskey], now: u32) -> Result<(), String> {
+    let mut error = "no signature".to_string();
+    for sig in &set.sigs {
+        match check_signature(set, sig, zone, keys, now) {
+            Ok(()) => return Ok(()),
+            Err(e) => error = e,
+        }
+    }
+    Err(format!("{} {}: {}", zone_label(&set.name), set.rtype, error))
+}
+
+/// Make a query ask for signatures (DO) and for data upstream validators consider bogus (CD), which we check ourselves
+fn dnssec_request(request: &Message) -> Message {
+    let mut request = request.clone();
+    request.checking_disabled = true;
+    let options = request.edns.take().map(|e| e.options).unwrap_or_default();
+    request.edns = Some(Edns { udp_size: EDNS_UDP_SIZE, version: 0, dnssec_ok: true, options });
+    request
+}
+
+/// Look up records the validator needs, such as DS and DNSKEY sets
+async fn lookup(name: &str, rtype: RecordType) -> Result<Message, String> {
+    let request = dnssec_request(&Message::query(query_id(), name, rtype));
+    let forwarded = upstream::forward(&request, &request.to_bytes()).await
+        .ok_or_else(|| format!("no answer for {} {}", zone_label(name), rtype))?;
+    let response = Message::parse(&forwarded.response)?;
+    match response.rcode {
+        RCODE_NOERROR | RCODE_NXDOMAIN => Ok(response),
+        rcode => Err(format!("{} {} failed with rcode {}", zone_label(name), rtype, rcode)),
+    }
+}
+
+/// A zone's keys, accepted when one matching a DS record signs the DNSKEY set. None when no DS record
+/// uses an algorithm and digest we support, which leaves the zone insecure (RFC 4035 5.2).
+async fn zone_keys(zone: &str, ds_set: &[Ds], now: u32) -> Result<Option<(Vec<Dnskey>, u32)>, String> {
+    let ds_set: Vec<&Ds> = ds_set.iter().filter(|ds| ds.usable()).collect();
+    if ds_set.is_empty() {
+        return Ok(None);
+    }
+    let response = lookup(zone, RecordType::Dnskey).await?;
+    let sets = rrsets(&response.answers);
+    let set = sets.iter().find(|s| s.name == zone && s.rtype == RecordType::Dnskey)
+        .ok_or_else(|| format!("{} has no DNSKEY records", zone_label(zone)))?;
+    let keys: Vec<Dnskey> = set.records.iter()
+        .filter_map(|rr| Dnskey::parse(rr))
+        .filter(|k| k.flags & FLAG_ZONE_KEY != 0 && k.flags & FLAG_REVOKED == 0)
+        .collect();
+    let entry_keys: Vec<Dnskey> = keys.iter().filter(|k| ds_set.iter().any(|ds| ds.matches(zone, k))).cloned().collect();
+    if entry_keys.is_empty() {
+        return Err(format!("no DNSKEY of {} matches its DS records", zone_label(zone)));
+    }
+    verify_rrset(set, zone, &entry_keys, now)?;
+    let ttl = set.records.iter().map(|rr| rr.ttl).min().unwrap_or(0);
+    Ok(Some((keys, ttl)))
+}
+
+async fn root_trust(now: u32) -> Result<(Trust, u32), String> {
+    let anchors: Vec<Ds> = ROOT_ANCHORS.iter()
+        .map(|(key_tag, algorithm, digest_type, digest)| Ds { key_tag: *key_tag, algorithm: *algorithm, digest_type: *digest_type, digest: hex_decode(digest) })
+        .collect();
+    match zone_keys("", &anchors, now).await? {
+        Some((keys, ttl)) => Ok((Trust::Secure { zone: String::new(), keys }, ttl)),
+        None => Err("no usable root trust anchor".into()),
+    }
+}
+
+/// Signed NSEC/NSEC3 records showing `name` as a delegation without DS: the child zone is unsigned
+fn proves_unsigned_delegation(authorities: &[ResourceRecord], name: &str) -> bool {
+    let unsigned = |types: &[u16]| {
+        types.contains(&RecordType::Ns.to_u16()) && !types.contains(&RecordType::Ds.to_u16()) && !types.contains(&RecordType::Soa.to_u16())
+    };
+    authorities.iter().any(|rr| match rr.rtype {
+        RecordType::Nsec => Nsec::parse(rr).is_some_and(|n| n.owner == name && unsigned(&n.types)),
+        // Names in an opt-out span may be unsigned delegations (RFC 5155 6)
+        RecordType::Nsec3 => Nsec3::parse(rr).is_some_and(|n| (n.matches(name) && unsigned(&n.types)) || (n.opt_out && n.covers(name))),
+        _ => false,
+    })
+}
+
+/// Trust for `name` given that of the zone above it: a signed delegation gives the child's keys,
+/// a proven unsigned one makes it insecure, and anything else keeps `name` in the parent's zone
+async fn delegation_trust(name: &str, zone: &str, keys: &[Dnskey], now: u32) -> Result<(Trust, u32), String> {
+    let response = lookup(name, RecordType::Ds).await?;
+    let answers = rrsets(&response.answers);
+    if let Some(set) = answers.iter().find(|s| s.name == name && s.rtype == RecordType::Ds) {
+        verify_rrset(set, zone, keys, now)?;
+        let ds_set: Vec<Ds> = set.records.iter().filter_map(|rr| Ds::parse(rr)).collect();
+        let ds_ttl = set.records.iter().map(|rr| rr.ttl).min().unwrap_or(0);
+        return Ok(match zone_keys(name, &ds_set, now).await? {
+            Some((child_keys, ttl)) => (Trust::Secure { zone: name.to_string(), keys: child_keys }, ttl.min(ds_ttl)),
+            None => (Trust::Insecure, ds_ttl),
+        });
+    }
+
+    // No DS. Only the parent's signed denial may make the name insecure; without one it stays in the
+    // parent's zone, where unsigned data is bogus, so stripping records can't downgrade an answer.
+    let authorities = rrsets(&response.authorities);
+    for set in answers.iter().chain(&authorities).filter(|s| s.rtype != RecordType::Ns) {
+        verify_rrset(set, zone, keys, now)?;
+    }
+    let ttl = response.authorities.iter().map(|rr| rr.ttl).min().unwrap_or(MAX_TRUST_TTL);
+    if proves_unsigned_delegation(&response.authorities, name) {
+        return Ok((Trust::Insecure, ttl));
+    }
+    Ok((Trust::Secure { zone: zone.to_string(), keys: keys.to_vec() }, ttl))
+}
+
+fn cached_trust(name: &str) -> Option<Result<Trust, String>> {
+    let cache = TRUST_CACHE.lock().unwrap();
+    cache.get(name).filter(|(expires, _)| *expires > Instant::now()).map(|(_, trust)| trust.clone())
+}
+
+fn cache_trust(name: &str, trust: Result<Trust, String>, ttl: u32) {
+    let mut cache = TRUST_CACHE.lock().unwrap();
+    if cache.len() >= MAX_TRUST_ENTRIES {
+        let now = Instant::now();
+        cache.retain(|_, (expires, _)| *expires > now);
+        if cache.len() >= MAX_TRUST_ENTRIES {
+            cache.clear();
+        }
+    }
+    let expires = Instant::now() + Duration::from_secs(ttl.clamp(1, MAX_TRUST_TTL) as u64);
+    cache.insert(name.to_string(), (expires, trust));
+}
+
+/// Trust in the zone enclosing `name`, found by walking down from the root one label at a time
+async fn trust_for(name: &str, now: u32) -> Result<Trust, String> {
+    let name = normalize(name);
+    let labels: Vec<&str> = name.split('.').filter(|l| !l.is_empty()).collect();
+    let mut trust: Option<Trust> = None;
+    for depth in 0..=labels.len() {
+        let current = labels[labels.len() - depth..].join(".");
+        let parent = match &trust {
+            Some(Trust::Insecure) => return Ok(Trust::Insecure), // Everything below an unsigned delegation is unsigned
+            Some(Trust::Secure { zone, keys }) => Some((zone.as_str(), keys.as_slice())),
+            None => None,
+        };
+        let next = match cached_trust(&current) {
+            Some(cached) => cached?,
+            None => {
+                let found = match parent {
+                    None => root_trust(now).await,
+                    Some((zone, keys)) => delegation_trust(&current, zone, keys, now).await,
+                };
+                let (result, ttl) = match found {
+                    Ok((trust, ttl)) => (Ok(trust), ttl),
+                    Err(e) => (Err(e), BOGUS_TTL),
+                };
+                cache_trust(&current, result.clone(), ttl);
+                result?
+            }
+        };
+        trust = Some(next);
+    }
+    trust.ok_or_else(|| "no trust anchor".to_string())
+}
+
+/// NSEC or NSEC3 records denying `name` (NXDOMAIN) or its `qtype` (NODATA). The records are already
+/// known to be signed by the zone; wildcards that could have matched aren't checked.
+fn proves_denial(authorities: &[ResourceRecord], name: &str, qtype: RecordType, nxdomain: bool) -> bool {
+    let nsecs: Vec<Nsec> = authorities.iter().filter(|rr| rr.rtype == RecordType::Nsec).filter_map(Nsec::parse).collect();
+    let nsec3s: Vec<Nsec3> = authorities.iter().filter(|rr| rr.rtype == RecordType::Nsec3).filter_map(Nsec3::parse).collect();
+    let lacks_type = |types: &[u16]| !types.contains(&qtype.to_u16()) && !types.contains(&RecordType::Cname.to_u16());
+    let key = canonical_key(name);
+    let nsec_covers = |n: &Nsec| covers(&canonical_key(&n.owner), &canonical_key(&n.next), &key);
+
+    if !nxdomain {
+        return nsecs.iter().any(|n| n.owner == name && lacks_type(&n.types))
+            // An empty non-terminal: the next name is below it
+            || nsecs.iter().any(|n| nsec_covers(n) && n.next != name && is_subdomain(&n.next, name))
+            || nsec3s.iter().any(|n| n.matches(name) && lacks_type(&n.types))
+            || (qtype == RecordType::Ds && nsec3s.iter().any(|n| n.opt_out && n.covers(name)));
+    }
+    if nsecs.iter().any(nsec_covers) {
+        return true;
+    }
+    // NSEC3: the closest existing ancestor has a record, and the next name below it is covered (RFC 5155 8.4)
+    let mut child = name;
+    while let Some(parent) = parent_name(child) {
+        if nsec3s.iter().any(|n| n.matches(parent)) {
+            return nsec3s.iter().any(|n| n.covers(child));
+        }
+        child = parent;
+    }
+    false
+}
+
+/// Check every answer RRset against the zone that signed it, or the denial when there is no answer
+async fn check_response(response: &Message, qname: &str, qtype: RecordType, now: u32) -> Result<Validation, String> {
+    let mut secure = true;
+    for set in &rrsets(&response.answers) {
+        // CNAMEs synthesized from a signed DNAME carry no signature of their own (RFC 6672 5.3.1)
+        let synthesized = set.rtype == RecordType::Cname && set.sigs.is_empty()
+            && response.answers.iter().any(|rr| rr.rtype == TYPE_DNAME && is_subdomain(&set.name, &rr.name) && normalize(&rr.name) != set.name);
+        if synthesized {
+            continue;
+        }
+        let trust = match set.sigs.first() {
+            // The signer has to be a zone above the data, or any zone could vouch for anything
+            Some(sig) if is_subdomain(&set.name, &sig.signer) => trust_for(&sig.signer, now).await?,
+            Some(sig) => return Err(format!("{} {} is signed by {}", set.name, set.rtype, zone_label(&sig.signer))),
+            None => trust_for(&set.name, now).await?,
+        };
+        match trust {
+            Trust::Insecure => secure = false,
+            Trust::Secure { zone, keys } => verify_rrset(set, &zone, &keys, now)?,
+        }
+    }
+
+    // Follow CNAMEs to the name the answer ends at
+    let mut name = normalize(qname);
+    for _ in 0..super::MAX_CNAME_CHAIN {
+        let target = response.answers.iter().find_map(|rr| match &rr.rdata {
+            RData::Cname(target) if qtype != RecordType::Cname && normalize(&rr.name) == name => Some(normalize(target)),
+            _ => None,
+        });
+        match target {
+            Some(target) => name = target,
+            None => break,
+        }
+    }
+    let answered = response.answers.iter().any(|rr| normalize(&rr.name) == name && (rr.rtype == qtype || qtype == RecordType::Any));
+    if !answered {
+        // NXDOMAIN or NODATA: the zone must sign a denial of the name
+        match trust_for(&name, now).await? {
+            Trust::Insecure => secure = false,
+            Trust::Secure { zone, keys } => {
+                for set in rrsets(&response.authorities).iter().filter(|s| s.rtype != RecordType::Ns) {
+                    verify_rrset(set, &zone, &keys, now)?;
+                }
+                if !proves_denial(&response.authorities, &name, qtype, response.rcode == RCODE_NXDOMAIN) {
+                    return Err(format!("no proof that {} {} doesn't exist", zone_label(&name), qtype));
+                }
+            }
+        }
+    }
+    Ok(if secure { Validation::Secure } else { Validation::Insecure })
+}
+
+/// Response for the client: AD on secure answers for clients that understand it (RFC 6840 5.7),
+/// and no DNSSEC records for clients that didn't ask for them (RFC 4035 3.2.1)
+fn for_client(request: &Message, mut response: Message, secure: bool) -> Vec<u8> {
+    let dnssec_ok = request.edns.as_ref().is_some_and(|e| e.dnssec_ok);
+    response.id = request.id;
+    response.authentic_data = secure && (dnssec_ok || request.authentic_data);
+    response.checking_disabled = request.checking_disabled;
+    if !dnssec_ok {
+        let asked = request.questions.first().map(|q| q.qtype);
+        for section in [&mut response.answers, &mut response.authorities, &mut response.additionals] {
+            section.retain(|rr| !matches!(rr.rtype, RecordType::Rrsig | RecordType::Nsec | RecordType::Nsec3) || Some(rr.rtype) == asked);
+        }
+    }
+    response.edns = Message::response_to(request).edns;
+    response.to_bytes()
+}
+
+/// SERVFAIL, with the reason as an extended DNS error for clients using EDNS
+fn bogus_response(request: &Message, reason: &str) -> Vec<u8> {
+    let mut response = Message::response_to(request);
+    response.rcode = RCODE_SERVFAIL;
+    if let Some(edns) = response.edns.as_mut() {
+        let mut info = EDE_DNSSEC_BOGUS.to_be_bytes().to_vec();
+        info.extend(reason.chars().take(MAX_REASON_LEN).collect::<String>().into_bytes());
+        edns.options.push((EDNS_OPTION_EDE, info));
+    }
+    response.to_bytes()
+}
+
+/// Forward a query upstream and validate the answer. Bogus answers become SERVFAIL.
+pub async fn forward_validated(request: &Message) -> Option<Validated> {
+    let question = request.questions.first()?;
+    let upstream_request = dnssec_request(request);
+    let forwarded = upstream::forward(&upstream_request, &upstream_request.to_bytes()).await?;
+    let (response, outcome) = match Message::parse(&forwarded.response) {
+        // Upstream errors carry no data to validate; they pass through as they are
+        Ok(response) if response.rcode != RCODE_NOERROR && response.rcode != RCODE_NXDOMAIN => (Some(response), Validation::Insecure),
+        Ok(response) => {
+            let outcome = check_response(&response, &question.name, question.qtype, now_serial()).await
+                .unwrap_or_else(Validation::Bogus);
+            (Some(response), outcome)
+        }
+        Err(e) => (None, Validation::Bogus(format!("unparseable response: {}", e))),
+    };
+    let response = match (response, &outcome) {
+        (Some(response), Validation::Secure) => for_client(request, response, true),
+        (Some(response), Validation::Insecure) => for_client(request, response, false),
+        (_, Validation::Bogus(reason)) => bogus_response(request, reason),
+        (None, _) => bogus_response(request, "unparseable response"),
+    };
+    Some(Validated { response, upstream: forwarded.upstream, outcome })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    #[test]
+    fn test_nsec3_hash_and_order() {
+        // RFC 5155 appendix A
+        assert_eq!(nsec3_hash("example", &[0xaa, 0xbb, 0xcc, 0xdd], 12), "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom");
+        assert_eq!(nsec3_hash("a.example", &[0xaa, 0xbb, 0xcc, 0xdd], 12), "35mthgpgcu1qg68fab165klnsnk3dpvl");
+
+        // RFC 4034 6.1
+        let mut names = vec!["z.example", "*.z.example", "a.example", "yljkjljk.a.example", "example", "Z.a.example", "zABC.a.EXAMPLE"];
+        names.sort_by_key(|n| canonical_key(n));
+        assert_eq!(names, ["example", "a.example", "yljkjljk.a.example", "Z.a.example", "zABC.a.EXAMPLE", "z.example", "*.z.example"]);
+        assert!(covers("b", "d", "c"));
+        assert!(covers("x", "b", "a")); // Last record wraps around
+        assert!(!covers("b", "d", "d"));
+        assert!(serial_before(u32::MAX - 5, 10));
+    }
+
+    #[test]
+    fn test_signature_chain() {
+        let rng = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let mut dnskey_rdata = vec![0x01, 0x01, 3, 15]; // Zone key with SEP flag, Ed25519
+        dnskey_rdata.extend_from_slice(pair.public_key().as_ref());
+        let dnskey_rr = ResourceRecord::new("example.com", RecordType::Dnskey, 3600, RData::Raw(dnskey_rdata.clone()));
+        let key = Dnskey::parse(&dnskey_rr).unwrap();
+
+        let ds_digest = digest::digest(&digest::SHA256, &[canonical_name("Example.COM"), dnskey_rdata].concat());
+        let ds = Ds { key_tag: key.key_tag, algorithm: 15, digest_type: 2, digest: ds_digest.as_ref().to_vec() };
+        assert!(ds.matches("example.com", &key));
+
+        // Sign "www.example.com A", then check it the way an answer is checked
+        let now = now_serial();
+        let records = vec![
+            ResourceRecord::new("WWW.example.com", RecordType::A, 300, RData::A("192.0.2.2".parse().unwrap())),
+            ResourceRecord::new("www.example.com", RecordType::A, 300, RData::A("192.0.2.1".parse().unwrap())),
+        ];
+        let mut header = vec![0, 1, 15, 3];
+        header.extend_from_slice(&300u32.to_be_bytes());
+        header.extend_from_slice(&(now + 3600).to_be_bytes());
+        header.extend_from_slice(&(now - 3600).to_be_bytes());
+        header.extend_from_slice(&key.key_tag.to_be_bytes());
+        header.extend(canonical_name("example.com"));
+        let unsigned = RrSet { name: "www.example.com".into(), rtype: RecordType::A, records: records.iter().collect(), sigs: Vec::new() };
+        let unsigned_sig = Rrsig::parse(&ResourceRecord::new("www.example.com", RecordType::Rrsig, 300, RData::Raw(header.clone()))).unwrap();
+        let sig_bytes = pair.sign(&signed_data(&unsigned, &unsigned_sig));
+        let mut rrsig = header;
+        rrsig.extend_from_slice(sig_bytes.as_ref());
+
+        let mut section = records.clone();
+        section.push(ResourceRecord::new("www.example.com", RecordType::Rrsig, 300, RData::Raw(rrsig)));
+        let sets = rrsets(&section);
+        assert_eq!(sets.len(), 1);
+        assert!(verify_rrset(&sets[0], "example.com", std::slice::from_ref(&key), now).is_ok());
+        assert!(verify_rrset(&sets[0], "com", std::slice::from_ref(&key), now).is_err());
+        assert!(verify_rrset(&sets[0], "example.com", std::slice::from_ref(&key), now + 7200).is_err()); // Expired
+
+        section[0].rdata = RData::A("192.0.2.66".parse().unwrap());
+        assert!(verify_rrset(&rrsets(&section)[0], "example.com", &[key], now).is_err());
+    }
+
+    #[test]
+    fn test_denial_proofs() {
+        let nsec = |owner: &str, next: &str, types: &[u8]| {
+            let mut rdata = canonical_name(next);
+            rdata.extend_from_slice(&[0, types.len() as u8]);
+            rdata.extend_from_slice(types);
+            ResourceRecord::new(owner, RecordType::Nsec, 300, RData::Raw(rdata))
+        };
+        // a.example.com has A and NS (bits 1 and 2); c.example.com is next
+        let records = vec![nsec("a.example.com", "c.example.com", &[0x60])];
+        assert!(proves_denial(&records, "b.example.com", RecordType::A, true));
+        assert!(!proves_denial(&records, "d.example.com", RecordType::A, true));
+        assert!(proves_denial(&records, "a.example.com", RecordType::Txt, false));
+        assert!(!proves_denial(&records, "a.example.com", RecordType::A, false));
+        // NS without DS or SOA: an unsigned delegation
+        assert!(proves_unsigned_delegation(&records, "a.example.com"));
+        assert!(!proves_unsigned_delegation(&records, "b.example.com"));
+    }
+}
@@ -0,0 +1,1023 @@
+// src-tauri/src/network/dns/mod.rs
+
+use serde::{Serialize, Deserialize};
+use crate::sysmodules::{fetch, post, logging, config, notify, paths};
+use crate::sysmodules::config::{BlockMode, ClientRejectAction};
+use crate::network::{dhcp, acl, cportal};
+use tauri::AppHandle;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::Semaphore;
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+
+pub mod access;
+pub mod blocklist;
+pub mod cache;
+pub mod dhcp_names;
+pub mod dnssec;
+pub mod policy;
+pub mod querylog;
+pub mod ratelimit;
+pub mod rebind;
+pub mod upstream;
+pub mod wire;
+pub mod zones;
+
+use dhcp_names::LocalAnswer;
+use zones::DnsZone;
+use wire::{Message, Question, RData, RecordType, ResourceRecord, MIN_UDP_SIZE, RCODE_FORMERR, RCODE_NOERROR, RCODE_NOTIMP, RCODE_NXDOMAIN, RCODE_REFUSED, RCODE_SERVFAIL};
+
+static DNS_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Open client connections beyond this are refused, so idle TCP clients can't pile up
+const MAX_TCP_CONNECTIONS: usize = 64;
+/// UDP queries being answered at once; further packets are dropped and the client retries
+const MAX_CONCURRENT_QUERIES: usize = 1024;
+/// A TCP client that sends nothing for this long is disconnected (RFC 7766 suggests seconds, not minutes)
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+
+lazy_static! {
+    static ref BLACKLIST_CACHE: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+    // Swapped whole on every change, so queries share it without copying
+    static ref RECORDS_CACHE: RwLock<Arc<Vec<DnsRecord>>> = RwLock::new(Arc::new(Vec::new()));
+    // Client IP -> groups of the user who signed in from it
+    static ref AUTHENTICATED_IPS: RwLock<HashMap<String, Vec<String>>> = RwLock::new(HashMap::new());
+    static ref UPSTREAM_INTERFACE: RwLock<String> = RwLock::new("0.0.0.0".to_string());
+    // Reloaded every few seconds so every query doesn't read the config from disk
+    static ref DNS_CONFIG: RwLock<Arc<config::DnsConfig>> = RwLock::new(Arc::new(config::DnsConfig::default()));
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DnsRecord {
+    pub name: String,
+    pub rtype: String, // "A","AAAA","CNAME","MX","TXT","SRV","PTR","NS","SOA"
+    pub value: String, // Presentation format, e.g. "10 mail.example.com" for MX
+    pub ttl: u32,
+}
+
+impl DnsRecord {
+    /// Wire form of this record, owned by `owner` (the name as the client asked for it)
+    pub fn to_resource_record(&self, owner: &str) -> Result<ResourceRecord, String> {
+        let rtype = RecordType::from_name(&self.rtype).ok_or_else(|| format!("Unknown record type {}", self.rtype))?;
+        let rdata = RData::from_text(rtype, &self.value)?;
+        Ok(ResourceRecord::new(owner, rtype, self.ttl, rdata))
+    }
+}
+
+/// Reject records the server couldn't serve, and normalize the type to upper case
+fn validate_record(input: &mut DnsRecordInput) -> Result<(), String> {
+    input.rtype = input.rtype.trim().to_uppercase();
+    let rtype = RecordType::from_name(&input.rtype).ok_or_else(|| format!("Unknown record type {}", input.rtype))?;
+    RData::from_text(rtype, &input.value).map(|_| ())
+}
+
+#[derive(Deserialize)]
+pub struct DnsRecordInput {
+    pub name: String,
+    pub rtype: String,
+    pub value: String,
+    pub ttl: u32,
+}
+
+pub fn authorize_ip(ip: String, groups: Vec<String>) {
+    let mut cache = AUTHENTICATED_IPS.write().unwrap();
+    cache.insert(ip.clone(), groups);
+    logging::log_info(&format!("Authorized IP for Internet: {}", ip));
+}
+
+/// Groups of the user signed in from `ip`, empty if nobody is
+pub fn client_groups(ip: &str) -> Vec<String> {
+    AUTHENTICATED_IPS.read().unwrap().get(ip).cloned().unwrap_or_default()
+}
+
+fn get_dns_file() -> String {
+    paths::get_config_path("dns.json").to_string_lossy().to_string()
+}
+
+/// Initialize caches from disk
+pub fn init_dns_caches() {
+    // Load Blacklist
+    if let Ok(data) = fetch::read_file(&get_blacklist_file()) {
+        let list: Vec<String> = serde_json::from_str(&data).unwrap_or_default();
+        let mut cache = BLACKLIST_CACHE.write().unwrap();
+        *cache = list.iter().filter_map(|d| blocklist::normalize_domain(d)).collect();
+    }
+    blocklist::load_blocklists();
+    policy::load_policies();
+
+    // Load Records
+    if let Ok(data) = fetch::read_file(&get_dns_file()) {
+        let list: Vec<DnsRecord> = serde_json::from_str(&data).unwrap_or_default();
+        let mut cache = RECORDS_CACHE.write().unwrap();
+        *cache = Arc::new(list);
+        dhcp_names::invalidate();
+    }
+    zones::load_zones();
+    logging::log_info("DNS caches initialized");
+}
+
+pub fn set_upstream_interface(ip: String) {
+    let mut iface = UPSTREAM_INTERFACE.write().unwrap();
+    *iface = ip;
+}
+
+/// List all DNS records
+pub fn list_records() -> Vec<DnsRecord> {
+    RECORDS_CACHE.read().unwrap().to_vec()
+}
+
+/// Replace all records, updating the cache and dns.json
+pub(crate) fn replace_records(records: Vec<DnsRecord>) -> Result<(), String> {
+    let serialized = serde_json::to_string_pretty(&records).map_err(|e| e.to_string())?;
+    post::write_file(&get_dns_file(), &serialized)?;
+    *RECORDS_CACHE.write().unwrap() = Arc::new(records);
+    dhcp_names::invalidate();
+    Ok(())
+}
+
+/// Add a DNS record (prevents duplicates)
+pub fn add_record(mut input: DnsRecordInput) -> Result<(), String> {
+    validate_record(&mut input)?;
+    let mut records = list_records(); // Get current copy
+    let name_for_log = input.name.clone();
+    
+    // Check for duplicate: same name and type
+    let exists = records.iter().any(|r| 
+        r.name.to_lowercase() == input.name.to_lowercase() && 
+        r.rtype.to_uppercase() == input.rtype.to_uppercase()
+    );
+    
+    if exists {
+        // Update existing record instead of adding duplicate
+        for rec in records.iter_mut() {
+            if rec.name.to_lowercase() == input.name.to_lowercase() && 
+               rec.rtype.to_uppercase() == input.rtype.to_uppercase() {
+                rec.value = input.value.clone();
+                rec.ttl = input.ttl;
+                break;
+            }
+        }
+        logging::log_info(&format!("Updated existing DNS record: {}", name_for_log));
+    } else {
+        records.push(DnsRecord {
+            name: input.name,
+            rtype: input.rtype,
+            value: input.value,
+            ttl: input.ttl,
+        });
+    }
+
+    // Update Cache
+    {
+        let mut cache = RECORDS_CACHE.write().unwrap();
+        *cache = Arc::new(records.clone());
+    }
+    dhcp_names::invalidate();
+
+    let serialized = serde_json::to_string_pretty(&records).map_err(|e| e.to_string())?;
+    post::write_file(&get_dns_file(), &serialized)?;
+    zones::bump_serial_for(&name_for_log);
+    logging::log_event("system".into(), "add_record".into(), name_for_log);
+    Ok(())
+}
+
+// --- DNS Server Implementation ---
+
+pub fn stop_dns_server() {
+    if DNS_RUNNING.load(Ordering::Relaxed) {
+        logging::log_info("Stopping DNS Server...");
+        DNS_RUNNING.store(false, Ordering::Relaxed);
+    }
+}
+
+pub fn is_server_running() -> bool {
+    DNS_RUNNING.load(Ordering::Relaxed)
+}
+
+pub fn get_query_count() -> usize {
+    querylog::recent_count()
+}
+
+pub fn start_dns_server(app_handle: Option<AppHandle>) {
+    if DNS_RUNNING.load(Ordering::Relaxed) {
+        logging::log_info("DNS Server is already running.");
+        return;
+    }
+
+    // Initialize caches before starting
+    init_dns_caches();
+    
+    // Ensure Gateway FQDN for Captive Portal
+    match fetch::fetch_setup() {
+        Ok(cfg) => {
+             let fqdn = if cfg.advanced.captive_portal_domain.is_empty() { 
+                 "crabflow.login".to_string() 
+             } else { 
+                 cfg.advanced.captive_portal_domain 
+             };
+             
+             // Inject Record for Gateway IP
+             // Assuming Gateway IP matches the DHCP server IP
+             let gateway_ip = cfg.dhcp.gateway;
+             logging::log_info(&format!("Injecting DNS A record: {} -> {}", fqdn, gateway_ip));
+             
+             let _ = add_record(DnsRecordInput {
+                 name: fqdn,
+                 rtype: "A".to_string(),
+                 value: gateway_ip,
+                 ttl: 300,
+             });
+        },
+        Err(e) => {
+            logging::log_warn(&format!("Could not load setup for DNS FQDN injection: {}", e));
+        }
+    }
+
+    DNS_RUNNING.store(true, Ordering::Relaxed);
+    reload_config();
+    blocklist::spawn_refresher();
+    let app = app_handle.clone();
+
+    // The server gets its own runtime; dropping it when the server stops ends every task it spawned
+    thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_multi_thread().thread_name("dns-worker").enable_all().build() {
+            Ok(rt) => rt,
+            Err(e) => {
+                logging::log_error(&format!("Failed to start DNS runtime: {}", e));
+                DNS_RUNNING.store(false, Ordering::Relaxed);
+                return;
+            }
+        };
+        runtime.block_on(run_server(app));
+        logging::log_info("DNS Server stopped.");
+    });
+}
+
+fn reload_config() {
+    if let Ok(cfg) = config::load_setup_config() {
+        cache::configure(&cfg.dns);
+        upstream::configure(&cfg.dns);
+        cportal::sync_http_redirect(&cfg);
+        *DNS_CONFIG.write().unwrap() = Arc::new(cfg.dns);
+    }
+}
+
+fn current_config() -> Arc<config::DnsConfig> {
+    DNS_CONFIG.read().unwrap().clone()
+}
+
+async fn run_server(app: Option<AppHandle>) {
+    let dns_config = current_config();
+    for network in access::invalid_networks(&dns_config) {
+        logging::log_warn(&format!("Ignoring invalid allowed DNS client network: {}", network));
+    }
+    // One socket per listen address, so queries arriving on other interfaces (e.g. the WAN) never reach us
+    let mut sockets = Vec::new();
+    let mut errors = Vec::new();
+    for ip in access::listen_addresses(&dns_config) {
+        let addr = SocketAddr::new(ip, 53);
+        match UdpSocket::bind(addr).await {
+            Ok(s) => sockets.push((addr, Arc::new(s))),
+            Err(e) => {
+                logging::log_error(&format!("Failed to bind DNS server to {}: {}", addr, e));
+                errors.push(format!("{}: {}", addr, e));
+            }
+        }
+    }
+    if sockets.is_empty() {
+        let err_msg = if errors.is_empty() {
+            "Failed to bind DNS server: none of the listen interfaces has an address".to_string()
+        } else {
+            format!("Failed to bind DNS server to port 53: {}", errors.join("; "))
+        };
+        logging::log_error(&err_msg);
+        if let Some(h) = &app {
+            notify::send_notification(h, "DNS Server Error", &err_msg, "error");
+        }
+        DNS_RUNNING.store(false, Ordering::Relaxed);
+        return;
+    }
+    let listening = sockets.iter().map(|(addr, _)| addr.to_string()).collect::<Vec<_>>().join(", ");
+    logging::log_info(&format!("DNS Server started on {}", listening));
+    if let Some(h) = &app {
+        notify::send_notification(h, "DNS Server Started", &format!("Listening on {}", listening), "success");
+    }
+
+    tokio::spawn(async {
+        while DNS_RUNNING.load(Ordering::Relaxed) {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            let _ = tokio::task::spawn_blocking(reload_config).await;
+        }
+    });
+    upstream::spawn_health_checks();
+    querylog::spawn_writer();
+    ratelimit::spawn_pruner();
+
+    // Each query runs as its own task, so a slow upstream only delays the clients waiting on it
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_QUERIES));
+    for (addr, socket) in sockets {
+        tokio::spawn(run_tcp_listener(addr, app.clone()));
+        tokio::spawn(run_udp_listener(socket, permits.clone(), app.clone()));
+    }
+    while DNS_RUNNING.load(Ordering::Relaxed) {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn run_udp_listener(socket: Arc<UdpSocket>, permits: Arc<Semaphore>, app: Option<AppHandle>) {
+    // Queries are small, but EDNS lets clients send up to 64K
+    let mut buf = vec![0u8; 65535];
+    while DNS_RUNNING.load(Ordering::Relaxed) {
+        // Wake up every second to notice when the server is stopped
+        let (amt, src) = match tokio::time::timeout(Duration::from_secs(1), socket.recv_from(&mut buf)).await {
+            Err(_) => continue,
+            Ok(Ok(received)) => received,
+            Ok(Err(e)) => {
+                if e.kind() != std::io::ErrorKind::ConnectionReset {
+                    logging::log_error(&format!("Error receiving DNS packet: {}", e));
+                }
+                continue;
+            }
+        };
+        let permit = match permits.clone().try_acquire_owned() {
+            Ok(p) => p,
+            Err(_) => {
+                logging::log_debug(&format!("Dropping DNS query from {}: too many queries in progress", src));
+                continue;
+            }
+        };
+        let query = buf[..amt].to_vec();
+        let socket = socket.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            if let Some(response) = serve_query(&query, &src.ip().to_string(), &app, true).await {
+                let _ = socket.send_to(&fit_udp(&query, response), src).await;
+            }
+            drop(permit);
+        });
+    }
+}
+
+/// ACL check, rate limits, answer, notify and log one query. Shared by the UDP and TCP listeners.
+async fn serve_query(query: &[u8], src_ip: &str, app: &Option<AppHandle>, udp: bool) -> Option<Vec<u8>> {
+    let dns_config = current_config();
+    // Allowed client networks
+    if !access::client_allowed(src_ip, &dns_config) {
+        logging::log_debug(&format!("Rejected DNS query from {}: not in an allowed client network", src_ip));
+        return match dns_config.client_reject_action {
+            ClientRejectAction::Drop => None,
+            ClientRejectAction::Refuse => {
+                let request = Message::parse(query).ok()?;
+                let mut refused = Message::response_to(&request);
+                refused.rcode = RCODE_REFUSED;
+                Some(refused.to_bytes())
+            }
+        };
+    }
+    // ACL Check
+    if !dns_config.allow_non_dhcp_clients {
+        // We check leases only if restricted.
+        // is_ip_leased is served from the in-memory lease table and ignores expired leases.
+        if !dhcp::is_ip_leased(src_ip) {
+            logging::log_debug(&format!("Blocked DNS query from non-DHCP client: {}", src_ip));
+            return None; // Drop packet
+        }
+    }
+
+    let admission = ratelimit::admit(src_ip, &dns_config);
+    if admission != ratelimit::Admission::Allow {
+        if admission == ratelimit::Admission::Ban {
+            if let Some(h) = app {
+                notify::send_notification(h, "DNS Client Banned", &format!("{} exceeded the query rate limit and is banned for {}s", src_ip, dns_config.rate_limit_ban_secs), "warning");
+            }
+        }
+        if ratelimit::should_log(src_ip) {
+            let (domain, query_type) = match Message::parse(query).ok().and_then(|m| m.questions.into_iter().next()) {
+                Some(q) => (q.name, q.qtype.to_string()),
+                None => (String::new(), String::new()),
+            };
+            querylog::record(querylog::entry(src_ip, domain, query_type, "Throttled".into(), &[], None, Duration::ZERO));
+        }
+        return None; // Drop packet
+    }
+
+    let started = Instant::now();
+    let mut answered = handle_dns_query(query, src_ip, &dns_config).await?;
+    if answered.status == "Blocked" {
+        if let Some(h) = app {
+            notify::send_notification(h, "DNS Blocked", &format!("Access to {} blocked from {}", answered.domain, src_ip), "warning");
+        }
+    }
+    // RRL only applies to UDP: over TCP the source address can't be spoofed
+    let limit = if udp {
+        ratelimit::limit_response(src_ip, &answered.domain, &answered.query_type, &answered.response, &dns_config)
+    } else {
+        ratelimit::ResponseLimit::Send
+    };
+    let throttled = limit != ratelimit::ResponseLimit::Send;
+    if throttled {
+        answered.status = "Throttled".to_string();
+    }
+    if !throttled || ratelimit::should_log(src_ip) {
+        querylog::record(querylog::entry(src_ip, answered.domain, answered.query_type, answered.status, &answered.response, answered.upstream, started.elapsed()));
+    }
+    match limit {
+        ratelimit::ResponseLimit::Send => Some(answered.response),
+        ratelimit::ResponseLimit::Slip => ratelimit::slip(&answered.response),
+        ratelimit::ResponseLimit::Drop => None,
+    }
+}
+
+/// Shrink a response to the UDP size the client advertised, setting TC so it retries over TCP
+fn fit_udp(query: &[u8], response: Vec<u8>) -> Vec<u8> {
+    let limit = Message::parse(query).map(|q| q.max_udp_size()).unwrap_or(MIN_UDP_SIZE);
+    if response.len() <= limit {
+        return response;
+    }
+    match Message::parse(&response) {
+        Ok(msg) => msg.to_udp_bytes(limit),
+        Err(_) => {
+            // Unparseable upstream answer: the bare header with TC and no records
+            let mut header = response[..12.min(response.len())].to_vec();
+            if header.len() == 12 {
+                header[2] |= 0x02;
+                header[4..12].fill(0);
+            }
+            header
+        }
+    }
+}
+
+async fn run_tcp_listener(addr: SocketAddr, app: Option<AppHandle>) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(l) => l,
+        Err(e) => {
+            // UDP keeps working; only large answers are affected
+            logging::log_error(&format!("Failed to bind DNS TCP listener to {}: {}", addr, e));
+            return;
+        }
+    };
+    logging::log_info(&format!("DNS Server listening on TCP {}", addr));
+
+    let connections = Arc::new(Semaphore::new(MAX_TCP_CONNECTIONS));
+    while DNS_RUNNING.load(Ordering::Relaxed) {
+        match tokio::time::timeout(Duration::from_secs(1), listener.accept()).await {
+            Err(_) => continue,
+            Ok(Ok((stream, src))) => {
+                let permit = match connections.clone().try_acquire_owned() {
+                    Ok(p) => p,
+                    Err(_) => {
+                        logging::log_warn(&format!("Refusing DNS TCP connection from {}: too many open connections", src));
+                        continue; // Dropping the stream closes it
+                    }
+                };
+                let app = app.clone();
+                tokio::spawn(async move {
+                    serve_tcp_connection(stream, src, &app).await;
+                    drop(permit);
+                });
+            }
+            Ok(Err(e)) => logging::log_error(&format!("Error accepting DNS TCP connection: {}", e)),
+        }
+    }
+}
+
+/// Serve length-prefixed queries (RFC 1035 4.2.2) until the client closes, idles out or the server stops
+async fn serve_tcp_connection(mut stream: TcpStream, src: SocketAddr, app: &Option<AppHandle>) {
+    let src_ip = src.ip().to_string();
+    while DNS_RUNNING.load(Ordering::Relaxed) {
+        let mut len = [0u8; 2];
+        match tokio::time::timeout(TCP_IDLE_TIMEOUT, stream.read_exact(&mut len)).await {
+            Ok(Ok(_)) => {}
+            _ => break, // Closed, idle or reset
+        }
+        let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+        match tokio::time::timeout(TCP_IDLE_TIMEOUT, stream.read_exact(&mut query)).await {
+            Ok(Ok(_)) => {}
+            _ => break,
+        }
+        let response = match serve_query(&query, &src_ip, app, false).await {
+            Some(r) if r.len() <= u16::MAX as usize => r,
+            _ => break,
+        };
+        let mut framed = Vec::with_capacity(response.len() + 2);
+        framed.extend_from_slice(&(response.len() as u16).to_be_bytes());
+        framed.extend_from_slice(&response);
+        if stream.write_all(&framed).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// A query's response and what happened to it, for the query log
+struct Answered {
+    response: Vec<u8>,
+    domain: String,
+    query_type: String,
+    status: String,
+    upstream: Option<String>,
+}
+
+impl Answered {
+    fn new(response: Vec<u8>, domain: String, query_type: String, status: &str) -> Self {
+        Self { response, domain, query_type, status: status.to_string(), upstream: None }
+    }
+}
+
+/// Answer one query. None means no response is sent.
+async fn handle_dns_query(query: &[u8], src_ip: &str, dns_config: &config::DnsConfig) -> Option<Answered> {
+    let request = match Message::parse(query) {
+        Ok(msg) if !msg.response => msg,
+        Ok(_) => return None,
+        Err(e) => {
+            logging::log_debug(&format!("Malformed DNS query from {}: {}", src_ip, e));
+            if query.len() < 12 { return None; }
+            // Echo the ID back so the client fails fast instead of retrying
+            let mut response = Message { id: u16::from_be_bytes([query[0], query[1]]), response: true, rcode: RCODE_FORMERR, ..Default::default() };
+            response.recursion_available = true;
+            return Some(Answered::new(response.to_bytes(), String::new(), String::new(), "Malformed"));
+        }
+    };
+    let mut response = Message::response_to(&request);
+    let question = match request.questions.first() {
+        Some(q) => q.clone(),
+        None => {
+            response.rcode = RCODE_FORMERR;
+            return Some(Answered::new(response.to_bytes(), String::new(), String::new(), "Malformed"));
+        }
+    };
+    let domain_name = question.name.to_lowercase();
+    let query_type = question.qtype.to_string();
+    if request.opcode != 0 {
+        response.rcode = RCODE_NOTIMP;
+        return Some(Answered::new(response.to_bytes(), domain_name, query_type, "NotImplemented"));
+    }
+
+    // Check Auth Status (Captive Portal)
+    let is_auth = {
+        let cache = AUTHENTICATED_IPS.read().unwrap();
+        cache.contains_key(src_ip)
+    };
+
+    // Check if captive portal is enabled via ACL config
+    let captive_portal_enabled = acl::is_captive_portal_enabled();
+
+    // Check if this is a captive portal detection domain (triggers "Sign in to network" prompt)
+    let is_detection_domain = acl::is_detection_domain(&domain_name);
+
+    // Check if domain should be allowed before authentication
+    let is_allowed_before_auth = acl::is_allowed_before_auth(&domain_name);
+
+    // The Hijack Logic - Only if captive portal is enabled
+    if captive_portal_enabled && !is_auth && !is_allowed_before_auth {
+        // Retrieve valid gateway/interface IP (The one user can reach to login)
+        let gateway_ip = UPSTREAM_INTERFACE.read().unwrap().clone();
+
+        // Every name resolves to the gateway, where the HTTP server serves the portal.
+        // AAAA gets an empty answer so clients fall back to IPv4 instead of bypassing the portal.
+        if let Ok(ip) = gateway_ip.parse::<Ipv4Addr>() {
+            for q in request.questions.iter().filter(|q| matches!(q.qtype, RecordType::A | RecordType::Any)) {
+                response.answers.push(ResourceRecord::new(&q.name, RecordType::A, 60, RData::A(ip)));
+            }
+        }
+
+        let status = if is_detection_domain {
+            logging::log_debug(&format!("Captive Portal Detection: {} -> {} (from {})", domain_name, gateway_ip, src_ip));
+            "CaptiveDetect"
+        } else if domain_name.contains("crabflow") {
+            "Portal"
+        } else {
+            "Redirected"
+        };
+        return Some(Answered::new(response.to_bytes(), domain_name, query_type, status));
+    }
+
+    // Blacklist, subscribed lists and allowlist; entries cover subdomains too.
+    // The client's policy decides which lists apply and whether safe search is forced.
+    let client_policy = policy::for_client(src_ip);
+    if let Some(blocked) = request.questions.iter().find_map(|q| blocklist::blocked_by(&q.name, client_policy.as_deref())) {
+        let policy_name = client_policy.as_ref().map_or("default", |p| p.name.as_str());
+        let mode = blocked.mode.unwrap_or(dns_config.block_mode);
+        logging::log_debug(&format!("Blocked {} for {} ({}, policy {}, {:?})", domain_name, src_ip, blocked.source, policy_name, mode));
+        block_response(&mut response, &request.questions, mode);
+        return Some(Answered::new(response.to_bytes(), domain_name, query_type, "Blocked"));
+    }
+    if client_policy.as_ref().is_some_and(|p| p.safe_search) {
+        if let Some(target) = policy::safe_search_target(&domain_name) {
+            for q in &request.questions {
+                response.answers.push(ResourceRecord::new(&q.name, RecordType::Cname, policy::SAFE_SEARCH_TTL, RData::Cname(target.to_string())));
+                if q.qtype != RecordType::Cname {
+                    response.answers.extend(chase_upstream(request.id, target, q.qtype).await);
+                }
+            }
+            return Some(Answered::new(response.to_bytes(), domain_name, query_type, "SafeSearch"));
+        }
+    }
+
+    // Local records and DHCP client names; anything else goes upstream as-is
+    let records = Arc::clone(&RECORDS_CACHE.read().unwrap());
+    let zones = zones::shared();
+    let local: Option<Vec<LocalResult>> = request.questions.iter().map(|q| resolve_local(q, &records, &zones, dns_config)).collect();
+    let local = match local {
+        Some(local) => local,
+        // Upstream answers a query as a whole, so local and forwarded answers can't be mixed in one response
+        None if request.questions.len() > 1 => {
+            response.rcode = RCODE_NOTIMP;
+            return Some(Answered::new(response.to_bytes(), domain_name, query_type, "NotImplemented"));
+        }
+        None => {
+            if let Some(cached) = cache::lookup(&request) {
+                return Some(Answered::new(cached, domain_name, query_type, "Cached"));
+            }
+            // Clients setting CD validate for themselves and get the answer unchecked
+            let validate = dns_config.dnssec_validation && !request.checking_disabled;
+            let forwarded = if validate {
+                dnssec::forward_validated(&request).await.map(|validated| {
+                    let status = match &validated.outcome {
+                        dnssec::Validation::Bogus(reason) => {
+                            logging::log_warn(&format!("DNSSEC validation failed for {} {} (from {}): {}", domain_name, query_type, src_ip, reason));
+                            "Bogus"
+                        }
+                        _ => "Forwarded",
+                    };
+                    (validated.response, validated.upstream, status)
+                })
+            } else {
+                upstream::forward(&request, query).await.map(|f| (f.response, f.upstream, "Forwarded"))
+            };
+            if let Some((answer, upstream, status)) = forwarded {
+                let (answer, status) = match rebind::filter(&request, &answer, dns_config) {
+                    Some(filtered) if status == "Forwarded" => (filtered, "Rebind"),
+                    _ => (answer, status),
+                };
+                // Unchecked answers mustn't be served from the cache to clients relying on validation
+                if status == "Forwarded" && (validate || !dns_config.dnssec_validation) {
+                    cache::store(&request, &answer);
+                }
+                return Some(Answered {
+                    upstream: Some(upstream),
+                    ..Answered::new(answer, domain_name, query_type, status)
+                });
+            }
+            response.rcode = RCODE_SERVFAIL;
+            return Some(Answered::new(response.to_bytes(), domain_name, query_type, "Failed"));
+        }
+    };
+    for (q, local) in request.questions.iter().zip(local) {
+        response.answers.extend(local.answers);
+        if let Some(target) = &local.chase {
+            response.answers.extend(chase_upstream(request.id, target, q.qtype).await);
+        }
+        response.authorities.extend(local.authorities);
+        if local.rcode != RCODE_NOERROR {
+            response.rcode = local.rcode;
+        }
+    }
+    response.authoritative = true;
+    Some(Answered::new(response.to_bytes(), domain_name, query_type, "Allowed"))
+}
+
+/// Fill in the answer for a blocked query according to `mode`
+fn block_response(response: &mut Message, questions: &[Question], mode: BlockMode) {
+    let gateway = UPSTREAM_INTERFACE.read().unwrap().parse::<Ipv4Addr>().ok().filter(|ip| !ip.is_unspecified());
+    let (a, aaaa) = match mode {
+        BlockMode::Nxdomain => {
+            response.rcode = RCODE_NXDOMAIN;
+            return;
+        }
+        BlockMode::Refused => {
+            response.rcode = RCODE_REFUSED;
+            return;
+        }
+        // The block page is only reachable over IPv4, so AAAA gets no answer and clients fall back
+        BlockMode::BlockPage if gateway.is_some() => (gateway, None),
+        _ => (Some(Ipv4Addr::UNSPECIFIED), Some(Ipv6Addr::UNSPECIFIED)),
+    };
+    for q in questions {
+        let record = match q.qtype {
+            RecordType::A | RecordType::Any => a.map(|ip| ResourceRecord::new(&q.name, RecordType::A, 0, RData::A(ip))),
+            RecordType::Aaaa => aaaa.map(|ip| ResourceRecord::new(&q.name, RecordType::Aaaa, 0, RData::Aaaa(ip))),
+            _ => None,
+        };
+        response.answers.extend(record);
+    }
+}
+
+/// Longest CNAME chain we follow before giving up
+const MAX_CNAME_CHAIN: usize = 8;
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.').eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+/// A locally answered question
+#[derive(Debug, Default)]
+struct LocalResult {
+    answers: Vec<ResourceRecord>,
+    authorities: Vec<ResourceRecord>, // SOA of the zone for negative answers
+    rcode: u16,
+    chase: Option<String>, // CNAME target that left local data, to resolve upstream
+}
+
+/// Answer a question from `records`, local zones and DHCP client names, chasing CNAMEs.
+/// Returns None when the name isn't local, so the query should be forwarded.
+fn resolve_local(question: &Question, records: &[DnsRecord], zones: &[DnsZone], dns_config: &config::DnsConfig) -> Option<LocalResult> {
+    let mut result = LocalResult::default();
+    let mut name = question.name.clone();
+
+    for _ in 0..MAX_CNAME_CHAIN {
+        let zone = zones::find_zone(zones, records, &name);
+        let mut matching: Vec<ResourceRecord> = records.iter()
+            .filter(|r| same_name(&r.name, &name))
+            .filter_map(|r| r.to_resource_record(&name).ok())
+            .collect();
+        if let Some(zone) = &zone {
+            matching.extend(zone.apex_records(&name));
+            if matching.is_empty() {
+                matching = zones::synthesize_ptr(zones, records, &name);
+            }
+        }
+
+        // DHCP client names sit between explicit records and wildcards
+        let mut dhcp_nxdomain = false;
+        if matching.is_empty() && dns_config.register_dhcp_hostnames {
+            if let Some(local) = dhcp_names::answer(&name, question.qtype, &dns_config.local_domain) {
+                let ttl = dhcp_names::DHCP_RECORD_TTL;
+                match local {
+                    LocalAnswer::A(ip) => result.answers.push(ResourceRecord::new(&name, RecordType::A, ttl, RData::A(ip))),
+                    LocalAnswer::Ptr(target) => result.answers.push(ResourceRecord::new(&name, RecordType::Ptr, ttl, RData::Ptr(target))),
+                    LocalAnswer::NoData => {}
+                    LocalAnswer::NxDomain => dhcp_nxdomain = true,
+                }
+                if !dhcp_nxdomain {
+                    return Some(result);
+                }
+            }
+        }
+
+        if matching.is_empty() {
+            if let Some(zone) = &zone {
+                if !zones::name_exists(records, &name) {
+                    matching = zones::wildcard_records(zone, records, &name).iter()
+                        .filter_map(|r| r.to_resource_record(&name).ok())
+                        .collect();
+                }
+                if matching.is_empty() {
+                    // Authoritative negative answer; an empty non-terminal is NODATA, not NXDOMAIN
+                    let exists = zones::name_exists(records, &name) || same_name(&name, &zone.name);
+                    result.rcode = if exists { RCODE_NOERROR } else { RCODE_NXDOMAIN };
+                    result.authorities.push(zone.negative_soa());
+                    return Some(result);
+                }
+            }
+        }
+
+        if matching.is_empty() {
+            if dhcp_nxdomain {
+                result.rcode = RCODE_NXDOMAIN;
+                return Some(result);
+            }
+            if result.answers.is_empty() {
+                return None;
+            }
+            // The chain left our data: the target is resolved upstream and its answers appended
+            result.chase = Some(name);
+            return Some(result);
+        }
+
+        let wanted: Vec<ResourceRecord> = matching.iter()
+            .filter(|r| question.qtype == RecordType::Any || r.rtype == question.qtype)
+            .cloned()
+            .collect();
+        if !wanted.is_empty() {
+            result.answers.extend(wanted);
+            return Some(result);
+        }
+        match matching.into_iter().find(|r| r.rtype == RecordType::Cname) {
+            Some(cname) => {
+                if let RData::Cname(target) = &cname.rdata {
+                    name = target.clone();
+                }
+                result.answers.push(cname);
+            }
+            // The name exists but has no records of this type (NODATA)
+            None => {
+                result.authorities.extend(zone.map(|z| z.negative_soa()));
+                return Some(result);
+            }
+        }
+    }
+    logging::log_warn(&format!("CNAME chain for {} is longer than {} records", question.name, MAX_CNAME_CHAIN));
+    result.rcode = RCODE_SERVFAIL;
+    Some(result)
+}
+
+async fn chase_upstream(id: u16, name: &str, qtype: RecordType) -> Vec<ResourceRecord> {
+    let request = Message::query(id, name, qtype);
+    upstream::forward(&request, &request.to_bytes()).await
+        .and_then(|f| Message::parse(&f.response).ok())
+        .map(|m| m.answers)
+        .unwrap_or_default()
+}
+
+fn get_blacklist_file() -> String {
+    paths::get_config_path("blacklist.json").to_string_lossy().to_string()
+}
+
+pub fn list_blacklist() -> Vec<String> {
+    BLACKLIST_CACHE.read().unwrap().iter().cloned().collect()
+}
+
+#[tauri::command]
+pub fn get_blacklist() -> Vec<String> {
+    list_blacklist()
+}
+
+#[tauri::command]
+pub fn block_domain(domain: String) -> Result<(), String> {
+    let domain = blocklist::normalize_domain(&domain).ok_or_else(|| format!("Invalid domain {}", domain))?;
+    let mut list = list_blacklist();
+    if !list.contains(&domain) {
+        list.push(domain.clone());
+        
+        // Update Cache
+        {
+            let mut cache = BLACKLIST_CACHE.write().unwrap();
+            cache.insert(domain.clone());
+        }
+
+        let serialized = serde_json::to_string_pretty(&list).map_err(|e| e.to_string())?;
+        post::write_file(&get_blacklist_file(), &serialized)?;
+        logging::log_event("system".into(), "block_domain".into(), domain);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn unblock_domain(domain: String) -> Result<(), String> {
+    let domain = blocklist::normalize_domain(&domain).unwrap_or(domain);
+    let mut list = list_blacklist();
+    if let Some(pos) = list.iter().position(|x| *x == domain) {
+        list.remove(pos);
+
+        // Update Cache
+        {
+            let mut cache = BLACKLIST_CACHE.write().unwrap();
+            cache.remove(&domain);
+        }
+
+        let serialized = serde_json::to_string_pretty(&list).map_err(|e| e.to_string())?;
+        post::write_file(&get_blacklist_file(), &serialized)?;
+        logging::log_event("system".into(), "unblock_domain".into(), domain);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn import_blacklist(domains: Vec<String>) -> Result<usize, String> {
+    let mut list = list_blacklist();
+    let mut count = 0;
+    let mut cache = BLACKLIST_CACHE.write().unwrap();
+
+    for domain in domains.iter().filter_map(|d| blocklist::normalize_domain(d)) {
+        if !cache.contains(&domain) {
+            list.push(domain.clone());
+            cache.insert(domain);
+            count += 1;
+        }
+    }
+    if count > 0 {
+        let serialized = serde_json::to_string_pretty(&list).map_err(|e| e.to_string())?;
+        post::write_file(&get_blacklist_file(), &serialized)?;
+        logging::log_event("system".into(), "import_blacklist".into(), format!("Imported {} domains", count));
+    }
+    Ok(count)
+}
+
+/// Update a DNS record
+pub fn update_record(old_name: String, old_rtype: String, mut input: DnsRecordInput) -> Result<(), String> {
+    validate_record(&mut input)?;
+    let mut records = list_records();
+    if let Some(index) = records.iter().position(|r| r.name == old_name && r.rtype == old_rtype) {
+        let name_for_log = input.name.clone();
+        records[index] = DnsRecord {
+            name: input.name,
+            rtype: input.rtype,
+            value: input.value,
+            ttl: input.ttl,
+        };
+        
+        {
+            let mut cache = RECORDS_CACHE.write().unwrap();
+            *cache = Arc::new(records.clone());
+        }
+        dhcp_names::invalidate();
+
+        let serialized = serde_json::to_string_pretty(&records).map_err(|e| e.to_string())?;
+        post::write_file(&get_dns_file(), &serialized)?;
+        zones::bump_serial_for(&old_name);
+        zones::bump_serial_for(&name_for_log);
+        logging::log_event("system".into(), "update_record".into(), name_for_log);
+        Ok(())
+    } else {
+        Err("Record not found".to_string())
+    }
+}
+
+/// Remove a DNS record
+pub fn remove_record(name: String, rtype: String) -> Result<(), String> {
+    logging::log_info(&format!("remove_record called with name='{}' rtype='{}'", name, rtype));
+    let mut records = list_records();
+    let before = records.len();
+
+    // Use case-insensitive comparison for safety
+    records.retain(|r| !(r.name.eq_ignore_ascii_case(&name) && r.rtype.eq_ignore_ascii_case(&rtype)));
+
+    let after = records.len();
+    logging::log_info(&format!("remove_record: before={}, after={} (removed={})", before, after, before.saturating_sub(after)));
+
+    // Update Cache
+    {
+        let mut cache = RECORDS_CACHE.write().unwrap();
+        *cache = Arc::new(records.clone());
+    }
+    dhcp_names::invalidate();
+
+    let serialized = serde_json::to_string_pretty(&records).map_err(|e| e.to_string())?;
+    post::write_file(&get_dns_file(), &serialized)?;
+    zones::bump_serial_for(&name);
+    logging::log_event("system".into(), "remove_record".into(), name.clone());
+
+    if before == after {
+        return Err(format!("No matching record found for {} ({})", name, rtype));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str, rtype: &str, value: &str) -> DnsRecord {
+        DnsRecord { name: name.into(), rtype: rtype.into(), value: value.into(), ttl: 300 }
+    }
+
+    #[test]
+    fn test_resolve_local() {
+        let records = vec![
+            record("www.home.lan", "CNAME", "nas.home.lan"),
+            record("nas.home.lan", "A", "10.0.0.5"),
+            record("nas.home.lan", "AAAA", "fd00::5"),
+            record("home.lan", "MX", "10 mail.home.lan"),
+        ];
+        let cfg = config::DnsConfig { register_dhcp_hostnames: false, ..Default::default() };
+        let zones = vec![DnsZone::new("home.lan")];
+        let ask = |name: &str, qtype, zones: &[DnsZone]| resolve_local(&Question { name: name.into(), qtype, qclass: wire::CLASS_IN }, &records, zones, &cfg);
+
+        let local = ask("WWW.home.lan", RecordType::Aaaa, &[]).unwrap();
+        assert_eq!(local.rcode, RCODE_NOERROR);
+        assert_eq!(local.answers.len(), 2);
+        assert_eq!(local.answers[0].name, "WWW.home.lan");
+        assert_eq!(local.answers[1].rdata, RData::Aaaa("fd00::5".parse().unwrap()));
+
+        // The name exists without a TXT record: NODATA rather than forwarding
+        assert_eq!(ask("nas.home.lan", RecordType::Txt, &[]).unwrap().answers.len(), 0);
+        assert_eq!(ask("home.lan", RecordType::Mx, &[]).unwrap().answers[0].rdata.to_string(), "10 mail.home.lan");
+        assert!(ask("example.com", RecordType::A, &[]).is_none());
+
+        // Without a zone unknown names are forwarded; with one they are an authoritative NXDOMAIN
+        assert!(ask("missing.home.lan", RecordType::A, &[]).is_none());
+        let missing = ask("missing.home.lan", RecordType::A, &zones).unwrap();
+        assert_eq!(missing.rcode, RCODE_NXDOMAIN);
+        assert_eq!(missing.authorities[0].rtype, RecordType::Soa);
+        assert_eq!(ask("home.lan", RecordType::Soa, &zones).unwrap().answers.len(), 1);
+    }
+
+    #[test]
+    fn test_block_response_modes() {
+        let questions = vec![Question { name: "ads.example.com".into(), qtype: RecordType::Aaaa, qclass: wire::CLASS_IN }];
+        let answer = |mode| {
+            let mut response = Message::default();
+            block_response(&mut response, &questions, mode);
+            response
+        };
+
+        let null = answer(BlockMode::NullIp);
+        assert_eq!(null.rcode, RCODE_NOERROR);
+        assert_eq!(null.answers[0].rdata, RData::Aaaa(Ipv6Addr::UNSPECIFIED));
+        assert_eq!(answer(BlockMode::Nxdomain).rcode, RCODE_NXDOMAIN);
+        let refused = answer(BlockMode::Refused);
+        assert_eq!(refused.rcode, RCODE_REFUSED);
+        assert!(refused.answers.is_empty());
+        // No usable gateway address yet: the block page falls back to the null address
+        assert_eq!(answer(BlockMode::BlockPage).answers.len(), 1);
+    }
+}
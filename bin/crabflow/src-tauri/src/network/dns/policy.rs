@@ -0,0 +1,232 @@
+// src-tauri/src/network/dns/policy.rs
+// Per-client filtering policies: which blocklists apply and whether safe search is enforced,
+// chosen by the client's IP, its lease MAC or the groups of the user signed in from it
+
+use serde::{Deserialize, Serialize};
+use crate::sysmodules::{fetch, post, logging, paths};
+use crate::network::dhcp;
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+use lazy_static::lazy_static;
+use uuid::Uuid;
+
+/// TTL of the CNAME that points a search engine at its safe-search front end
+pub const SAFE_SEARCH_TTL: u32 = 300;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct PolicyClients {
+    #[serde(default)]
+    pub ips: Vec<String>, // Addresses or CIDR ranges, e.g. "10.0.0.20" or "10.0.1.0/24"
+    #[serde(default)]
+    pub macs: Vec<String>, // Matched against the client's active DHCP lease
+    #[serde(default)]
+    pub groups: Vec<String>, // user_management groups of the user signed in through the captive portal
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FilterPolicy {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub clients: PolicyClients,
+    #[serde(default)]
+    pub blocklists: Vec<String>, // Subscription ids applied to these clients
+    #[serde(default)]
+    pub categories: Vec<String>, // Every subscription in these categories applies too
+    #[serde(default = "default_true")]
+    pub manual_blacklist: bool,
+    #[serde(default)]
+    pub safe_search: bool, // Force Google, Bing, DuckDuckGo and YouTube into their restricted modes
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl FilterPolicy {
+    fn matches(&self, ip: &str, mac: Option<&str>, groups: &[String]) -> bool {
+        self.clients.ips.iter().any(|range| ip_in_range(range, ip))
+            || mac.is_some_and(|mac| self.clients.macs.iter().any(|m| m.eq_ignore_ascii_case(mac)))
+            || groups.iter().any(|g| self.clients.groups.contains(g))
+    }
+
+    /// Whether a subscription applies to clients under this policy
+    pub fn uses_list(&self, id: &str, category: &str) -> bool {
+        self.blocklists.iter().any(|l| l == id) || self.categories.iter().any(|c| c.eq_ignore_ascii_case(category))
+    }
+}
+
+lazy_static! {
+    // Checked in order; the first enabled policy matching a client applies
+    static ref POLICIES: RwLock<Vec<Arc<FilterPolicy>>> = RwLock::new(Vec::new());
+}
+
+fn get_policies_file() -> String {
+    paths::get_config_path("dns_policies.json").to_string_lossy().to_string()
+}
+
+/// "10.0.0.5" is a single address, "10.0.0.0/24" a range; IPv6 works the same way
+fn parse_range(range: &str) -> Option<(IpAddr, u32)> {
+    let (network, prefix) = match range.trim().split_once('/') {
+        Some((network, prefix)) => (network.parse::<IpAddr>().ok()?, prefix.parse::<u32>().ok()?),
+        None => {
+            let network = range.trim().parse::<IpAddr>().ok()?;
+            (network, if network.is_ipv4() { 32 } else { 128 })
+        }
+    };
+    let max = if network.is_ipv4() { 32 } else { 128 };
+    (prefix <= max).then_some((network, prefix))
+}
+
+fn ip_in_range(range: &str, ip: &str) -> bool {
+    let (Some((network, prefix)), Ok(ip)) = (parse_range(range), ip.parse::<IpAddr>()) else { return false };
+    match (network, ip) {
+        (IpAddr::V4(net), IpAddr::V4(ip)) => {
+            let mask = if prefix == 0 { 0 } else { !0u32 << (32 - prefix) };
+            u32::from(net) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(net), IpAddr::V6(ip)) => {
+            let mask = if prefix == 0 { 0 } else { !0u128 << (128 - prefix) };
+            u128::from(net) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Safe-search front end for a search engine name, or None if `name` isn't one
+pub fn safe_search_target(name: &str) -> Option<&'static str> {
+    let name = name.trim_end_matches('.').to_lowercase();
+    let host = name.strip_prefix("www.").unwrap_or(&name);
+    if host.starts_with("google.") && !host[7..].contains("google") {
+        return Some("forcesafesearch.google.com");
+    }
+    match host {
+        "bing.com" => Some("strict.bing.com"),
+        "duckduckgo.com" => Some("safe.duckduckgo.com"),
+        "youtube.com" | "m.youtube.com" | "youtubei.googleapis.com" | "youtube.googleapis.com" | "youtube-nocookie.com" => Some("restrict.youtube.com"),
+        _ => None,
+    }
+}
+
+pub fn load_policies() {
+    if let Ok(data) = fetch::read_file(&get_policies_file()) {
+        let list: Vec<FilterPolicy> = serde_json::from_str(&data).unwrap_or_default();
+        *POLICIES.write().unwrap() = list.into_iter().map(Arc::new).collect();
+    }
+}
+
+fn save_policies(policies: &[Arc<FilterPolicy>]) -> Result<(), String> {
+    let list: Vec<&FilterPolicy> = policies.iter().map(|p| p.as_ref()).collect();
+    let serialized = serde_json::to_string_pretty(&list).map_err(|e| e.to_string())?;
+    post::write_file(&get_policies_file(), &serialized)
+}
+
+/// Policy for the client at `ip`. None means the defaults: every enabled list, no safe search.
+pub fn for_client(ip: &str) -> Option<Arc<FilterPolicy>> {
+    let policies = POLICIES.read().unwrap();
+    if policies.iter().all(|p| !p.enabled) {
+        return None;
+    }
+    let mac = dhcp::get_mac_from_ip(ip);
+    let groups = super::client_groups(ip);
+    policies.iter().find(|p| p.enabled && p.matches(ip, mac.as_deref(), &groups)).cloned()
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub fn list_dns_policies() -> Vec<FilterPolicy> {
+    POLICIES.read().unwrap().iter().map(|p| p.as_ref().clone()).collect()
+}
+
+/// Add a policy (empty id) or update an existing one in place
+#[tauri::command]
+pub fn save_dns_policy(mut policy: FilterPolicy) -> Result<FilterPolicy, String> {
+    if policy.name.trim().is_empty() {
+        return Err("Name is required".to_string());
+    }
+    if let Some(bad) = policy.clients.ips.iter().find(|r| parse_range(r).is_none()) {
+        return Err(format!("Invalid address or range {}", bad));
+    }
+    let mut policies = POLICIES.write().unwrap();
+    if policy.id.is_empty() {
+        policy.id = Uuid::new_v4().to_string();
+        policies.push(Arc::new(policy.clone()));
+    } else {
+        let existing = policies.iter_mut().find(|p| p.id == policy.id)
+            .ok_or_else(|| format!("Policy {} not found", policy.id))?;
+        *existing = Arc::new(policy.clone());
+    }
+    save_policies(&policies)?;
+    logging::log_event("system".into(), "save_dns_policy".into(), policy.name.clone());
+    Ok(policy)
+}
+
+#[tauri::command]
+pub fn remove_dns_policy(id: String) -> Result<(), String> {
+    let mut policies = POLICIES.write().unwrap();
+    let pos = policies.iter().position(|p| p.id == id).ok_or_else(|| format!("Policy {} not found", id))?;
+    let removed = policies.remove(pos);
+    save_policies(&policies)?;
+    logging::log_event("system".into(), "remove_dns_policy".into(), removed.name.clone());
+    Ok(())
+}
+
+/// Set the order policies are checked in; ids not listed keep their place at the end
+#[tauri::command]
+pub fn reorder_dns_policies(ids: Vec<String>) -> Result<(), String> {
+    let mut policies = POLICIES.write().unwrap();
+    policies.sort_by_key(|p| ids.iter().position(|id| *id == p.id).unwrap_or(usize::MAX));
+    save_policies(&policies)
+}
+
+/// Name of the policy the client at `ip` gets, None for the defaults
+#[tauri::command]
+pub fn get_client_policy(ip: String) -> Option<String> {
+    for_client(&ip).map(|p| p.name.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_matching() {
+        assert!(ip_in_range("10.0.1.0/24", "10.0.1.77"));
+        assert!(!ip_in_range("10.0.1.0/24", "10.0.2.1"));
+        assert!(ip_in_range("10.0.0.5", "10.0.0.5"));
+        assert!(ip_in_range("fd00::/64", "fd00::1234"));
+        assert!(!ip_in_range("10.0.0.0/8", "fd00::1"));
+        assert!(!ip_in_range("10.0.0.0/33", "10.0.0.1"));
+
+        let policy = FilterPolicy {
+            id: "guests".into(),
+            name: "Guests".into(),
+            enabled: true,
+            clients: PolicyClients { ips: vec![], macs: vec!["AA:BB:CC:DD:EE:FF".into()], groups: vec!["guest".into()] },
+            blocklists: vec!["list-1".into()],
+            categories: vec!["adult".into()],
+            manual_blacklist: true,
+            safe_search: true,
+        };
+        assert!(policy.matches("10.0.0.9", Some("aa:bb:cc:dd:ee:ff"), &[]));
+        assert!(policy.matches("10.0.0.9", None, &["guest".to_string()]));
+        assert!(!policy.matches("10.0.0.9", None, &["staff".to_string()]));
+        assert!(policy.uses_list("list-1", "ads"));
+        assert!(policy.uses_list("list-2", "Adult"));
+        assert!(!policy.uses_list("list-2", "ads"));
+    }
+
+    #[test]
+    fn test_safe_search_targets() {
+        assert_eq!(safe_search_target("www.google.com."), Some("forcesafesearch.google.com"));
+        assert_eq!(safe_search_target("google.co.uk"), Some("forcesafesearch.google.com"));
+        assert_eq!(safe_search_target("WWW.YouTube.com"), Some("restrict.youtube.com"));
+        assert_eq!(safe_search_target("bing.com"), Some("strict.bing.com"));
+        assert_eq!(safe_search_target("forcesafesearch.google.com"), None);
+        assert_eq!(safe_search_target("mail.google.com"), None);
+    }
+}
@@ -0,0 +1,265 @@
+// src-tauri/src/network/dns/querylog.rs
+// Query log: recent queries kept in memory, written to SurrealDB in batches and pruned by age
+
+use serde::{Deserialize, Serialize};
+use crate::sysmodules::{db, logging};
+use super::wire::{Message, EDNS_OPTION_EDE};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use lazy_static::lazy_static;
+
+const TABLE: &str = "dns_query_log";
+/// Entries kept in memory for the live view, and for queries while the database is unavailable
+const RECENT_LIMIT: usize = 10000;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+const PRUNE_INTERVAL_SECS: u64 = 3600;
+/// Longest answer text stored per entry
+const MAX_ANSWER_LEN: usize = 256;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DnsQueryLog {
+    pub timestamp: u64,
+    pub client_ip: String,
+    pub domain: String,
+    pub query_type: String,
+    pub status: String, // "Allowed", "Blocked", "Forwarded", "Cached"...
+    #[serde(default)]
+    pub rcode: String, // "NOERROR", "NXDOMAIN"...
+    #[serde(default)]
+    pub answer: String, // Answer records in presentation format, comma separated
+    #[serde(default)]
+    pub upstream: Option<String>, // Resolver that answered, for forwarded queries
+    #[serde(default)]
+    pub latency_ms: f64,
+}
+
+/// `get_query_logs` filters; every field is optional
+#[derive(Deserialize, Default, Clone, Debug)]
+pub struct QueryLogFilter {
+    pub client: Option<String>,
+    pub domain: Option<String>, // Substring, case-insensitive
+    pub status: Option<String>,
+    pub since: Option<u64>, // Unix seconds, inclusive
+    pub until: Option<u64>,
+}
+
+impl QueryLogFilter {
+    fn matches(&self, entry: &DnsQueryLog) -> bool {
+        self.client.as_ref().is_none_or(|c| entry.client_ip == *c)
+            && self.domain.as_ref().is_none_or(|d| entry.domain.contains(&d.to_lowercase()))
+            && self.status.as_ref().is_none_or(|s| entry.status == *s)
+            && self.since.is_none_or(|t| entry.timestamp >= t)
+            && self.until.is_none_or(|t| entry.timestamp <= t)
+    }
+}
+
+lazy_static! {
+    static ref RECENT: Mutex<VecDeque<DnsQueryLog>> = Mutex::new(VecDeque::new());
+    // Written to the database by the next flush
+    static ref PENDING: Mutex<VecDeque<DnsQueryLog>> = Mutex::new(VecDeque::new());
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+fn rcode_name(rcode: u16) -> String {
+    match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        other => format!("RCODE{}", other),
+    }
+}
+
+/// Build a log entry from the response sent to the client
+pub fn entry(client_ip: &str, domain: String, query_type: String, status: String, response: &[u8], upstream: Option<String>, latency: Duration) -> DnsQueryLog {
+    let (rcode, answer) = match Message::parse(response) {
+        Ok(msg) => {
+            let mut answer = msg.answers.iter().map(|rr| rr.rdata.to_string()).collect::<Vec<_>>().join(", ");
+            // Without answers, an extended DNS error (RFC 8914) says why, e.g. which DNSSEC check failed
+            if answer.is_empty() {
+                if let Some((_, info)) = msg.edns.iter().flat_map(|e| &e.options).find(|(code, data)| *code == EDNS_OPTION_EDE && data.len() > 2) {
+                    answer = String::from_utf8_lossy(&info[2..]).into_owned();
+                }
+            }
+            if answer.len() > MAX_ANSWER_LEN {
+                let mut end = MAX_ANSWER_LEN;
+                while !answer.is_char_boundary(end) { end -= 1; }
+                answer.truncate(end);
+            }
+            (rcode_name(msg.rcode), answer)
+        }
+        Err(_) => (String::new(), String::new()),
+    };
+    DnsQueryLog {
+        timestamp: now_secs(),
+        client_ip: client_ip.to_string(),
+        domain,
+        query_type,
+        status,
+        rcode,
+        answer,
+        upstream,
+        latency_ms: latency.as_secs_f64() * 1000.0,
+    }
+}
+
+pub fn record(entry: DnsQueryLog) {
+    // Without a database nothing would ever drain the queue
+    if super::current_config().query_log_persist && db::DB.get().is_some() {
+        let mut pending = PENDING.lock().unwrap();
+        pending.push_back(entry.clone());
+        // The database isn't keeping up; drop the oldest rather than grow without bound
+        if pending.len() > RECENT_LIMIT {
+            pending.pop_front();
+        }
+    }
+    let mut recent = RECENT.lock().unwrap();
+    recent.push_back(entry);
+    if recent.len() > RECENT_LIMIT {
+        recent.pop_front();
+    }
+}
+
+pub fn recent_count() -> usize {
+    RECENT.lock().unwrap().len()
+}
+
+/// Write pending entries to the database. On failure they are queued again for the next flush.
+pub async fn flush() -> Result<(), String> {
+    let Some(database) = db::DB.get() else { return Ok(()) };
+    let batch = std::mem::take(&mut *PENDING.lock().unwrap());
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let result = database.query(format!("INSERT INTO {} $rows RETURN NONE", TABLE))
+        .bind(("rows", batch.clone()))
+        .await;
+    if let Err(e) = result {
+        requeue(&mut PENDING.lock().unwrap(), batch);
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+/// Put a failed batch back in front of the entries recorded since, keeping the newest `RECENT_LIMIT`
+fn requeue(pending: &mut VecDeque<DnsQueryLog>, batch: VecDeque<DnsQueryLog>) {
+    let newer = std::mem::replace(pending, batch);
+    pending.extend(newer);
+    let excess = pending.len().saturating_sub(RECENT_LIMIT);
+    pending.drain(..excess);
+}
+
+/// Delete entries older than the retention period
+async fn prune(retention_days: u32) -> Result<(), String> {
+    let Some(database) = db::DB.get() else { return Ok(()) };
+    let cutoff = now_secs().saturating_sub(retention_days as u64 * 86400);
+    database.query(format!("DELETE {} WHERE timestamp < $cutoff", TABLE))
+        .bind(("cutoff", cutoff))
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Flush the log every few seconds and prune it hourly. Runs on the DNS server's runtime until it stops.
+pub fn spawn_writer() {
+    tokio::spawn(async {
+        if let Some(database) = db::DB.get() {
+            let index = format!("DEFINE INDEX IF NOT EXISTS {0}_time ON {0} FIELDS timestamp", TABLE);
+            if let Err(e) = database.query(index).await {
+                logging::log_warn(&format!("Failed to index DNS query log: {}", e));
+            }
+        }
+        let mut last_prune = 0;
+        while super::is_server_running() {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            if let Err(e) = flush().await {
+                logging::log_warn(&format!("Failed to write DNS query log: {}", e));
+            }
+            let config = super::current_config();
+            if now_secs() >= last_prune + PRUNE_INTERVAL_SECS {
+                last_prune = now_secs();
+                if let Err(e) = prune(config.query_log_retention_days).await {
+                    logging::log_warn(&format!("Failed to prune DNS query log: {}", e));
+                }
+            }
+        }
+        let _ = flush().await;
+    });
+}
+
+/// Newest first. Read from the database when it's enabled, otherwise from the recent entries in memory.
+#[tauri::command]
+pub async fn get_query_logs(limit: usize, filter: Option<QueryLogFilter>) -> Result<Vec<DnsQueryLog>, String> {
+    let filter = filter.unwrap_or_default();
+    let database = db::DB.get().filter(|_| super::current_config().query_log_persist);
+    let Some(database) = database else {
+        let recent = RECENT.lock().unwrap();
+        return Ok(recent.iter().rev().filter(|e| filter.matches(e)).take(limit).cloned().collect());
+    };
+    flush().await?;
+
+    let mut conditions = Vec::new();
+    if filter.client.is_some() { conditions.push("client_ip = $client"); }
+    if filter.domain.is_some() { conditions.push("string::contains(domain, $domain)"); }
+    if filter.status.is_some() { conditions.push("status = $status"); }
+    if filter.since.is_some() { conditions.push("timestamp >= $since"); }
+    if filter.until.is_some() { conditions.push("timestamp <= $until"); }
+    let where_clause = if conditions.is_empty() { String::new() } else { format!(" WHERE {}", conditions.join(" AND ")) };
+    let sql = format!("SELECT * OMIT id FROM {}{} ORDER BY timestamp DESC LIMIT $limit", TABLE, where_clause);
+
+    let mut response = database.query(sql)
+        .bind(("client", filter.client))
+        .bind(("domain", filter.domain.map(|d| d.to_lowercase())))
+        .bind(("status", filter.status))
+        .bind(("since", filter.since))
+        .bind(("until", filter.until))
+        .bind(("limit", limit))
+        .await
+        .map_err(|e| e.to_string())?;
+    response.take::<Vec<DnsQueryLog>>(0).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::wire::{RData, RecordType, ResourceRecord};
+
+    #[test]
+    fn test_entry_and_filter() {
+        let mut response = Message::response_to(&Message::query(7, "example.com", RecordType::A));
+        response.answers.push(ResourceRecord::new("example.com", RecordType::A, 60, RData::A("93.184.216.34".parse().unwrap())));
+        let log = entry("10.0.0.9", "example.com".into(), "A".into(), "Forwarded".into(), &response.to_bytes(), Some("1.1.1.1:53".into()), Duration::from_micros(1500));
+        assert_eq!(log.rcode, "NOERROR");
+        assert_eq!(log.answer, "93.184.216.34");
+        assert_eq!(log.latency_ms, 1.5);
+
+        let filter = |f: QueryLogFilter| f.matches(&log);
+        assert!(filter(QueryLogFilter::default()));
+        assert!(filter(QueryLogFilter { client: Some("10.0.0.9".into()), domain: Some("EXAMPLE".into()), ..Default::default() }));
+        assert!(!filter(QueryLogFilter { status: Some("Blocked".into()), ..Default::default() }));
+        assert!(!filter(QueryLogFilter { since: Some(log.timestamp + 1), ..Default::default() }));
+        assert!(filter(QueryLogFilter { until: Some(log.timestamp), ..Default::default() }));
+    }
+
+    #[test]
+    fn test_requeue_keeps_order_and_cap() {
+        let log = |n: usize| entry("10.0.0.9", format!("q{}.example", n), "A".into(), "Forwarded".into(), &[], None, Duration::ZERO);
+        let mut pending: VecDeque<DnsQueryLog> = (3..5).map(log).collect();
+        requeue(&mut pending, (0..3).map(log).collect());
+        let domains: Vec<&str> = pending.iter().map(|e| e.domain.as_str()).collect();
+        assert_eq!(domains, ["q0.example", "q1.example", "q2.example", "q3.example", "q4.example"]);
+
+        // Over the cap the failed batch, being oldest, goes first
+        let mut pending: VecDeque<DnsQueryLog> = (2..RECENT_LIMIT + 1).map(log).collect();
+        requeue(&mut pending, (0..2).map(log).collect());
+        assert_eq!(pending.len(), RECENT_LIMIT);
+        assert_eq!(pending[0].domain, "q1.example");
+        assert_eq!(pending[1].domain, "q2.example");
+    }
+}
@@ -0,0 +1,313 @@
+// src-tauri/src/network/dns/ratelimit.rs
+// Token-bucket query limits per client and per subnet, response-rate limiting (RRL) against
+// amplification, and temporary bans for clients that keep exceeding their limit
+
+use serde::Serialize;
+use crate::sysmodules::{config, logging};
+use super::wire::Message;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use lazy_static::lazy_static;
+
+/// Buckets hold this many seconds' worth of queries, so short bursts get through
+const BURST_SECS: f64 = 2.0;
+/// Clients are grouped into subnets of this size for the subnet limit and RRL
+const IPV4_SUBNET_PREFIX: u32 = 24;
+const IPV6_SUBNET_PREFIX: u32 = 56;
+/// Window in which throttled queries count towards a ban
+const BAN_WINDOW: Duration = Duration::from_secs(60);
+/// Every Nth response over the RRL limit goes out truncated instead of being dropped, so real clients retry over TCP
+const RRL_SLIP: u32 = 2;
+/// Throttled queries are logged at most this often per client, so a flood doesn't flood the query log too
+const LOG_INTERVAL: Duration = Duration::from_secs(1);
+/// Idle state is dropped after this long; also the cap on tracked clients under a spoofed-source flood
+const IDLE_AFTER: Duration = Duration::from_secs(120);
+const MAX_TRACKED: usize = 100_000;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(30);
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(rate: u32, now: Instant) -> Self {
+        Bucket { tokens: rate as f64 * BURST_SECS, updated: now }
+    }
+
+    /// Refill for the time since the last query, then take a token if there is one
+    fn take(&mut self, rate: u32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64 * BURST_SECS);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+struct ClientState {
+    bucket: Bucket,
+    throttled: u32, // Since `window_start`
+    window_start: Instant,
+    last_logged: Option<Instant>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct ResponseKey {
+    subnet: IpAddr,
+    name: String,
+    qtype: String,
+    rcode: u8,
+}
+
+struct ResponseState {
+    bucket: Bucket,
+    limited: u32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DnsBan {
+    pub ip: String,
+    pub since: u64, // Unix seconds
+    pub until: u64,
+    pub throttled: u32, // Queries over the limit in the minute before the ban
+}
+
+/// What to do with an incoming query
+#[derive(Debug, PartialEq)]
+pub enum Admission {
+    Allow,
+    Drop, // Over a limit, or banned
+    Ban,  // Over the limit once too often; the client is banned from now on
+}
+
+/// What to do with a UDP response
+#[derive(Debug, PartialEq)]
+pub enum ResponseLimit {
+    Send,
+    Slip, // Send it truncated, so a real client retries over TCP
+    Drop,
+}
+
+lazy_static! {
+    static ref CLIENTS: Mutex<HashMap<IpAddr, ClientState>> = Mutex::new(HashMap::new());
+    static ref SUBNETS: Mutex<HashMap<IpAddr, Bucket>> = Mutex::new(HashMap::new());
+    static ref RESPONSES: Mutex<HashMap<ResponseKey, ResponseState>> = Mutex::new(HashMap::new());
+    static ref BANS: Mutex<HashMap<IpAddr, DnsBan>> = Mutex::new(HashMap::new());
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// The /24 or /56 a client is in
+fn subnet_of(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => IpAddr::V4((u32::from(v4) & !0u32 << (32 - IPV4_SUBNET_PREFIX)).into()),
+        IpAddr::V6(v6) => IpAddr::V6((u128::from(v6) & !0u128 << (128 - IPV6_SUBNET_PREFIX)).into()),
+    }
+}
+
+fn is_banned(ip: IpAddr) -> bool {
+    let mut bans = BANS.lock().unwrap();
+    match bans.get(&ip) {
+        Some(ban) if ban.until > now_secs() => true,
+        Some(_) => {
+            bans.remove(&ip);
+            false
+        }
+        None => false,
+    }
+}
+
+/// Check a query against the client's and its subnet's limits. Queries from this machine are never limited.
+pub fn admit(src_ip: &str, dns_config: &config::DnsConfig) -> Admission {
+    let Ok(ip) = src_ip.parse::<IpAddr>() else { return Admission::Allow };
+    if ip.is_loopback() {
+        return Admission::Allow;
+    }
+    if is_banned(ip) {
+        return Admission::Drop;
+    }
+    let now = Instant::now();
+
+    let subnet_ok = dns_config.rate_limit_per_subnet == 0 || {
+        let rate = dns_config.rate_limit_per_subnet;
+        let mut subnets = SUBNETS.lock().unwrap();
+        if subnets.len() >= MAX_TRACKED {
+            subnets.clear();
+        }
+        subnets.entry(subnet_of(ip)).or_insert_with(|| Bucket::full(rate, now)).take(rate, now)
+    };
+
+    let mut clients = CLIENTS.lock().unwrap();
+    if clients.len() >= MAX_TRACKED {
+        clients.clear();
+    }
+    let rate = dns_config.rate_limit_per_ip;
+    let client = clients.entry(ip).or_insert_with(|| ClientState {
+        bucket: Bucket::full(rate, now),
+        throttled: 0,
+        window_start: now,
+        last_logged: None,
+    });
+    let client_ok = rate == 0 || client.bucket.take(rate, now);
+    if subnet_ok && client_ok {
+        return Admission::Allow;
+    }
+
+    // Only the client's own excess counts towards a ban: a busy subnet shouldn't get its quiet members banned
+    let threshold = dns_config.rate_limit_ban_threshold;
+    if client_ok || threshold == 0 {
+        return Admission::Drop;
+    }
+    if now.saturating_duration_since(client.window_start) >= BAN_WINDOW {
+        client.window_start = now;
+        client.throttled = 0;
+    }
+    client.throttled += 1;
+    if client.throttled < threshold {
+        return Admission::Drop;
+    }
+    let ban = DnsBan {
+        ip: src_ip.to_string(),
+        since: now_secs(),
+        until: now_secs() + dns_config.rate_limit_ban_secs,
+        throttled: client.throttled,
+    };
+    client.throttled = 0;
+    drop(clients);
+    logging::log_warn(&format!("DNS client {} banned for {}s: {} queries over the rate limit within a minute", src_ip, dns_config.rate_limit_ban_secs, ban.throttled));
+    BANS.lock().unwrap().insert(ip, ban);
+    Admission::Ban
+}
+
+/// Response-rate limiting (RRL): identical UDP responses to one subnet are limited, so the server
+/// is useless for reflecting traffic at a spoofed victim
+pub fn limit_response(src_ip: &str, domain: &str, query_type: &str, response: &[u8], dns_config: &config::DnsConfig) -> ResponseLimit {
+    let rate = dns_config.rrl_responses_per_second;
+    let Ok(ip) = src_ip.parse::<IpAddr>() else { return ResponseLimit::Send };
+    if rate == 0 || ip.is_loopback() || response.len() < 4 {
+        return ResponseLimit::Send;
+    }
+    let key = ResponseKey { subnet: subnet_of(ip), name: domain.to_string(), qtype: query_type.to_string(), rcode: response[3] & 0x0F };
+    let now = Instant::now();
+    let mut responses = RESPONSES.lock().unwrap();
+    if responses.len() >= MAX_TRACKED {
+        responses.clear();
+    }
+    let state = responses.entry(key).or_insert_with(|| ResponseState { bucket: Bucket::full(rate, now), limited: 0 });
+    if state.bucket.take(rate, now) {
+        return ResponseLimit::Send;
+    }
+    state.limited += 1;
+    if state.limited.is_multiple_of(RRL_SLIP) { ResponseLimit::Slip } else { ResponseLimit::Drop }
+}
+
+/// Header and question only, with TC set
+pub fn slip(response: &[u8]) -> Option<Vec<u8>> {
+    let mut msg = Message::parse(response).ok()?;
+    msg.answers.clear();
+    msg.authorities.clear();
+    msg.additionals.clear();
+    msg.truncated = true;
+    Some(msg.to_bytes())
+}
+
+/// Whether a limited query from this client should go into the query log
+pub fn should_log(src_ip: &str) -> bool {
+    let Ok(ip) = src_ip.parse::<IpAddr>() else { return false };
+    let now = Instant::now();
+    let mut clients = CLIENTS.lock().unwrap();
+    let Some(client) = clients.get_mut(&ip) else { return true };
+    if client.last_logged.is_some_and(|t| now.saturating_duration_since(t) < LOG_INTERVAL) {
+        return false;
+    }
+    client.last_logged = Some(now);
+    true
+}
+
+/// Forget idle clients, subnets and responses, and expired bans
+fn prune() {
+    let now = Instant::now();
+    let idle = |updated: Instant| now.saturating_duration_since(updated) >= IDLE_AFTER;
+    CLIENTS.lock().unwrap().retain(|_, c| !idle(c.bucket.updated));
+    SUBNETS.lock().unwrap().retain(|_, b| !idle(b.updated));
+    RESPONSES.lock().unwrap().retain(|_, r| !idle(r.bucket.updated));
+    let secs = now_secs();
+    BANS.lock().unwrap().retain(|_, b| b.until > secs);
+}
+
+/// Prune the limiter's state periodically. Runs on the DNS server's runtime until it stops.
+pub fn spawn_pruner() {
+    tokio::spawn(async {
+        while super::is_server_running() {
+            tokio::time::sleep(PRUNE_INTERVAL).await;
+            prune();
+        }
+    });
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub fn list_dns_bans() -> Vec<DnsBan> {
+    let secs = now_secs();
+    let mut bans: Vec<DnsBan> = BANS.lock().unwrap().values().filter(|b| b.until > secs).cloned().collect();
+    bans.sort_by_key(|b| std::cmp::Reverse(b.since));
+    bans
+}
+
+#[tauri::command]
+pub fn unban_dns_client(ip: String) -> Result<(), String> {
+    let addr = ip.parse::<IpAddr>().map_err(|_| format!("Invalid IP address {}", ip))?;
+    BANS.lock().unwrap().remove(&addr).ok_or_else(|| format!("{} is not banned", ip))?;
+    if let Some(client) = CLIENTS.lock().unwrap().get_mut(&addr) {
+        client.throttled = 0;
+    }
+    logging::log_event("system".into(), "unban_dns_client".into(), ip);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = Bucket::full(5, start);
+        assert_eq!((0..20).filter(|_| bucket.take(5, start)).count(), 10); // Two seconds' burst
+        assert!(!bucket.take(5, start));
+        assert!(bucket.take(5, start + Duration::from_millis(200))); // One token back after 1/5 s
+        assert!(!bucket.take(5, start + Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn test_subnets() {
+        assert_eq!(subnet_of("10.0.3.77".parse().unwrap()), "10.0.3.0".parse::<IpAddr>().unwrap());
+        assert_eq!(subnet_of("2001:db8:1:2ff::1".parse().unwrap()), "2001:db8:1:200::".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_admit_and_ban() {
+        let cfg = config::DnsConfig { rate_limit_per_ip: 1, rate_limit_ban_threshold: 3, rate_limit_ban_secs: 60, ..Default::default() };
+        let ip = "198.51.100.7";
+        assert_eq!(admit(ip, &cfg), Admission::Allow);
+        assert_eq!(admit(ip, &cfg), Admission::Allow);
+        assert_eq!(admit(ip, &cfg), Admission::Drop);
+        assert_eq!(admit(ip, &cfg), Admission::Drop);
+        assert_eq!(admit(ip, &cfg), Admission::Ban);
+        assert!(list_dns_bans().iter().any(|b| b.ip == ip));
+        assert_eq!(admit(ip, &cfg), Admission::Drop);
+        unban_dns_client(ip.to_string()).unwrap();
+        assert!(!list_dns_bans().iter().any(|b| b.ip == ip));
+        assert_eq!(admit("127.0.0.1", &config::DnsConfig { rate_limit_per_ip: 0, ..cfg }), Admission::Allow);
+    }
+}
@@ -0,0 +1,132 @@
+// src-tauri/src/network/dns/rebind.rs
+// DNS rebinding protection: upstream answers pointing names at private, loopback, link-local or
+// CGNAT addresses are stripped or refused, so a public site can't reach devices (or us) on the LAN
+
+use crate::sysmodules::config::{self, RebindAction};
+use crate::sysmodules::logging;
+use super::wire::{Message, RData, RCODE_REFUSED};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+fn internal_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_private()            // 10/8, 172.16/12, 192.168/16
+        || ip.is_loopback()
+        || ip.is_link_local()  // 169.254/16
+        || ip.is_unspecified()
+        || a == 0              // "This network", reaches the local host on some systems
+        || (a == 100 && (b & 0xC0) == 64) // CGNAT 100.64/10 (RFC 6598)
+}
+
+fn internal_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || (first & 0xFFC0) == 0xFE80 // Link-local fe80::/10
+        || (first & 0xFE00) == 0xFC00 // Unique local fc00::/7
+        || ip.to_ipv4_mapped().is_some_and(internal_v4)
+}
+
+/// Whether an address is only reachable from inside the network
+pub fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => internal_v4(ip),
+        IpAddr::V6(ip) => internal_v6(ip),
+    }
+}
+
+fn under(name: &str, suffix: &str) -> bool {
+    let suffix = suffix.trim().trim_start_matches("*.").trim_matches('.').to_lowercase();
+    !suffix.is_empty() && (name == suffix || name.ends_with(&format!(".{}", suffix)))
+}
+
+/// Names allowed to resolve to internal addresses: the allowlist, and forward zones,
+/// whose split-DNS resolvers are there to answer with internal addresses
+fn allowed(name: &str, dns_config: &config::DnsConfig) -> bool {
+    let name = name.trim_end_matches('.').to_lowercase();
+    dns_config.rebind_allowlist.iter().any(|d| under(&name, d))
+        || dns_config.forward_zones.iter().filter(|z| z.enabled).any(|z| under(&name, &z.suffix))
+}
+
+/// Apply rebinding protection to a forwarded response. Returns the response to send instead
+/// when it pointed a name at an internal address, None when it can go out as it is.
+pub fn filter(request: &Message, response: &[u8], dns_config: &config::DnsConfig) -> Option<Vec<u8>> {
+    if !dns_config.rebind_protection {
+        return None;
+    }
+    let question = request.questions.first()?;
+    if allowed(&question.name, dns_config) {
+        return None;
+    }
+    let mut msg = Message::parse(response).ok()?;
+    let offending = |rdata: &RData| match rdata {
+        RData::A(ip) => is_internal(IpAddr::V4(*ip)),
+        RData::Aaaa(ip) => is_internal(IpAddr::V6(*ip)),
+        _ => false,
+    };
+    let blocked: Vec<String> = msg.answers.iter()
+        .filter(|rr| offending(&rr.rdata) && !allowed(&rr.name, dns_config))
+        .map(|rr| format!("{} {}", rr.name, rr.rdata))
+        .collect();
+    if blocked.is_empty() {
+        return None;
+    }
+    logging::log_debug(&format!("Rebinding protection filtered {}: {}", question.name, blocked.join(", ")));
+
+    match dns_config.rebind_action {
+        RebindAction::Strip => {
+            msg.answers.retain(|rr| !offending(&rr.rdata) || allowed(&rr.name, dns_config));
+            msg.authentic_data = false; // What's left is no longer the signed RRset
+            Some(msg.to_bytes())
+        }
+        RebindAction::Refuse => {
+            let mut refused = Message::response_to(request);
+            refused.rcode = RCODE_REFUSED;
+            Some(refused.to_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::wire::{RecordType, ResourceRecord};
+    use crate::sysmodules::config::ForwardZone;
+
+    #[test]
+    fn test_internal_ranges() {
+        for ip in ["10.0.0.1", "172.31.255.1", "192.168.1.1", "127.0.0.1", "169.254.1.1", "100.64.0.1", "100.127.255.254", "0.0.0.0", "::1", "fe80::1", "fd00::1", "::ffff:192.168.0.1"] {
+            assert!(is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "172.32.0.1", "100.128.0.1", "2606:4700::1111", "::ffff:1.1.1.1"] {
+            assert!(!is_internal(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let request = Message::query(3, "evil.example.com", RecordType::A);
+        let mut response = Message::response_to(&request);
+        response.answers.push(ResourceRecord::new("evil.example.com", RecordType::A, 60, RData::A("93.184.216.34".parse().unwrap())));
+        response.answers.push(ResourceRecord::new("evil.example.com", RecordType::A, 60, RData::A("10.0.0.1".parse().unwrap())));
+        let bytes = response.to_bytes();
+
+        let mut cfg = config::DnsConfig { rebind_protection: true, ..Default::default() };
+        let stripped = Message::parse(&filter(&request, &bytes, &cfg).unwrap()).unwrap();
+        assert_eq!(stripped.answers.len(), 1);
+        assert_eq!(stripped.answers[0].rdata.to_string(), "93.184.216.34");
+
+        cfg.rebind_action = RebindAction::Refuse;
+        let refused = Message::parse(&filter(&request, &bytes, &cfg).unwrap()).unwrap();
+        assert_eq!(refused.rcode, RCODE_REFUSED);
+        assert!(refused.answers.is_empty());
+
+        cfg.rebind_allowlist = vec!["example.com".into()];
+        assert!(filter(&request, &bytes, &cfg).is_none());
+        cfg.rebind_allowlist.clear();
+        cfg.forward_zones = vec![ForwardZone { suffix: "example.com".into(), upstreams: vec![], source_interface: String::new(), enabled: true }];
+        assert!(filter(&request, &bytes, &cfg).is_none());
+        cfg.rebind_protection = false;
+        cfg.forward_zones.clear();
+        assert!(filter(&request, &bytes, &cfg).is_none());
+    }
+}
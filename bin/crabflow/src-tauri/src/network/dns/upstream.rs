@@ -0,0 +1,678 @@
+// src-tauri/src/network/dns/upstream.rs
+// Forwarding to the configured upstream resolvers over UDP, TCP, TLS (RFC 7858) or HTTPS (RFC 8484),
+// with per-suffix forward zones, background health checks, latency-based selection and coalescing of identical in-flight queries
+
+use serde::Serialize;
+use crate::sysmodules::{config::{self, ForwardZone, UpstreamProtocol, UpstreamServer}, logging};
+use super::wire::{Message, RecordType};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+use tokio::sync::broadcast;
+use lazy_static::lazy_static;
+
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(2);
+/// Consecutive failures before an upstream is only tried when the healthy ones fail too
+const FAILURE_THRESHOLD: u32 = 3;
+
+static HEALTH_INTERVAL_SECS: AtomicU64 = AtomicU64::new(30);
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UpstreamStatus {
+    pub address: String,
+    pub protocol: UpstreamProtocol,
+    pub enabled: bool,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub last_rtt_ms: Option<u64>,
+    pub srtt_ms: Option<u64>, // Smoothed round trip, used to pick the fastest healthy upstream
+    pub last_error: Option<String>,
+    pub last_checked: Option<u64>,
+    pub queries: u64,
+    pub failures: u64,
+    pub zone: Option<String>, // Forward zone suffix, None for the default upstreams
+}
+
+impl UpstreamStatus {
+    fn new(server: &UpstreamServer) -> Self {
+        Self {
+            address: server.address.clone(),
+            protocol: server.protocol,
+            enabled: server.enabled,
+            healthy: true,
+            consecutive_failures: 0,
+            last_rtt_ms: None,
+            srtt_ms: None,
+            last_error: None,
+            last_checked: None,
+            queries: 0,
+            failures: 0,
+            zone: None,
+        }
+    }
+}
+
+/// Queries that would get the same upstream answer share one exchange
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct InflightKey {
+    name: String,
+    qtype: u16,
+    qclass: u16,
+    dnssec_ok: bool,
+    checking_disabled: bool,
+}
+
+/// An upstream answer and the server that gave it
+#[derive(Debug, Clone)]
+pub struct Forwarded {
+    pub response: Vec<u8>,
+    pub upstream: String,
+}
+
+type InflightSender = broadcast::Sender<Option<Arc<Forwarded>>>;
+
+lazy_static! {
+    static ref UPSTREAMS: RwLock<Vec<UpstreamServer>> = RwLock::new(config::DnsConfig::default().upstreams);
+    // Enabled forward zones, longest suffix first
+    static ref FORWARD_ZONES: RwLock<Vec<ForwardZone>> = RwLock::new(Vec::new());
+    static ref HEALTH: RwLock<HashMap<String, UpstreamStatus>> = RwLock::new(HashMap::new());
+    static ref INFLIGHT: Mutex<HashMap<InflightKey, InflightSender>> = Mutex::new(HashMap::new());
+    // DoH client, rebuilt when the outgoing interface changes so connections are reused between queries
+    static ref HTTP_CLIENT: Mutex<Option<(Option<IpAddr>, reqwest::Client)>> = Mutex::new(None);
+    static ref TLS_CONNECTOR: tokio_rustls::TlsConnector = {
+        let roots = rustls::RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+        let config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .expect("ring supports the default TLS versions")
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        tokio_rustls::TlsConnector::from(Arc::new(config))
+    };
+}
+
+fn status_key(server: &UpstreamServer) -> String {
+    format!("{:?}:{}", server.protocol, server.address)
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn normalize_suffix(suffix: &str) -> String {
+    suffix.trim().trim_matches('.').to_lowercase()
+}
+
+/// Enabled zones with normalized suffixes, most specific first
+fn sorted_zones(zones: &[ForwardZone]) -> Vec<ForwardZone> {
+    let mut zones: Vec<ForwardZone> = zones.iter()
+        .filter(|z| z.enabled && !normalize_suffix(&z.suffix).is_empty())
+        .map(|z| ForwardZone { suffix: normalize_suffix(&z.suffix), ..z.clone() })
+        .collect();
+    zones.sort_by_key(|z| std::cmp::Reverse(z.suffix.split('.').count()));
+    zones
+}
+
+/// Forward zone `name` falls under, the longest matching suffix winning
+fn zone_for<'a>(zones: &'a [ForwardZone], name: &str) -> Option<&'a ForwardZone> {
+    let name = normalize_suffix(name);
+    zones.iter().find(|z| name == z.suffix || name.ends_with(&format!(".{}", z.suffix)))
+}
+
+/// Apply upstream settings. Health is kept for servers that are still configured.
+pub fn configure(dns_config: &config::DnsConfig) {
+    HEALTH_INTERVAL_SECS.store(dns_config.upstream_health_interval_secs.max(5), Ordering::Relaxed);
+    let zones = sorted_zones(&dns_config.forward_zones);
+    let mut upstreams = UPSTREAMS.write().unwrap();
+    let mut forward_zones = FORWARD_ZONES.write().unwrap();
+    if *upstreams == dns_config.upstreams && *forward_zones == zones {
+        return;
+    }
+    *upstreams = dns_config.upstreams.clone();
+    *forward_zones = zones;
+    let keys: Vec<String> = upstreams.iter().chain(forward_zones.iter().flat_map(|z| &z.upstreams)).map(status_key).collect();
+    HEALTH.write().unwrap().retain(|k, _| keys.contains(k));
+    logging::log_info(&format!("DNS upstreams: {}", upstreams.iter().filter(|u| u.enabled).map(|u| u.address.as_str()).collect::<Vec<_>>().join(", ")));
+    for zone in forward_zones.iter() {
+        logging::log_info(&format!("DNS forward zone {}: {}", zone.suffix, zone.upstreams.iter().filter(|u| u.enabled).map(|u| u.address.as_str()).collect::<Vec<_>>().join(", ")));
+    }
+}
+
+fn zone_bind_ip(zone: &ForwardZone, default_bind: &str) -> String {
+    let source = zone.source_interface.trim();
+    if source.is_empty() { default_bind.to_string() } else { source.to_string() }
+}
+
+/// Resolvers and source address for `name`: its forward zone's, or the defaults
+fn route(name: Option<&str>) -> (Vec<UpstreamServer>, String) {
+    let default_bind = super::UPSTREAM_INTERFACE.read().unwrap().clone();
+    let zones = FORWARD_ZONES.read().unwrap();
+    match name.and_then(|n| zone_for(&zones, n)) {
+        Some(zone) => (zone.upstreams.clone(), zone_bind_ip(zone, &default_bind)),
+        None => (UPSTREAMS.read().unwrap().clone(), default_bind),
+    }
+}
+
+/// Enabled upstreams in the order to try them: healthy ones first, fastest first within each group.
+/// Servers without a measurement yet sort first so they get one; ties keep the configured order.
+fn ordered(servers: &[UpstreamServer], health: &HashMap<String, UpstreamStatus>) -> Vec<UpstreamServer> {
+    let mut enabled: Vec<&UpstreamServer> = servers.iter().filter(|s| s.enabled).collect();
+    enabled.sort_by_key(|s| match health.get(&status_key(s)) {
+        Some(h) => (!h.healthy, h.srtt_ms.unwrap_or(0)),
+        None => (false, 0),
+    });
+    enabled.into_iter().cloned().collect()
+}
+
+fn record_result(server: &UpstreamServer, result: &Result<Duration, String>, probe: bool) {
+    let mut health = HEALTH.write().unwrap();
+    let status = health.entry(status_key(server)).or_insert_with(|| UpstreamStatus::new(server));
+    status.enabled = server.enabled;
+    if !probe {
+        status.queries += 1;
+    }
+    status.last_checked = Some(now_secs());
+    match result {
+        Ok(rtt) => {
+            if !status.healthy {
+                logging::log_info(&format!("DNS upstream {} is reachable again", server.address));
+            }
+            let rtt = rtt.as_millis() as u64;
+            status.healthy = true;
+            status.consecutive_failures = 0;
+            status.last_rtt_ms = Some(rtt);
+            // Same weighting as TCP's SRTT: one slow answer moves the average by an eighth
+            status.srtt_ms = Some(status.srtt_ms.map_or(rtt, |srtt| (srtt * 7 + rtt) / 8));
+            status.last_error = None;
+        }
+        Err(e) => {
+            status.failures += 1;
+            status.consecutive_failures += 1;
+            status.last_error = Some(e.clone());
+            // A failure costs as much as a timeout, so flaky servers drift behind reliable ones
+            let penalty = UPSTREAM_TIMEOUT.as_millis() as u64;
+            status.srtt_ms = Some(status.srtt_ms.map_or(penalty, |srtt| (srtt * 7 + penalty) / 8));
+            if status.healthy && status.consecutive_failures >= FAILURE_THRESHOLD {
+                status.healthy = false;
+                logging::log_warn(&format!("DNS upstream {} marked unhealthy: {}", server.address, e));
+            }
+        }
+    }
+}
+
+fn inflight_key(request: &Message) -> Option<InflightKey> {
+    match request.questions.as_slice() {
+        [q] => Some(InflightKey {
+            name: q.name.to_lowercase(),
+            qtype: q.qtype.to_u16(),
+            qclass: q.qclass,
+            dnssec_ok: request.edns.as_ref().is_some_and(|e| e.dnssec_ok),
+            checking_disabled: request.checking_disabled,
+        }),
+        _ => None,
+    }
+}
+
+/// Removes the in-flight entry if the leading query is cancelled before it finishes
+struct InflightGuard(Option<InflightKey>);
+
+impl InflightGuard {
+    fn finish(mut self, response: &Option<Forwarded>) {
+        if let Some(key) = self.0.take() {
+            if let Some(sender) = INFLIGHT.lock().unwrap().remove(&key) {
+                let _ = sender.send(response.clone().map(Arc::new));
+            }
+        }
+    }
+}
+
+impl Drop for InflightGuard {
+    fn drop(&mut self) {
+        if let Some(key) = self.0.take() {
+            INFLIGHT.lock().unwrap().remove(&key);
+        }
+    }
+}
+
+/// Send a query upstream. While an identical query is already out, wait for its answer instead of sending another.
+pub async fn forward(request: &Message, query: &[u8]) -> Option<Forwarded> {
+    let (servers, bind_ip) = route(request.questions.first().map(|q| q.name.as_str()));
+    forward_coalesced(request, query, &servers, &bind_ip).await
+}
+
+async fn forward_coalesced(request: &Message, query: &[u8], servers: &[UpstreamServer], bind_ip: &str) -> Option<Forwarded> {
+    let key = match inflight_key(request) {
+        Some(k) => k,
+        None => return forward_uncoalesced(query, servers, bind_ip).await,
+    };
+    let waiting = {
+        let mut inflight = INFLIGHT.lock().unwrap();
+        match inflight.get(&key) {
+            Some(sender) => Some(sender.subscribe()),
+            None => {
+                inflight.insert(key.clone(), broadcast::channel(1).0);
+                None
+            }
+        }
+    };
+
+    if let Some(mut receiver) = waiting {
+        let shared = match receiver.recv().await {
+            Ok(Some(shared)) => shared,
+            Ok(None) => return None,
+            Err(_) => return forward_uncoalesced(query, servers, bind_ip).await, // The leading query was cancelled
+        };
+        return match readdress(&shared.response, request) {
+            Some(response) => Some(Forwarded { response, upstream: shared.upstream.clone() }),
+            None => forward_uncoalesced(query, servers, bind_ip).await,
+        };
+    }
+
+    let guard = InflightGuard(Some(key));
+    let response = forward_uncoalesced(query, servers, bind_ip).await;
+    guard.finish(&response);
+    response
+}
+
+/// The leader's answer for a follower: its ID, and its own capitalization of the question (0x20)
+fn readdress(response: &[u8], request: &Message) -> Option<Vec<u8>> {
+    let mut response = Message::parse(response).ok()?;
+    response.id = request.id;
+    response.questions = request.questions.clone();
+    response.recursion_desired = request.recursion_desired;
+    Some(response.to_bytes())
+}
+
+/// Try each upstream in turn until one answers
+async fn forward_uncoalesced(query: &[u8], servers: &[UpstreamServer], bind_ip: &str) -> Option<Forwarded> {
+    let servers = ordered(servers, &HEALTH.read().unwrap());
+    for server in &servers {
+        let started = Instant::now();
+        let result = exchange(server, query, bind_ip).await;
+        record_result(server, &result.as_ref().map(|_| started.elapsed()).map_err(|e| e.clone()), false);
+        match result {
+            Ok(response) => return Some(Forwarded { response, upstream: server.address.clone() }),
+            Err(e) => logging::log_debug(&format!("Upstream {} failed: {}", server.address, e)),
+        }
+    }
+    None
+}
+
+async fn timed<T>(operation: impl Future<Output = Result<T, String>>) -> Result<T, String> {
+    tokio::time::timeout(UPSTREAM_TIMEOUT, operation).await.map_err(|_| "Timed out".to_string())?
+}
+
+/// One query/response exchange with `server`
+async fn exchange(server: &UpstreamServer, query: &[u8], bind_ip: &str) -> Result<Vec<u8>, String> {
+    let response = match server.protocol {
+        UpstreamProtocol::Udp => {
+            let addr = socket_addr(&server.address, 53)?;
+            let response = timed(query_udp(query, addr, bind_ip)).await?;
+            if response.len() > 2 && response[2] & 0x02 != 0 {
+                // Upstream truncated the answer: ask again over TCP for the whole thing
+                match timed(query_tcp(query, addr, bind_ip)).await {
+                    Ok(full) => full,
+                    Err(e) => {
+                        // The client still gets TC and can retry over TCP itself
+                        logging::log_warn(&format!("TCP fallback to {} failed: {}", server.address, e));
+                        response
+                    }
+                }
+            } else {
+                response
+            }
+        }
+        UpstreamProtocol::Tcp => timed(query_tcp(query, socket_addr(&server.address, 53)?, bind_ip)).await?,
+        UpstreamProtocol::Tls => timed(query_tls(query, socket_addr(&server.address, 853)?, &server.tls_name, bind_ip)).await?,
+        UpstreamProtocol::Https => timed(query_https(query, &server.address, bind_ip)).await?,
+    };
+    // An answer to some other query (or garbage) counts as a failure
+    if !answers(query, &response) {
+        return Err("Response does not match the query".to_string());
+    }
+    Ok(response)
+}
+
+/// Whether `response` carries the query's ID and repeats its question
+fn answers(query: &[u8], response: &[u8]) -> bool {
+    let (query, response) = match (Message::parse(query), Message::parse(response)) {
+        (Ok(q), Ok(r)) => (q, r),
+        _ => return false,
+    };
+    response.id == query.id
+        && response.questions.len() == query.questions.len()
+        && response.questions.iter().zip(&query.questions).all(|(r, q)| {
+            r.name.eq_ignore_ascii_case(&q.name) && r.qtype == q.qtype && r.qclass == q.qclass
+        })
+}
+
+/// "1.1.1.1", "1.1.1.1:853", "[2606:4700::1111]:853". Names aren't resolved: we are the resolver.
+fn socket_addr(address: &str, default_port: u16) -> Result<SocketAddr, String> {
+    if let Ok(addr) = address.parse::<SocketAddr>() {
+        return Ok(addr);
+    }
+    address.trim_matches(|c| c == '[' || c == ']').parse::<IpAddr>()
+        .map(|ip| SocketAddr::new(ip, default_port))
+        .map_err(|_| format!("Upstream address must be an IP address, got {}", address))
+}
+
+/// The address `bind_ip` stands for when talking to an `ipv4` (or IPv6) server: an IP literal, or an
+/// address of the interface it names, e.g. "tun0". None sends from any address. A named interface
+/// without a usable address is an error, so queries meant for a VPN don't leave through the default route.
+fn source_address(bind_ip: &str, ipv4: bool) -> Result<Option<IpAddr>, String> {
+    let bind_ip = bind_ip.trim();
+    if bind_ip.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(ip) = bind_ip.parse::<IpAddr>() {
+        return Ok(Some(ip).filter(|ip| !ip.is_unspecified() && ip.is_ipv4() == ipv4));
+    }
+    get_if_addrs::get_if_addrs().unwrap_or_default().into_iter()
+        .filter(|i| i.name == bind_ip)
+        .map(|i| i.addr.ip())
+        // IPv6 link-local addresses can't be bound without a scope
+        .find(|ip| ip.is_ipv4() == ipv4 && !matches!(ip, IpAddr::V6(v6) if (v6.segments()[0] & 0xFFC0) == 0xFE80))
+        .map(Some)
+        .ok_or_else(|| format!("Interface {} has no {} address", bind_ip, if ipv4 { "IPv4" } else { "IPv6" }))
+}
+
+fn local_ip(bind_ip: &str, remote: &SocketAddr) -> Result<Option<IpAddr>, String> {
+    source_address(bind_ip, remote.is_ipv4())
+}
+
+async fn query_udp(query: &[u8], addr: SocketAddr, bind_ip: &str) -> Result<Vec<u8>, String> {
+    let local = local_ip(bind_ip, &addr)?.unwrap_or(if addr.is_ipv4() { IpAddr::from([0u8; 4]) } else { IpAddr::from([0u16; 8]) });
+    let socket = UdpSocket::bind(SocketAddr::new(local, 0)).await.map_err(|e| format!("Failed to bind forwarder to {}: {}", local, e))?;
+    socket.connect(addr).await.map_err(|e| e.to_string())?;
+    socket.send(query).await.map_err(|e| e.to_string())?;
+    let mut buf = vec![0u8; 65535];
+    loop {
+        let amt = socket.recv(&mut buf).await.map_err(|e| e.to_string())?;
+        // Skip stray datagrams (late answers, spoofing attempts) and keep waiting for ours
+        if amt >= 2 && buf[..2] == query[..2] {
+            buf.truncate(amt);
+            return Ok(buf);
+        }
+    }
+}
+
+async fn connect_tcp(addr: SocketAddr, bind_ip: &str) -> Result<TcpStream, String> {
+    let socket = if addr.is_ipv4() { TcpSocket::new_v4() } else { TcpSocket::new_v6() }.map_err(|e| e.to_string())?;
+    // Leave from the same interface as UDP queries
+    if let Some(ip) = local_ip(bind_ip, &addr)? {
+        socket.bind(SocketAddr::new(ip, 0)).map_err(|e| format!("Failed to bind to {}: {}", ip, e))?;
+    }
+    socket.connect(addr).await.map_err(|e| e.to_string())
+}
+
+/// Length-prefixed exchange used by both TCP and TLS (RFC 1035 4.2.2)
+async fn exchange_framed<S: AsyncRead + AsyncWrite + Unpin>(stream: &mut S, query: &[u8]) -> Result<Vec<u8>, String> {
+    let mut framed = Vec::with_capacity(query.len() + 2);
+    framed.extend_from_slice(&(query.len() as u16).to_be_bytes());
+    framed.extend_from_slice(query);
+    stream.write_all(&framed).await.map_err(|e| e.to_string())?;
+    stream.flush().await.map_err(|e| e.to_string())?;
+
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).await.map_err(|e| e.to_string())?;
+    let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response).await.map_err(|e| e.to_string())?;
+    Ok(response)
+}
+
+async fn query_tcp(query: &[u8], addr: SocketAddr, bind_ip: &str) -> Result<Vec<u8>, String> {
+    exchange_framed(&mut connect_tcp(addr, bind_ip).await?, query).await
+}
+
+async fn query_tls(query: &[u8], addr: SocketAddr, tls_name: &str, bind_ip: &str) -> Result<Vec<u8>, String> {
+    let name = if tls_name.is_empty() { addr.ip().to_string() } else { tls_name.to_string() };
+    let server_name = rustls::pki_types::ServerName::try_from(name.clone()).map_err(|_| format!("Invalid TLS name {}", name))?;
+    let tcp = connect_tcp(addr, bind_ip).await?;
+    let mut stream = TLS_CONNECTOR.connect(server_name, tcp).await.map_err(|e| format!("TLS: {}", e))?;
+    exchange_framed(&mut stream, query).await.map_err(|e| format!("TLS: {}", e))
+}
+
+fn http_client(bind_ip: &str) -> Result<reqwest::Client, String> {
+    let local = match bind_ip.trim().parse::<IpAddr>() {
+        Ok(ip) => Some(ip).filter(|ip| !ip.is_unspecified()),
+        // The server's family isn't known before the URL is resolved; prefer the interface's IPv4 address
+        Err(_) => source_address(bind_ip, true).or_else(|_| source_address(bind_ip, false))?,
+    };
+    let mut cached = HTTP_CLIENT.lock().unwrap();
+    if let Some((ip, client)) = cached.as_ref() {
+        if *ip == local {
+            return Ok(client.clone());
+        }
+    }
+    let mut builder = reqwest::Client::builder().timeout(UPSTREAM_TIMEOUT);
+    if let Some(ip) = local {
+        builder = builder.local_address(ip);
+    }
+    let client = builder.build().map_err(|e| e.to_string())?;
+    *cached = Some((local, client.clone()));
+    Ok(client)
+}
+
+/// RFC 8484 POST. The URL host is looked up with the system resolver, so use an IP
+/// (https://1.1.1.1/dns-query) when this router is its own resolver.
+async fn query_https(query: &[u8], url: &str, bind_ip: &str) -> Result<Vec<u8>, String> {
+    let response = http_client(bind_ip)?
+        .post(url)
+        .header("content-type", "application/dns-message")
+        .header("accept", "application/dns-message")
+        .body(query.to_vec())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status.as_u16()));
+    }
+    response.bytes().await.map(|b| b.to_vec()).map_err(|e| e.to_string())
+}
+
+fn probe_query() -> Vec<u8> {
+    let id = (std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0) & 0xFFFF) as u16;
+    Message::query(id, ".", RecordType::Ns).to_bytes()
+}
+
+/// Periodically probe every enabled upstream so failed ones come back, and slow ones are noticed, without client traffic.
+/// Runs on the DNS server's runtime and ends with it.
+pub fn spawn_health_checks() {
+    tokio::spawn(async {
+        while super::is_server_running() {
+            let default_bind = super::UPSTREAM_INTERFACE.read().unwrap().clone();
+            let mut servers: Vec<(UpstreamServer, String)> = UPSTREAMS.read().unwrap().iter().map(|s| (s.clone(), default_bind.clone())).collect();
+            for zone in FORWARD_ZONES.read().unwrap().iter() {
+                let bind_ip = zone_bind_ip(zone, &default_bind);
+                servers.extend(zone.upstreams.iter().map(|s| (s.clone(), bind_ip.clone())));
+            }
+            for (server, bind_ip) in servers.iter().filter(|(s, _)| s.enabled) {
+                let started = Instant::now();
+                let result = exchange(server, &probe_query(), bind_ip).await.map(|_| started.elapsed());
+                record_result(server, &result, true);
+            }
+            tokio::time::sleep(Duration::from_secs(HEALTH_INTERVAL_SECS.load(Ordering::Relaxed))).await;
+        }
+    });
+}
+
+/// Health of the configured upstreams in configured order, followed by each forward zone's
+#[tauri::command]
+pub fn get_upstream_status() -> Vec<UpstreamStatus> {
+    let health = HEALTH.read().unwrap();
+    let status = |s: &UpstreamServer, zone: Option<&str>| UpstreamStatus {
+        zone: zone.map(str::to_string),
+        ..health.get(&status_key(s)).cloned().unwrap_or_else(|| UpstreamStatus::new(s))
+    };
+    let mut list: Vec<UpstreamStatus> = UPSTREAMS.read().unwrap().iter().map(|s| status(s, None)).collect();
+    for zone in FORWARD_ZONES.read().unwrap().iter() {
+        list.extend(zone.upstreams.iter().map(|s| status(s, Some(&zone.suffix))));
+    }
+    list
+}
+
+/// Query `server` once and return the round trip in milliseconds. Used to check a server before saving it.
+#[tauri::command]
+pub async fn test_upstream(server: UpstreamServer) -> Result<u64, String> {
+    let bind_ip = super::UPSTREAM_INTERFACE.read().unwrap().clone();
+    let started = Instant::now();
+    exchange(&server, &probe_query(), &bind_ip).await?;
+    Ok(started.elapsed().as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    /// Stand-in resolver: echoes each query back as an empty NOERROR response
+    fn answer(query: &[u8]) -> Vec<u8> {
+        let request = Message::parse(query).unwrap();
+        Message::response_to(&request).to_bytes()
+    }
+
+    /// UDP stand-in that answers after `delay`, counting the queries it sees
+    fn stub_udp(delay: Duration, seen: Arc<AtomicUsize>) -> SocketAddr {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((amt, src)) = socket.recv_from(&mut buf) {
+                seen.fetch_add(1, Ordering::SeqCst);
+                thread::sleep(delay);
+                let _ = socket.send_to(&answer(&buf[..amt]), src);
+            }
+        });
+        addr
+    }
+
+    fn stub_tcp() -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut len = [0u8; 2];
+                let _ = stream.read_exact(&mut len);
+                let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+                let _ = stream.read_exact(&mut query);
+                let response = answer(&query);
+                let _ = stream.write_all(&(response.len() as u16).to_be_bytes());
+                let _ = stream.write_all(&response);
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_exchange_with_stub_resolvers() {
+        let query = Message::query(0x4242, "example.com", RecordType::A).to_bytes();
+        let udp = stub_udp(Duration::ZERO, Arc::new(AtomicUsize::new(0)));
+        for (addr, protocol) in [(udp, UpstreamProtocol::Udp), (stub_tcp(), UpstreamProtocol::Tcp)] {
+            let server = UpstreamServer::new(&addr.to_string(), protocol);
+            let response = Message::parse(&exchange(&server, &query, "0.0.0.0").await.unwrap()).unwrap();
+            assert_eq!(response.id, 0x4242);
+            assert!(response.response);
+        }
+        assert!(socket_addr("example.com", 53).is_err());
+        assert_eq!(socket_addr("[2606:4700::1111]", 853).unwrap().port(), 853);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_coalesces_inflight_queries() {
+        let seen = Arc::new(AtomicUsize::new(0));
+        let addr = stub_udp(Duration::from_millis(200), seen.clone());
+        let servers = Arc::new(vec![UpstreamServer::new(&addr.to_string(), UpstreamProtocol::Udp)]);
+        let names = ["coalesce.example", "Coalesce.example", "cOALESCE.example", "COALESCE.EXAMPLE", "coalesce.Example"];
+
+        let tasks: Vec<_> = names.iter().enumerate().map(|(id, name)| {
+            let servers = servers.clone();
+            let request = Message::query(100 + id as u16, name, RecordType::A);
+            tokio::spawn(async move {
+                let forwarded = forward_coalesced(&request, &request.to_bytes(), &servers, "0.0.0.0").await?;
+                let response = Message::parse(&forwarded.response).unwrap();
+                Some((response.id, response.questions[0].name.clone()))
+            })
+        }).collect();
+        for (id, task) in tasks.into_iter().enumerate() {
+            assert_eq!(task.await.unwrap(), Some((100 + id as u16, names[id].to_string())));
+        }
+        assert_eq!(seen.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_response_must_answer_the_query() {
+        let query = Message::query(7, "www.Example.com", RecordType::A);
+        let mut response = Message::response_to(&query);
+        assert!(answers(&query.to_bytes(), &response.to_bytes()));
+        response.questions[0].name = "WWW.example.COM".into();
+        assert!(answers(&query.to_bytes(), &response.to_bytes()));
+        response.questions[0].name = "evil.example.com".into();
+        assert!(!answers(&query.to_bytes(), &response.to_bytes()));
+        assert!(!answers(&query.to_bytes(), &Message::response_to(&Message::query(8, "www.example.com", RecordType::A)).to_bytes()));
+        assert!(!answers(&query.to_bytes(), &Message::response_to(&Message::query(7, "www.example.com", RecordType::Aaaa)).to_bytes()));
+        assert!(!answers(&query.to_bytes(), &[0, 7]));
+    }
+
+    #[test]
+    fn test_upstream_selection() {
+        let servers = vec![
+            UpstreamServer::new("10.0.0.1", UpstreamProtocol::Udp),
+            UpstreamServer::new("10.0.0.2", UpstreamProtocol::Tls),
+            UpstreamServer { enabled: false, ..UpstreamServer::new("10.0.0.3", UpstreamProtocol::Udp) },
+            UpstreamServer::new("10.0.0.4", UpstreamProtocol::Tcp),
+        ];
+        let mut health = HashMap::new();
+        let mut down = UpstreamStatus::new(&servers[0]);
+        down.healthy = false;
+        health.insert(status_key(&servers[0]), down);
+        let mut slow = UpstreamStatus::new(&servers[1]);
+        slow.srtt_ms = Some(80);
+        health.insert(status_key(&servers[1]), slow);
+        let mut fast = UpstreamStatus::new(&servers[3]);
+        fast.srtt_ms = Some(12);
+        health.insert(status_key(&servers[3]), fast);
+
+        let order: Vec<String> = ordered(&servers, &health).into_iter().map(|s| s.address).collect();
+        assert_eq!(order, vec!["10.0.0.4", "10.0.0.2", "10.0.0.1"]);
+    }
+
+    #[test]
+    fn test_source_address() {
+        assert_eq!(source_address("", true), Ok(None));
+        assert_eq!(source_address("0.0.0.0", true), Ok(None));
+        assert_eq!(source_address("10.8.0.2", true), Ok(Some("10.8.0.2".parse().unwrap())));
+        assert_eq!(source_address("10.8.0.2", false), Ok(None));
+        if cfg!(target_os = "linux") {
+            assert_eq!(source_address("lo", true), Ok(Some("127.0.0.1".parse().unwrap())));
+        }
+        assert!(source_address("no-such-interface0", true).is_err());
+    }
+
+    #[test]
+    fn test_forward_zone_matching() {
+        let zone = |suffix: &str, enabled| ForwardZone {
+            suffix: suffix.into(),
+            upstreams: vec![UpstreamServer::new("10.8.0.1", UpstreamProtocol::Udp)],
+            source_interface: String::new(),
+            enabled,
+        };
+        let zones = sorted_zones(&[zone("Example.com.", true), zone("corp.example.com", true), zone("10.in-addr.arpa", true), zone("lab.example.com", false)]);
+        assert_eq!(zones.len(), 3);
+
+        let matched = |name| zone_for(&zones, name).map(|z| z.suffix.as_str());
+        assert_eq!(matched("dc1.CORP.example.com."), Some("corp.example.com"));
+        assert_eq!(matched("corp.example.com"), Some("corp.example.com"));
+        assert_eq!(matched("www.example.com"), Some("example.com"));
+        assert_eq!(matched("host.lab.example.com"), Some("example.com"));
+        assert_eq!(matched("5.0.0.10.in-addr.arpa"), Some("10.in-addr.arpa"));
+        assert_eq!(matched("badexample.com"), None);
+        assert_eq!(matched("example.org"), None);
+    }
+}
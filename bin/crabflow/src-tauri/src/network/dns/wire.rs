@@ -0,0 +1,763 @@
+// src-tauri/src/network/dns/wire.rs
+// DNS wire format (RFC 1035): message parsing and building with name compression and EDNS0 (RFC 6891)
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// UDP payload size we advertise in our own OPT record (the DNS flag day 2020 value)
+pub const EDNS_UDP_SIZE: u16 = 1232;
+/// Largest UDP response a client without EDNS accepts (RFC 1035)
+pub const MIN_UDP_SIZE: usize = 512;
+
+pub const CLASS_IN: u16 = 1;
+
+pub const RCODE_NOERROR: u16 = 0;
+pub const RCODE_FORMERR: u16 = 1;
+pub const RCODE_SERVFAIL: u16 = 2;
+pub const RCODE_NXDOMAIN: u16 = 3;
+pub const RCODE_NOTIMP: u16 = 4;
+pub const RCODE_REFUSED: u16 = 5;
+
+/// EDNS option carrying an Extended DNS Error (RFC 8914): an info code and optional text
+pub const EDNS_OPTION_EDE: u16 = 15;
+
+/// Guards against compression pointer loops
+const MAX_POINTER_HOPS: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RecordType {
+    A,
+    Ns,
+    Cname,
+    Soa,
+    Ptr,
+    Mx,
+    Txt,
+    Aaaa,
+    Srv,
+    Opt,
+    Ds,
+    Rrsig,
+    Nsec,
+    Dnskey,
+    Nsec3,
+    Any,
+    Other(u16),
+}
+
+impl RecordType {
+    pub fn from_u16(value: u16) -> Self {
+        match value {
+            1 => RecordType::A,
+            2 => RecordType::Ns,
+            5 => RecordType::Cname,
+            6 => RecordType::Soa,
+            12 => RecordType::Ptr,
+            15 => RecordType::Mx,
+            16 => RecordType::Txt,
+            28 => RecordType::Aaaa,
+            33 => RecordType::Srv,
+            41 => RecordType::Opt,
+            43 => RecordType::Ds,
+            46 => RecordType::Rrsig,
+            47 => RecordType::Nsec,
+            48 => RecordType::Dnskey,
+            50 => RecordType::Nsec3,
+            255 => RecordType::Any,
+            other => RecordType::Other(other),
+        }
+    }
+
+    pub fn to_u16(self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::Ns => 2,
+            RecordType::Cname => 5,
+            RecordType::Soa => 6,
+            RecordType::Ptr => 12,
+            RecordType::Mx => 15,
+            RecordType::Txt => 16,
+            RecordType::Aaaa => 28,
+            RecordType::Srv => 33,
+            RecordType::Opt => 41,
+            RecordType::Ds => 43,
+            RecordType::Rrsig => 46,
+            RecordType::Nsec => 47,
+            RecordType::Dnskey => 48,
+            RecordType::Nsec3 => 50,
+            RecordType::Any => 255,
+            RecordType::Other(v) => v,
+        }
+    }
+
+    /// "AAAA", "mx", "TYPE65" -> record type
+    pub fn from_name(name: &str) -> Option<Self> {
+        let upper = name.trim().to_uppercase();
+        Some(match upper.as_str() {
+            "A" => RecordType::A,
+            "NS" => RecordType::Ns,
+            "CNAME" => RecordType::Cname,
+            "SOA" => RecordType::Soa,
+            "PTR" => RecordType::Ptr,
+            "MX" => RecordType::Mx,
+            "TXT" => RecordType::Txt,
+            "AAAA" => RecordType::Aaaa,
+            "SRV" => RecordType::Srv,
+            "OPT" => RecordType::Opt,
+            "DS" => RecordType::Ds,
+            "RRSIG" => RecordType::Rrsig,
+            "NSEC" => RecordType::Nsec,
+            "DNSKEY" => RecordType::Dnskey,
+            "NSEC3" => RecordType::Nsec3,
+            "ANY" | "*" => RecordType::Any,
+            _ => RecordType::from_u16(upper.strip_prefix("TYPE")?.parse().ok()?),
+        })
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordType::A => write!(f, "A"),
+            RecordType::Ns => write!(f, "NS"),
+            RecordType::Cname => write!(f, "CNAME"),
+            RecordType::Soa => write!(f, "SOA"),
+            RecordType::Ptr => write!(f, "PTR"),
+            RecordType::Mx => write!(f, "MX"),
+            RecordType::Txt => write!(f, "TXT"),
+            RecordType::Aaaa => write!(f, "AAAA"),
+            RecordType::Srv => write!(f, "SRV"),
+            RecordType::Opt => write!(f, "OPT"),
+            RecordType::Ds => write!(f, "DS"),
+            RecordType::Rrsig => write!(f, "RRSIG"),
+            RecordType::Nsec => write!(f, "NSEC"),
+            RecordType::Dnskey => write!(f, "DNSKEY"),
+            RecordType::Nsec3 => write!(f, "NSEC3"),
+            RecordType::Any => write!(f, "ANY"),
+            RecordType::Other(v) => write!(f, "TYPE{}", v),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Question {
+    pub name: String,
+    pub qtype: RecordType,
+    pub qclass: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(String),
+    Ns(String),
+    Ptr(String),
+    Mx { preference: u16, exchange: String },
+    Txt(Vec<Vec<u8>>),
+    Srv { priority: u16, weight: u16, port: u16, target: String },
+    Soa { mname: String, rname: String, serial: u32, refresh: u32, retry: u32, expire: u32, minimum: u32 },
+    /// Any type we don't decode, kept byte for byte
+    Raw(Vec<u8>),
+}
+
+impl RData {
+    /// Parse presentation format, as stored in `DnsRecord.value`:
+    /// MX "10 mail.example.com", SRV "0 5 5060 sip.example.com",
+    /// SOA "ns1.example.com hostmaster.example.com 1 3600 600 86400 300", TXT as-is or "quoted" "strings"
+    pub fn from_text(rtype: RecordType, value: &str) -> Result<RData, String> {
+        let value = value.trim();
+        let fields: Vec<&str> = value.split_whitespace().collect();
+        let num = |s: &str| s.parse::<u32>().map_err(|_| format!("'{}' is not a number", s));
+        let num16 = |s: &str| s.parse::<u16>().map_err(|_| format!("'{}' is not a 16-bit number", s));
+        let name = |s: &str| -> Result<String, String> {
+            let s = s.trim_end_matches('.');
+            if s.is_empty() || s.split('.').any(|l| l.is_empty() || l.len() > 63) {
+                return Err(format!("'{}' is not a valid domain name", s));
+            }
+            Ok(s.to_string())
+        };
+
+        match rtype {
+            RecordType::A => value.parse().map(RData::A).map_err(|_| format!("'{}' is not an IPv4 address", value)),
+            RecordType::Aaaa => value.parse().map(RData::Aaaa).map_err(|_| format!("'{}' is not an IPv6 address", value)),
+            RecordType::Cname => name(value).map(RData::Cname),
+            RecordType::Ns => name(value).map(RData::Ns),
+            RecordType::Ptr => name(value).map(RData::Ptr),
+            RecordType::Mx => match fields.as_slice() {
+                [exchange] => Ok(RData::Mx { preference: 10, exchange: name(exchange)? }),
+                [preference, exchange] => Ok(RData::Mx { preference: num16(preference)?, exchange: name(exchange)? }),
+                _ => Err("MX value must be '<preference> <exchange>'".into()),
+            },
+            RecordType::Txt => Ok(RData::Txt(parse_txt(value))),
+            RecordType::Srv => match fields.as_slice() {
+                [priority, weight, port, target] => Ok(RData::Srv {
+                    priority: num16(priority)?,
+                    weight: num16(weight)?,
+                    port: num16(port)?,
+                    target: name(target)?,
+                }),
+                _ => Err("SRV value must be '<priority> <weight> <port> <target>'".into()),
+            },
+            RecordType::Soa => match fields.as_slice() {
+                [mname, rname, serial, refresh, retry, expire, minimum] => Ok(RData::Soa {
+                    mname: name(mname)?,
+                    rname: name(rname)?,
+                    serial: num(serial)?,
+                    refresh: num(refresh)?,
+                    retry: num(retry)?,
+                    expire: num(expire)?,
+                    minimum: num(minimum)?,
+                }),
+                _ => Err("SOA value must be '<mname> <rname> <serial> <refresh> <retry> <expire> <minimum>'".into()),
+            },
+            other => Err(format!("{} records are not supported", other)),
+        }
+    }
+}
+
+/// `"a b" "c"` -> [a b, c]; unquoted text is one string, split into 255-byte chunks
+fn parse_txt(value: &str) -> Vec<Vec<u8>> {
+    if value.starts_with('"') {
+        let mut strings = Vec::new();
+        let mut current: Option<Vec<u8>> = None;
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            match (c, current.as_mut()) {
+                ('"', None) => current = Some(Vec::new()),
+                ('"', Some(_)) => strings.push(current.take().unwrap_or_default()),
+                ('\\', Some(s)) => { if let Some(n) = chars.next() { s.extend_from_slice(n.to_string().as_bytes()); } }
+                (c, Some(s)) => s.extend_from_slice(c.to_string().as_bytes()),
+                (_, None) => {}
+            }
+        }
+        strings.extend(current);
+        return strings;
+    }
+    let bytes = value.as_bytes();
+    if bytes.is_empty() {
+        return vec![Vec::new()];
+    }
+    bytes.chunks(255).map(|c| c.to_vec()).collect()
+}
+
+impl fmt::Display for RData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RData::A(ip) => write!(f, "{}", ip),
+            RData::Aaaa(ip) => write!(f, "{}", ip),
+            RData::Cname(n) | RData::Ns(n) | RData::Ptr(n) => write!(f, "{}", n),
+            RData::Mx { preference, exchange } => write!(f, "{} {}", preference, exchange),
+            RData::Txt(strings) => {
+                let quoted: Vec<String> = strings.iter()
+                    .map(|s| format!("\"{}\"", String::from_utf8_lossy(s).replace('\\', "\\\\").replace('"', "\\\"")))
+                    .collect();
+                write!(f, "{}", quoted.join(" "))
+            }
+            RData::Srv { priority, weight, port, target } => write!(f, "{} {} {} {}", priority, weight, port, target),
+            RData::Soa { mname, rname, serial, refresh, retry, expire, minimum } =>
+                write!(f, "{} {} {} {} {} {} {}", mname, rname, serial, refresh, retry, expire, minimum),
+            RData::Raw(bytes) => write!(f, "\\# {} {}", bytes.len(), bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResourceRecord {
+    pub name: String,
+    pub rtype: RecordType,
+    pub class: u16,
+    pub ttl: u32,
+    pub rdata: RData,
+}
+
+impl ResourceRecord {
+    pub fn new(name: &str, rtype: RecordType, ttl: u32, rdata: RData) -> Self {
+        Self { name: name.trim_end_matches('.').to_string(), rtype, class: CLASS_IN, ttl, rdata }
+    }
+}
+
+/// EDNS0 pseudo-record (OPT). The extended RCODE bits live in `Message.rcode`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Edns {
+    pub udp_size: u16,
+    pub version: u8,
+    pub dnssec_ok: bool,
+    pub options: Vec<(u16, Vec<u8>)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Message {
+    pub id: u16,
+    pub response: bool,
+    pub opcode: u8,
+    pub authoritative: bool,
+    pub truncated: bool,
+    pub recursion_desired: bool,
+    pub recursion_available: bool,
+    pub authentic_data: bool,
+    pub checking_disabled: bool,
+    pub rcode: u16, // 12 bits: the low 4 in the header, the rest in OPT
+    pub questions: Vec<Question>,
+    pub answers: Vec<ResourceRecord>,
+    pub authorities: Vec<ResourceRecord>,
+    pub additionals: Vec<ResourceRecord>, // Excluding OPT, which is in `edns`
+    pub edns: Option<Edns>,
+}
+
+impl Message {
+    /// A recursive query for one name
+    pub fn query(id: u16, name: &str, qtype: RecordType) -> Self {
+        Message {
+            id,
+            recursion_desired: true,
+            questions: vec![Question { name: name.trim_end_matches('.').to_string(), qtype, qclass: CLASS_IN }],
+            ..Default::default()
+        }
+    }
+
+    /// Empty NOERROR response echoing the request's ID, flags, questions and EDNS
+    pub fn response_to(request: &Message) -> Self {
+        Message {
+            id: request.id,
+            response: true,
+            opcode: request.opcode,
+            recursion_desired: request.recursion_desired,
+            recursion_available: true,
+            checking_disabled: request.checking_disabled,
+            questions: request.questions.clone(),
+            edns: request.edns.as_ref().map(|e| Edns {
+                udp_size: EDNS_UDP_SIZE,
+                version: 0,
+                dnssec_ok: e.dnssec_ok,
+                options: Vec::new(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Largest UDP response the sender of this query accepts, capped at what we advertise
+    pub fn max_udp_size(&self) -> usize {
+        self.edns.as_ref().map_or(MIN_UDP_SIZE, |e| (e.udp_size as usize).clamp(MIN_UDP_SIZE, EDNS_UDP_SIZE as usize))
+    }
+
+    /// Encode for a UDP client accepting `limit` bytes. Additional records are dropped first;
+    /// if the answer still doesn't fit, only the header and question go out with TC set (RFC 2181 9).
+    pub fn to_udp_bytes(&self, limit: usize) -> Vec<u8> {
+        let bytes = self.to_bytes();
+        if bytes.len() <= limit {
+            return bytes;
+        }
+        let mut trimmed = self.clone();
+        trimmed.additionals.clear();
+        let bytes = trimmed.to_bytes();
+        if bytes.len() <= limit {
+            return bytes;
+        }
+        trimmed.answers.clear();
+        trimmed.authorities.clear();
+        trimmed.truncated = true;
+        trimmed.to_bytes()
+    }
+
+    pub fn parse(buf: &[u8]) -> Result<Message, String> {
+        let mut r = Reader { buf, pos: 0 };
+        let id = r.u16()?;
+        let flags = r.u16()?;
+        let counts = [r.u16()?, r.u16()?, r.u16()?, r.u16()?];
+
+        let mut msg = Message {
+            id,
+            response: flags & 0x8000 != 0,
+            opcode: ((flags >> 11) & 0x0F) as u8,
+            authoritative: flags & 0x0400 != 0,
+            truncated: flags & 0x0200 != 0,
+            recursion_desired: flags & 0x0100 != 0,
+            recursion_available: flags & 0x0080 != 0,
+            authentic_data: flags & 0x0020 != 0,
+            checking_disabled: flags & 0x0010 != 0,
+            rcode: flags & 0x000F,
+            ..Default::default()
+        };
+
+        for _ in 0..counts[0] {
+            let name = r.name()?;
+            let qtype = RecordType::from_u16(r.u16()?);
+            let qclass = r.u16()?;
+            msg.questions.push(Question { name, qtype, qclass });
+        }
+        for _ in 0..counts[1] {
+            msg.answers.push(r.record()?);
+        }
+        for _ in 0..counts[2] {
+            msg.authorities.push(r.record()?);
+        }
+        for _ in 0..counts[3] {
+            let rr = r.record()?;
+            if rr.rtype != RecordType::Opt {
+                msg.additionals.push(rr);
+                continue;
+            }
+            if msg.edns.is_some() {
+                return Err("more than one OPT record".into());
+            }
+            let options = match &rr.rdata {
+                RData::Raw(data) => parse_edns_options(data)?,
+                _ => Vec::new(),
+            };
+            msg.rcode |= ((rr.ttl >> 24) as u16) << 4;
+            msg.edns = Some(Edns {
+                udp_size: rr.class,
+                version: (rr.ttl >> 16) as u8,
+                dnssec_ok: rr.ttl & 0x8000 != 0,
+                options,
+            });
+        }
+        Ok(msg)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer { buf: Vec::with_capacity(512), names: HashMap::new(), canonical: false };
+        let flags = (self.response as u16) << 15
+            | ((self.opcode as u16) & 0x0F) << 11
+            | (self.authoritative as u16) << 10
+            | (self.truncated as u16) << 9
+            | (self.recursion_desired as u16) << 8
+            | (self.recursion_available as u16) << 7
+            | (self.authentic_data as u16) << 5
+            | (self.checking_disabled as u16) << 4
+            | (self.rcode & 0x0F);
+        let arcount = self.additionals.len() + self.edns.is_some() as usize;
+
+        w.u16(self.id);
+        w.u16(flags);
+        w.u16(self.questions.len() as u16);
+        w.u16(self.answers.len() as u16);
+        w.u16(self.authorities.len() as u16);
+        w.u16(arcount as u16);
+
+        for q in &self.questions {
+            w.name(&q.name, true);
+            w.u16(q.qtype.to_u16());
+            w.u16(q.qclass);
+        }
+        for rr in self.answers.iter().chain(&self.authorities).chain(&self.additionals) {
+            w.record(rr);
+        }
+        if let Some(edns) = &self.edns {
+            w.buf.push(0); // Root name
+            w.u16(RecordType::Opt.to_u16());
+            w.u16(edns.udp_size);
+            w.u32(((self.rcode >> 4) as u32) << 24 | (edns.version as u32) << 16 | if edns.dnssec_ok { 0x8000 } else { 0 });
+            let len_at = w.buf.len();
+            w.u16(0);
+            for (code, data) in &edns.options {
+                w.u16(*code);
+                w.u16(data.len() as u16);
+                w.buf.extend_from_slice(data);
+            }
+            w.patch_len(len_at);
+        }
+        w.buf
+    }
+}
+
+/// Uncompressed, lowercased wire form of a name, as DNSSEC signs it (RFC 4034 6.2)
+pub fn canonical_name(name: &str) -> Vec<u8> {
+    let mut w = Writer { buf: Vec::new(), names: HashMap::new(), canonical: true };
+    w.name(name, false);
+    w.buf
+}
+
+impl RData {
+    /// Uncompressed wire form with embedded names lowercased (RFC 4034 6.2). Raw data is kept as received.
+    pub fn to_canonical_bytes(&self) -> Vec<u8> {
+        let mut w = Writer { buf: Vec::new(), names: HashMap::new(), canonical: true };
+        w.rdata(self);
+        w.buf
+    }
+}
+
+fn parse_edns_options(data: &[u8]) -> Result<Vec<(u16, Vec<u8>)>, String> {
+    let mut r = Reader { buf: data, pos: 0 };
+    let mut options = Vec::new();
+    while r.pos < data.len() {
+        let code = r.u16()?;
+        let len = r.u16()? as usize;
+        options.push((code, r.bytes(len)?.to_vec()));
+    }
+    Ok(options)
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|e| *e <= self.buf.len()).ok_or("message truncated")?;
+        let out = &self.buf[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Read a possibly compressed name; the cursor ends after the name's in-place bytes
+    fn name(&mut self) -> Result<String, String> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut pos = self.pos;
+        let mut resume = None;
+        let mut hops = 0;
+        loop {
+            let len = *self.buf.get(pos).ok_or("name truncated")? as usize;
+            match len & 0xC0 {
+                0x00 if len == 0 => {
+                    pos += 1;
+                    break;
+                }
+                0x00 => {
+                    let label = self.buf.get(pos + 1..pos + 1 + len).ok_or("label truncated")?;
+                    labels.push(String::from_utf8_lossy(label).into_owned());
+                    pos += 1 + len;
+                }
+                0xC0 => {
+                    let low = *self.buf.get(pos + 1).ok_or("pointer truncated")? as usize;
+                    hops += 1;
+                    if hops > MAX_POINTER_HOPS {
+                        return Err("compression pointer loop".into());
+                    }
+                    resume.get_or_insert(pos + 2);
+                    pos = (len & 0x3F) << 8 | low;
+                }
+                _ => return Err("unsupported label type".into()),
+            }
+        }
+        self.pos = resume.unwrap_or(pos);
+        let name = labels.join(".");
+        if name.len() > 253 {
+            return Err("name too long".into());
+        }
+        Ok(name)
+    }
+
+    fn record(&mut self) -> Result<ResourceRecord, String> {
+        let name = self.name()?;
+        let rtype = RecordType::from_u16(self.u16()?);
+        let class = self.u16()?;
+        let ttl = self.u32()?;
+        let rdlen = self.u16()? as usize;
+        let start = self.pos;
+        let end = start + rdlen;
+        if end > self.buf.len() {
+            return Err("rdata truncated".into());
+        }
+
+        let rdata = match rtype {
+            RecordType::A if rdlen == 4 => {
+                let b = self.bytes(4)?;
+                RData::A(Ipv4Addr::new(b[0], b[1], b[2], b[3]))
+            }
+            RecordType::Aaaa if rdlen == 16 => {
+                let b: [u8; 16] = self.bytes(16)?.try_into().map_err(|_| "bad AAAA")?;
+                RData::Aaaa(Ipv6Addr::from(b))
+            }
+            RecordType::Cname => RData::Cname(self.name()?),
+            RecordType::Ns => RData::Ns(self.name()?),
+            RecordType::Ptr => RData::Ptr(self.name()?),
+            RecordType::Mx => RData::Mx { preference: self.u16()?, exchange: self.name()? },
+            RecordType::Srv => RData::Srv { priority: self.u16()?, weight: self.u16()?, port: self.u16()?, target: self.name()? },
+            RecordType::Soa => RData::Soa {
+                mname: self.name()?,
+                rname: self.name()?,
+                serial: self.u32()?,
+                refresh: self.u32()?,
+                retry: self.u32()?,
+                expire: self.u32()?,
+                minimum: self.u32()?,
+            },
+            RecordType::Txt => {
+                let mut strings = Vec::new();
+                while self.pos < end {
+                    let len = self.u8()? as usize;
+                    strings.push(self.bytes(len)?.to_vec());
+                }
+                RData::Txt(strings)
+            }
+            _ => RData::Raw(self.bytes(rdlen)?.to_vec()),
+        };
+        if self.pos != end {
+            return Err(format!("{} rdata length mismatch", rtype));
+        }
+        Ok(ResourceRecord { name, rtype, class, ttl, rdata })
+    }
+}
+
+struct Writer {
+    buf: Vec<u8>,
+    names: HashMap<String, u16>, // Lowercased suffix -> offset, for compression
+    canonical: bool, // DNSSEC canonical form: no compression, lowercase names
+}
+
+impl Writer {
+    fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    fn name(&mut self, name: &str, compress: bool) {
+        let lowered;
+        let (name, compress) = if self.canonical {
+            lowered = name.to_ascii_lowercase();
+            (lowered.as_str(), false)
+        } else {
+            (name, compress)
+        };
+        let labels: Vec<&str> = name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()).collect();
+        for i in 0..labels.len() {
+            let suffix = labels[i..].join(".").to_lowercase();
+            if compress {
+                if let Some(offset) = self.names.get(&suffix) {
+                    self.u16(0xC000 | offset);
+                    return;
+                }
+            }
+            // Pointers can only reach the first 16 KiB
+            if self.buf.len() < 0x3FFF {
+                self.names.entry(suffix).or_insert(self.buf.len() as u16);
+            }
+            let label = &labels[i].as_bytes()[..labels[i].len().min(63)];
+            self.buf.push(label.len() as u8);
+            self.buf.extend_from_slice(label);
+        }
+        self.buf.push(0);
+    }
+
+    /// Fill in a u16 length placeholder at `at` with the number of bytes written after it
+    fn patch_len(&mut self, at: usize) {
+        let len = (self.buf.len() - at - 2) as u16;
+        self.buf[at..at + 2].copy_from_slice(&len.to_be_bytes());
+    }
+
+    fn record(&mut self, rr: &ResourceRecord) {
+        self.name(&rr.name, true);
+        self.u16(rr.rtype.to_u16());
+        self.u16(rr.class);
+        self.u32(rr.ttl);
+        let len_at = self.buf.len();
+        self.u16(0);
+        self.rdata(&rr.rdata);
+        self.patch_len(len_at);
+    }
+
+    fn rdata(&mut self, rdata: &RData) {
+        match rdata {
+            RData::A(ip) => self.buf.extend_from_slice(&ip.octets()),
+            RData::Aaaa(ip) => self.buf.extend_from_slice(&ip.octets()),
+            RData::Cname(n) | RData::Ns(n) | RData::Ptr(n) => self.name(n, true),
+            RData::Mx { preference, exchange } => {
+                self.u16(*preference);
+                self.name(exchange, true);
+            }
+            RData::Txt(strings) => {
+                for s in strings {
+                    let s = &s[..s.len().min(255)];
+                    self.buf.push(s.len() as u8);
+                    self.buf.extend_from_slice(s);
+                }
+            }
+            RData::Srv { priority, weight, port, target } => {
+                self.u16(*priority);
+                self.u16(*weight);
+                self.u16(*port);
+                self.name(target, false); // RFC 2782: no compression in SRV targets
+            }
+            RData::Soa { mname, rname, serial, refresh, retry, expire, minimum } => {
+                self.name(mname, true);
+                self.name(rname, true);
+                for v in [serial, refresh, retry, expire, minimum] {
+                    self.u32(*v);
+                }
+            }
+            RData::Raw(bytes) => self.buf.extend_from_slice(bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut query = Message::query(0x1234, "www.example.com", RecordType::Aaaa);
+        query.edns = Some(Edns { udp_size: 4096, version: 0, dnssec_ok: true, options: vec![(10, vec![1, 2, 3, 4, 5, 6, 7, 8])] });
+        let parsed = Message::parse(&query.to_bytes()).unwrap();
+        assert_eq!(parsed, query);
+
+        let mut response = Message::response_to(&parsed);
+        response.answers = vec![
+            ResourceRecord::new("www.example.com", RecordType::Cname, 300, RData::Cname("web.example.com".into())),
+            ResourceRecord::new("web.example.com", RecordType::Aaaa, 300, RData::Aaaa("2001:db8::1".parse().unwrap())),
+            ResourceRecord::new("example.com", RecordType::Mx, 300, RData::Mx { preference: 10, exchange: "mail.example.com".into() }),
+            ResourceRecord::new("_sip._udp.example.com", RecordType::Srv, 300, RData::from_text(RecordType::Srv, "0 5 5060 sip.example.com").unwrap()),
+            ResourceRecord::new("example.com", RecordType::Txt, 300, RData::from_text(RecordType::Txt, "\"v=spf1 -all\" \"second\"").unwrap()),
+        ];
+        response.rcode = 16; // BADVERS, needs the OPT extension bits
+        let bytes = response.to_bytes();
+        let parsed = Message::parse(&bytes).unwrap();
+        assert_eq!(parsed, response);
+        assert_eq!(parsed.answers[4].rdata.to_string(), "\"v=spf1 -all\" \"second\"");
+
+        // Compression: "example" is written once and every later name points back at it,
+        // except the SRV target, which must stay uncompressed
+        assert_eq!(bytes.windows(8).filter(|w| w == b"\x07example").count(), 2);
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert!(Message::parse(&[0x12]).is_err());
+        // Question whose name points at itself
+        let looped = [0, 1, 0x01, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xC0, 12, 0, 1, 0, 1];
+        assert!(Message::parse(&looped).is_err());
+        assert!(RData::from_text(RecordType::Mx, "ten mail.example.com").is_err());
+        assert_eq!(RecordType::from_name("type65"), Some(RecordType::Other(65)));
+    }
+
+    #[test]
+    fn test_udp_truncation() {
+        let query = Message::query(9, "big.example.com", RecordType::Txt);
+        assert_eq!(query.max_udp_size(), MIN_UDP_SIZE);
+        let mut response = Message::response_to(&query);
+        for i in 0..20 {
+            response.answers.push(ResourceRecord::new("big.example.com", RecordType::Txt, 300, RData::Txt(vec![vec![b'a' + i; 40]])));
+        }
+        assert!(response.to_bytes().len() > MIN_UDP_SIZE);
+
+        let truncated = Message::parse(&response.to_udp_bytes(MIN_UDP_SIZE)).unwrap();
+        assert!(truncated.truncated);
+        assert!(truncated.answers.is_empty());
+        assert_eq!(truncated.questions, query.questions);
+
+        let full = Message::parse(&response.to_udp_bytes(4096)).unwrap();
+        assert!(!full.truncated);
+        assert_eq!(full.answers.len(), 20);
+    }
+}
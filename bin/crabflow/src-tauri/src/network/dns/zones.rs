@@ -0,0 +1,684 @@
+// src-tauri/src/network/dns/zones.rs
+// Authoritative local zones: SOA/NS, wildcards, automatic reverse zones and RFC 1035 zone files
+
+use serde::{Serialize, Deserialize};
+use crate::sysmodules::{fetch, post, logging, paths};
+use super::{DnsRecord, RECORDS_CACHE, same_name};
+use super::wire::{RData, RecordType, ResourceRecord};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, RwLock};
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref ZONES_CACHE: RwLock<Arc<Vec<DnsZone>>> = RwLock::new(Arc::new(Vec::new()));
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DnsZone {
+    pub name: String, // Apex, e.g. "home.lan" or "1.168.192.in-addr.arpa"
+    #[serde(default)]
+    pub primary_ns: String, // SOA MNAME; empty = ns.<zone>
+    #[serde(default)]
+    pub admin_email: String, // SOA RNAME, "hostmaster@home.lan" or "hostmaster.home.lan"; empty = hostmaster.<zone>
+    #[serde(default)]
+    pub serial: u32, // Bumped on every change to the zone or its records
+    #[serde(default = "default_refresh")]
+    pub refresh: u32,
+    #[serde(default = "default_retry")]
+    pub retry: u32,
+    #[serde(default = "default_expire")]
+    pub expire: u32,
+    #[serde(default = "default_minimum")]
+    pub minimum: u32, // Negative-caching TTL (RFC 2308)
+    #[serde(default = "default_ttl")]
+    pub ttl: u32, // TTL of the SOA and NS records
+    #[serde(default)]
+    pub nameservers: Vec<String>,
+    #[serde(default = "default_auto_reverse")]
+    pub auto_reverse: bool, // Answer PTR queries for this zone's A/AAAA records
+}
+
+fn default_refresh() -> u32 { 3600 }
+fn default_retry() -> u32 { 600 }
+fn default_expire() -> u32 { 86400 }
+fn default_minimum() -> u32 { 300 }
+fn default_ttl() -> u32 { 3600 }
+fn default_auto_reverse() -> bool { true }
+
+impl DnsZone {
+    pub fn new(name: &str) -> Self {
+        DnsZone {
+            name: normalize(name),
+            primary_ns: String::new(),
+            admin_email: String::new(),
+            serial: next_serial(0),
+            refresh: default_refresh(),
+            retry: default_retry(),
+            expire: default_expire(),
+            minimum: default_minimum(),
+            ttl: default_ttl(),
+            nameservers: Vec::new(),
+            auto_reverse: default_auto_reverse(),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        in_zone(name, &self.name)
+    }
+
+    fn mname(&self) -> String {
+        if self.primary_ns.is_empty() { format!("ns.{}", self.name) } else { normalize(&self.primary_ns) }
+    }
+
+    fn rname(&self) -> String {
+        if self.admin_email.is_empty() {
+            return format!("hostmaster.{}", self.name);
+        }
+        // The local part's dots would read as label separators, so RFC 1035 escapes them; we just replace '@'
+        normalize(&self.admin_email.replacen('@', ".", 1))
+    }
+
+    pub fn soa_record(&self) -> ResourceRecord {
+        ResourceRecord::new(&self.name, RecordType::Soa, self.ttl, RData::Soa {
+            mname: self.mname(),
+            rname: self.rname(),
+            serial: self.serial,
+            refresh: self.refresh,
+            retry: self.retry,
+            expire: self.expire,
+            minimum: self.minimum,
+        })
+    }
+
+    /// SOA for the authority section of a negative answer, with the RFC 2308 TTL
+    pub fn negative_soa(&self) -> ResourceRecord {
+        let mut soa = self.soa_record();
+        soa.ttl = self.ttl.min(self.minimum);
+        soa
+    }
+
+    /// SOA and NS records synthesized at the apex
+    pub fn apex_records(&self, owner: &str) -> Vec<ResourceRecord> {
+        if !same_name(owner, &self.name) {
+            return Vec::new();
+        }
+        let mut out = vec![self.soa_record()];
+        let nameservers = if self.nameservers.is_empty() { vec![self.mname()] } else { self.nameservers.clone() };
+        out.extend(nameservers.iter().map(|ns| ResourceRecord::new(owner, RecordType::Ns, self.ttl, RData::Ns(normalize(ns)))));
+        for rr in out.iter_mut() {
+            rr.name = owner.to_string();
+        }
+        out
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.trim().trim_end_matches('.').to_lowercase()
+}
+
+pub fn in_zone(name: &str, zone: &str) -> bool {
+    let (name, zone) = (normalize(name), normalize(zone));
+    name == zone || name.ends_with(&format!(".{}", zone))
+}
+
+/// Serials follow the YYYYMMDDnn convention, and always move forward
+fn next_serial(current: u32) -> u32 {
+    let today: u32 = chrono::Utc::now().format("%Y%m%d").to_string().parse().unwrap_or(0);
+    (today.saturating_mul(100)).max(current.saturating_add(1))
+}
+
+fn get_zones_file() -> String {
+    paths::get_config_path("zones.json").to_string_lossy().to_string()
+}
+
+pub fn load_zones() {
+    if let Ok(data) = fetch::read_file(&get_zones_file()) {
+        let zones: Vec<DnsZone> = serde_json::from_str(&data).unwrap_or_default();
+        *ZONES_CACHE.write().unwrap() = Arc::new(zones);
+    }
+}
+
+fn save_zones(zones: Vec<DnsZone>) -> Result<(), String> {
+    let serialized = serde_json::to_string_pretty(&zones).map_err(|e| e.to_string())?;
+    post::write_file(&get_zones_file(), &serialized)?;
+    *ZONES_CACHE.write().unwrap() = Arc::new(zones);
+    Ok(())
+}
+
+pub fn zones() -> Vec<DnsZone> {
+    ZONES_CACHE.read().unwrap().to_vec()
+}
+
+/// The current zones without copying them, for the query path
+pub fn shared() -> Arc<Vec<DnsZone>> {
+    Arc::clone(&ZONES_CACHE.read().unwrap())
+}
+
+/// Bump the serial of the zone holding `name` after one of its records changed
+pub fn bump_serial_for(name: &str) {
+    let mut zones = zones();
+    if let Some(zone) = zones.iter_mut().filter(|z| z.contains(name)).max_by_key(|z| z.name.len()) {
+        zone.serial = next_serial(zone.serial);
+        if let Err(e) = save_zones(zones) {
+            logging::log_error(&format!("Failed to save DNS zones: {}", e));
+        }
+    }
+}
+
+// --- Reverse zones ---
+
+/// "4.3.2.1.in-addr.arpa" / nibble-form "ip6.arpa" -> address
+pub fn parse_reverse_name(name: &str) -> Option<IpAddr> {
+    let name = normalize(name);
+    if let Some(rest) = name.strip_suffix(".in-addr.arpa") {
+        let octets: Vec<u8> = rest.split('.').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+        return match octets.as_slice() {
+            [d, c, b, a] => Some(IpAddr::V4(Ipv4Addr::new(*a, *b, *c, *d))),
+            _ => None,
+        };
+    }
+    let rest = name.strip_suffix(".ip6.arpa")?;
+    let nibbles: Vec<u8> = rest.split('.').map(|n| u8::from_str_radix(n, 16).ok().filter(|_| n.len() == 1)).collect::<Option<_>>()?;
+    if nibbles.len() != 32 {
+        return None;
+    }
+    let mut bytes = [0u8; 16];
+    for (i, pair) in nibbles.rchunks(2).enumerate() {
+        bytes[i] = pair[1] << 4 | pair[0];
+    }
+    Some(IpAddr::V6(Ipv6Addr::from(bytes)))
+}
+
+/// The /24 (IPv4) or /64 (IPv6) reverse zone an address falls in
+fn reverse_zone_for(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            format!("{}.{}.{}.in-addr.arpa", o[2], o[1], o[0])
+        }
+        IpAddr::V6(v6) => {
+            let nibbles: Vec<String> = v6.octets()[..8].iter().rev().flat_map(|b| [b & 0x0F, b >> 4]).map(|n| format!("{:x}", n)).collect();
+            format!("{}.ip6.arpa", nibbles.join("."))
+        }
+    }
+}
+
+fn address_of(record: &DnsRecord) -> Option<IpAddr> {
+    match record.rtype.to_uppercase().as_str() {
+        "A" | "AAAA" => record.value.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// Zone that is authoritative for `name`: the most specific configured zone, or the
+/// reverse zone implied by a forward zone's A/AAAA records when `auto_reverse` is on
+pub fn find_zone(zones: &[DnsZone], records: &[DnsRecord], name: &str) -> Option<DnsZone> {
+    let configured = zones.iter().filter(|z| z.contains(name)).max_by_key(|z| z.name.len()).cloned();
+    if configured.is_some() || !(name.ends_with(".arpa") || name.ends_with(".arpa.")) {
+        return configured;
+    }
+    records.iter()
+        .filter(|r| !r.name.starts_with('*'))
+        .filter_map(|r| Some((address_of(r)?, r)))
+        .filter(|(ip, _)| in_zone(name, &reverse_zone_for(ip)))
+        .find_map(|(ip, r)| {
+            let parent = zones.iter().filter(|z| z.auto_reverse && z.contains(&r.name)).max_by_key(|z| z.name.len())?;
+            Some(DnsZone { name: reverse_zone_for(&ip), ..parent.clone() })
+        })
+}
+
+/// PTRs for `name` built from A/AAAA records in zones with `auto_reverse`
+pub fn synthesize_ptr(zones: &[DnsZone], records: &[DnsRecord], name: &str) -> Vec<ResourceRecord> {
+    let ip = match parse_reverse_name(name) {
+        Some(ip) => ip,
+        None => return Vec::new(),
+    };
+    records.iter()
+        .filter(|r| !r.name.starts_with('*') && address_of(r) == Some(ip))
+        .filter(|r| zones.iter().any(|z| z.auto_reverse && z.contains(&r.name)))
+        .map(|r| ResourceRecord::new(name, RecordType::Ptr, r.ttl, RData::Ptr(normalize(&r.name))))
+        .collect()
+}
+
+// --- Wildcards (RFC 4592) ---
+
+/// Whether `name` owns records or has records below it (an empty non-terminal)
+pub fn name_exists(records: &[DnsRecord], name: &str) -> bool {
+    let name = normalize(name);
+    let suffix = format!(".{}", name);
+    records.iter().any(|r| {
+        let owner = normalize(&r.name);
+        owner == name || owner.ends_with(&suffix)
+    })
+}
+
+/// Records from the wildcard at the closest encloser of `name`, rewritten to be owned by `name`
+pub fn wildcard_records(zone: &DnsZone, records: &[DnsRecord], name: &str) -> Vec<DnsRecord> {
+    let lowered = normalize(name);
+    let mut encloser = lowered.as_str();
+    while let Some((_, parent)) = encloser.split_once('.') {
+        encloser = parent;
+        if !in_zone(encloser, &zone.name) {
+            break;
+        }
+        if same_name(encloser, &zone.name) || name_exists(records, encloser) {
+            let source = format!("*.{}", encloser);
+            return records.iter()
+                .filter(|r| same_name(&r.name, &source))
+                .map(|r| DnsRecord { name: name.to_string(), ..r.clone() })
+                .collect();
+        }
+    }
+    Vec::new()
+}
+
+// --- Zone files (RFC 1035 master file format) ---
+
+/// A zone plus the records parsed from its zone file
+#[derive(Debug)]
+pub struct ZoneFile {
+    pub zone: DnsZone,
+    pub records: Vec<DnsRecord>,
+}
+
+/// "3600", "1h", "1h30m", "2w" -> seconds
+fn parse_ttl(token: &str) -> Option<u32> {
+    if let Ok(v) = token.parse() {
+        return Some(v);
+    }
+    let mut total: u32 = 0;
+    let mut digits = String::new();
+    for c in token.to_lowercase().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c { 's' => 1, 'm' => 60, 'h' => 3600, 'd' => 86400, 'w' => 604800, _ => return None };
+        total = total.checked_add(digits.parse::<u32>().ok()?.checked_mul(unit)?)?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return None;
+    }
+    Some(total)
+}
+
+/// Split a zone file into logical lines of tokens, joining parenthesized continuations.
+/// Each line records its starting line number and whether it began with whitespace (inherit the owner).
+fn zone_lines(data: &str) -> Vec<(usize, bool, Vec<String>)> {
+    let mut out = Vec::new();
+    let mut current: Option<(usize, bool, Vec<String>)> = None;
+    let mut depth = 0;
+    for (n, line) in data.lines().enumerate() {
+        let entry = current.get_or_insert_with(|| (n + 1, line.starts_with([' ', '\t']), Vec::new()));
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                ';' => break,
+                '(' => depth += 1,
+                ')' => depth = (depth - 1).max(0),
+                '"' => {
+                    let mut s = String::from('"');
+                    while let Some(c) = chars.next() {
+                        s.push(c);
+                        if c == '\\' {
+                            if let Some(n) = chars.next() { s.push(n); }
+                        } else if c == '"' {
+                            break;
+                        }
+                    }
+                    entry.2.push(s);
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    let mut s = c.to_string();
+                    while let Some(n) = chars.next_if(|n| !n.is_whitespace() && !"();\"".contains(*n)) {
+                        s.push(n);
+                    }
+                    entry.2.push(s);
+                }
+            }
+        }
+        if depth == 0 {
+            if let Some(entry) = current.take() {
+                if !entry.2.is_empty() {
+                    out.push(entry);
+                }
+            }
+        }
+    }
+    out.extend(current.filter(|e| !e.2.is_empty()));
+    out
+}
+
+fn qualify(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_string()
+    } else if let Some(abs) = name.strip_suffix('.') {
+        abs.to_lowercase()
+    } else if origin.is_empty() {
+        name.to_lowercase()
+    } else {
+        format!("{}.{}", name, origin).to_lowercase()
+    }
+}
+
+/// Parse a BIND-style zone file. `default_origin` is used when the file has no $ORIGIN or SOA.
+pub fn parse_zone_file(data: &str, default_origin: &str) -> Result<ZoneFile, String> {
+    let mut origin = normalize(default_origin);
+    let mut default_ttl = default_ttl();
+    let mut last_owner = origin.clone();
+    let mut zone: Option<DnsZone> = None;
+    let mut nameservers = Vec::new();
+    let mut records = Vec::new();
+    let mut errors = Vec::new();
+
+    for (line_no, inherit_owner, tokens) in zone_lines(data) {
+        let fail = |msg: String| format!("line {}: {}", line_no, msg);
+        match tokens[0].to_uppercase().as_str() {
+            "$ORIGIN" => {
+                match tokens.get(1) {
+                    Some(o) => origin = qualify(o, &origin),
+                    None => errors.push(fail("$ORIGIN needs a name".into())),
+                }
+                continue;
+            }
+            "$TTL" => {
+                match tokens.get(1).and_then(|t| parse_ttl(t)) {
+                    Some(t) => default_ttl = t,
+                    None => errors.push(fail("$TTL needs a number".into())),
+                }
+                continue;
+            }
+            "$INCLUDE" | "$GENERATE" => {
+                errors.push(fail(format!("{} is not supported", tokens[0])));
+                continue;
+            }
+            _ => {}
+        }
+
+        let mut rest = tokens.as_slice();
+        let owner = if inherit_owner {
+            last_owner.clone()
+        } else {
+            let owner = qualify(&rest[0], &origin);
+            rest = &rest[1..];
+            owner
+        };
+        last_owner = owner.clone();
+
+        // Optional TTL and class, in either order
+        let mut ttl = default_ttl;
+        for _ in 0..2 {
+            match rest.first() {
+                Some(t) if t.eq_ignore_ascii_case("IN") => rest = &rest[1..],
+                Some(t) if t.eq_ignore_ascii_case("CH") || t.eq_ignore_ascii_case("HS") => {
+                    errors.push(fail(format!("class {} is not supported", t)));
+                    rest = &[];
+                }
+                Some(t) if t.starts_with(|c: char| c.is_ascii_digit()) && parse_ttl(t).is_some() => {
+                    ttl = parse_ttl(t).unwrap_or(default_ttl);
+                    rest = &rest[1..];
+                }
+                _ => break,
+            }
+        }
+        let (rtype, rdata) = match rest.split_first() {
+            Some((t, rdata)) => match RecordType::from_name(t) {
+                Some(rtype) => (rtype, rdata),
+                None => { errors.push(fail(format!("unknown record type {}", t))); continue; }
+            },
+            None => {
+                if !errors.last().is_some_and(|e| e.starts_with(&format!("line {}:", line_no))) {
+                    errors.push(fail("missing record type".into()));
+                }
+                continue;
+            }
+        };
+
+        // Names inside rdata are relative to $ORIGIN too
+        let mut fields: Vec<String> = rdata.to_vec();
+        let name_fields: &[usize] = match rtype {
+            RecordType::Cname | RecordType::Ns | RecordType::Ptr => &[0],
+            RecordType::Mx => &[1],
+            RecordType::Srv => &[3],
+            RecordType::Soa => &[0, 1],
+            _ => &[],
+        };
+        for &i in name_fields {
+            if let Some(f) = fields.get_mut(i) {
+                *f = qualify(f, &origin);
+            }
+        }
+        if rtype == RecordType::Soa {
+            for f in fields.iter_mut().skip(2) {
+                if let Some(v) = parse_ttl(f) { *f = v.to_string(); }
+            }
+        }
+        let value = fields.join(" ");
+        let parsed = match RData::from_text(rtype, &value) {
+            Ok(r) => r,
+            Err(e) => { errors.push(fail(e)); continue; }
+        };
+
+        match parsed {
+            RData::Soa { mname, rname, serial, refresh, retry, expire, minimum } => {
+                if zone.is_some() {
+                    errors.push(fail("more than one SOA record".into()));
+                    continue;
+                }
+                origin = if origin.is_empty() { owner.clone() } else { origin };
+                zone = Some(DnsZone {
+                    name: owner.clone(),
+                    primary_ns: mname,
+                    admin_email: rname,
+                    serial,
+                    refresh,
+                    retry,
+                    expire,
+                    minimum,
+                    ttl,
+                    nameservers: Vec::new(),
+                    auto_reverse: default_auto_reverse(),
+                });
+            }
+            RData::Ns(ns) if zone.as_ref().map_or(owner == origin, |z| z.name == owner) => nameservers.push(ns),
+            _ => records.push(DnsRecord { name: owner, rtype: rtype.to_string(), value, ttl }),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors.join("; "));
+    }
+    let mut zone = match zone {
+        Some(z) => z,
+        None if !origin.is_empty() => DnsZone::new(&origin),
+        None => return Err("zone file has no SOA or $ORIGIN; give the zone name".into()),
+    };
+    zone.nameservers = nameservers;
+    if let Some(outside) = records.iter().find(|r| !zone.contains(&r.name)) {
+        return Err(format!("{} is outside zone {}", outside.name, zone.name));
+    }
+    Ok(ZoneFile { zone, records })
+}
+
+/// Zone file rdata: like presentation format, but with fully qualified (dot-terminated) names
+fn zone_file_rdata(rdata: &RData) -> String {
+    match rdata {
+        RData::Cname(n) | RData::Ns(n) | RData::Ptr(n) => format!("{}.", n),
+        RData::Mx { preference, exchange } => format!("{} {}.", preference, exchange),
+        RData::Srv { priority, weight, port, target } => format!("{} {} {} {}.", priority, weight, port, target),
+        RData::Soa { mname, rname, serial, refresh, retry, expire, minimum } =>
+            format!("{}. {}. ( {} {} {} {} {} )", mname, rname, serial, refresh, retry, expire, minimum),
+        other => other.to_string(),
+    }
+}
+
+/// Render a zone and its records in BIND zone file format. Records that belong to a more specific zone are left out.
+pub fn write_zone_file(zone: &DnsZone, zones: &[DnsZone], records: &[DnsRecord]) -> String {
+    let relative = |name: &str| {
+        let name = normalize(name);
+        if name == zone.name { "@".to_string() } else { name.strip_suffix(&format!(".{}", zone.name)).unwrap_or(&name).to_string() }
+    };
+    let mut out = format!("$ORIGIN {}.\n$TTL {}\n", zone.name, zone.ttl);
+    for rr in zone.apex_records(&zone.name) {
+        out.push_str(&format!("@\t{}\tIN\t{}\t{}\n", rr.ttl, rr.rtype, zone_file_rdata(&rr.rdata)));
+    }
+    for r in records {
+        let owner_zone = zones.iter().filter(|z| z.contains(&r.name)).max_by_key(|z| z.name.len());
+        if owner_zone.is_none_or(|z| z.name != zone.name) {
+            continue;
+        }
+        match r.to_resource_record(&r.name) {
+            Ok(rr) => out.push_str(&format!("{}\t{}\tIN\t{}\t{}\n", relative(&r.name), r.ttl, rr.rtype, zone_file_rdata(&rr.rdata))),
+            Err(e) => out.push_str(&format!("; skipped {} {}: {}\n", r.name, r.rtype, e)),
+        }
+    }
+    out
+}
+
+// --- Commands ---
+
+#[tauri::command]
+pub fn list_zones() -> Vec<DnsZone> {
+    zones()
+}
+
+/// Add a zone, or replace the one with the same name
+#[tauri::command]
+pub fn save_zone(mut zone: DnsZone) -> Result<(), String> {
+    zone.name = normalize(&zone.name);
+    if zone.name.is_empty() {
+        return Err("Zone name is required".into());
+    }
+    let mut all = zones();
+    let previous = all.iter().position(|z| z.name == zone.name);
+    zone.serial = next_serial(previous.map_or(zone.serial, |i| all[i].serial.max(zone.serial)));
+    match previous {
+        Some(i) => all[i] = zone.clone(),
+        None => all.push(zone.clone()),
+    }
+    save_zones(all)?;
+    logging::log_event("system".into(), "save_zone".into(), zone.name);
+    Ok(())
+}
+
+/// Remove a zone. Its records stay unless `remove_records` is set.
+#[tauri::command]
+pub fn remove_zone(name: String, remove_records: bool) -> Result<(), String> {
+    let name = normalize(&name);
+    let mut all = zones();
+    let before = all.len();
+    all.retain(|z| z.name != name);
+    if all.len() == before {
+        return Err(format!("Zone {} not found", name));
+    }
+    if remove_records {
+        let mut records = super::list_records();
+        records.retain(|r| !in_zone(&r.name, &name) || all.iter().any(|z| z.name.len() > name.len() && z.contains(&r.name)));
+        super::replace_records(records)?;
+    }
+    save_zones(all)?;
+    logging::log_event("system".into(), "remove_zone".into(), name);
+    Ok(())
+}
+
+/// Import a zone file. Existing records in the zone are replaced when `replace` is set, otherwise merged.
+/// Returns the number of records imported.
+#[tauri::command]
+pub fn import_zone_file(data: String, origin: Option<String>, replace: bool) -> Result<usize, String> {
+    let parsed = parse_zone_file(&data, origin.as_deref().unwrap_or(""))?;
+    let zone_name = parsed.zone.name.clone();
+    let count = parsed.records.len();
+
+    let mut records = RECORDS_CACHE.read().unwrap().to_vec();
+    if replace {
+        let all = zones();
+        records.retain(|r| !in_zone(&r.name, &zone_name) || all.iter().any(|z| z.name.len() > zone_name.len() && z.contains(&r.name)));
+    }
+    for rec in parsed.records {
+        let duplicate = records.iter().any(|r| same_name(&r.name, &rec.name) && r.rtype.eq_ignore_ascii_case(&rec.rtype) && r.value == rec.value);
+        if !duplicate {
+            records.push(rec);
+        }
+    }
+    super::replace_records(records)?;
+    save_zone(parsed.zone)?;
+    logging::log_event("system".into(), "import_zone_file".into(), format!("{}: {} records", zone_name, count));
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn export_zone_file(name: String) -> Result<String, String> {
+    let all = zones();
+    let zone = all.iter().find(|z| z.name == normalize(&name)).ok_or_else(|| format!("Zone {} not found", name))?;
+    Ok(write_zone_file(zone, &all, &super::list_records()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZONE: &str = r#"
+$ORIGIN home.lan.
+$TTL 1h
+@       IN SOA  ns1 hostmaster (
+                2024010101 ; serial
+                3600 600 1w 300 )
+        IN NS   ns1
+ns1     IN A    10.0.0.1
+nas     300 IN A 10.0.0.5
+www     IN CNAME nas
+*.lab   IN A    10.0.0.9
+@       IN MX   10 mail.example.com.
+txt     IN TXT  "hello; world" "two"
+"#;
+
+    #[test]
+    fn test_parse_zone_file() {
+        let parsed = parse_zone_file(ZONE, "").unwrap();
+        assert_eq!(parsed.zone.name, "home.lan");
+        assert_eq!(parsed.zone.primary_ns, "ns1.home.lan");
+        assert_eq!(parsed.zone.expire, 604800);
+        assert_eq!(parsed.zone.nameservers, vec!["ns1.home.lan".to_string()]);
+        assert_eq!(parsed.records.len(), 6);
+        assert_eq!(parsed.records[1].ttl, 300);
+        assert_eq!(parsed.records[2].value, "nas.home.lan");
+        assert_eq!(parsed.records[3].name, "*.lab.home.lan");
+        assert_eq!(parsed.records[5].value, "\"hello; world\" \"two\"");
+
+        // Export and re-import gives the same zone
+        let zones = vec![parsed.zone.clone()];
+        let text = write_zone_file(&parsed.zone, &zones, &parsed.records);
+        let again = parse_zone_file(&text, "").unwrap();
+        assert_eq!(again.zone, parsed.zone);
+        assert_eq!(again.records, parsed.records);
+
+        assert!(parse_zone_file("www IN CNAME nas\n", "").is_err());
+        assert!(parse_zone_file("other.net. IN A 10.0.0.1\n", "home.lan").is_err());
+    }
+
+    #[test]
+    fn test_wildcards_and_reverse() {
+        let parsed = parse_zone_file(ZONE, "").unwrap();
+        let zones = vec![parsed.zone.clone()];
+        let records = parsed.records;
+
+        let wild = wildcard_records(&zones[0], &records, "db.lab.home.lan");
+        assert_eq!(wild.len(), 1);
+        assert_eq!(wild[0].name, "db.lab.home.lan");
+        assert!(wildcard_records(&zones[0], &records, "db.other.home.lan").is_empty());
+        // "lab.home.lan" exists as an empty non-terminal, so it is NODATA rather than NXDOMAIN
+        assert!(name_exists(&records, "lab.home.lan"));
+
+        let reverse = find_zone(&zones, &records, "5.0.0.10.in-addr.arpa").unwrap();
+        assert_eq!(reverse.name, "0.0.10.in-addr.arpa");
+        let ptr = synthesize_ptr(&zones, &records, "5.0.0.10.in-addr.arpa");
+        assert_eq!(ptr[0].rdata, RData::Ptr("nas.home.lan".into()));
+        assert!(find_zone(&zones, &records, "5.0.0.192.in-addr.arpa").is_none());
+
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let name = format!("1.{}.{}", "0.".repeat(15).trim_end_matches('.'), reverse_zone_for(&v6));
+        assert_eq!(parse_reverse_name(&name), Some(v6));
+    }
+}
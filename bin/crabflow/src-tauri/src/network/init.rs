@@ -1,7 +1,9 @@
+#[cfg(target_os = "windows")]
 use std::thread;
+#[cfg(target_os = "windows")]
 use std::time::Duration;
 use crate::sysmodules::{logging, post, fetch, config, paths};
-use crate::network::{dhcp, dns, cportal};
+use crate::network::{dhcp, dhcpv6, dns, cportal};
 use tauri::AppHandle;
 
 #[cfg(target_os = "windows")]
@@ -179,11 +181,8 @@ pub fn initialize_networking(app_handle: Option<AppHandle>) {
             logging::log_info(&format!("Setting DNS upstream interface to: {}", upstream));
             dns::set_upstream_interface(upstream);
 
-            // Apply Captive Portal rules if enabled
-            if cfg.dhcp.captive_portal {
-                logging::log_info("Applying Captive Portal rules on startup...");
-                cportal::apply_portal_rules(true, &cfg);
-            }
+            // Redirect LAN HTTP to the captive portal / block page if either is enabled
+            cportal::sync_http_redirect(&cfg);
 
             // If hotspot enabled, try to auto-assign gateway IP to hotspot interface
             if cfg.hotspot.enabled {
@@ -209,6 +208,9 @@ pub fn initialize_networking(app_handle: Option<AppHandle>) {
         }
     }
 
+    dhcp::reload_lease_table();
+    dhcpv6::reload_lease_table();
+
     // Initialize DNS
     let dns_str = dns_file.to_string_lossy().to_string();
     match fetch::read_file(&dns_str) {
@@ -223,6 +225,9 @@ pub fn initialize_networking(app_handle: Option<AppHandle>) {
 
     // Start DHCP Server in a background thread
     dhcp::start_dhcp_server(app_handle.clone());
+
+    // Start DHCPv6 and router advertisements if an IPv6 prefix is configured
+    dhcpv6::start_dhcpv6_server(app_handle.clone());
     
     // Start DNS Server in a background thread
     dns::start_dns_server(app_handle);
@@ -233,6 +238,7 @@ pub fn initialize_networking(app_handle: Option<AppHandle>) {
 pub fn shutdown_networking() {
     logging::log_info("Shutting down networking components...");
     dhcp::stop_dhcp_server();
+    dhcpv6::stop_dhcpv6_server();
     dns::stop_dns_server();
     logging::log_info("Networking shutdown complete.");
 }
@@ -2,6 +2,7 @@ pub mod acl;
 pub mod auth;
 pub mod dns;
 pub mod dhcp;
+pub mod dhcpv6;
 pub mod client;
 pub mod firewall;
 pub mod cportal;
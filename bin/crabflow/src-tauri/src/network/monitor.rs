@@ -153,10 +153,10 @@ pub fn start_performance_logging(filename: String, _state_sys: State<'static, Mu
 
         // Write header if empty
         if let Ok(metadata) = file.metadata() {
-            if metadata.len() == 0 {
-                if let Err(_) = writeln!(file, "timestamp,cpu_usage,memory_percentage,total_bytes_rx,total_bytes_tx") {
-                     logging::log_error("Failed to write CSV header");
-                }
+            if metadata.len() == 0
+                && writeln!(file, "timestamp,cpu_usage,memory_percentage,total_bytes_rx,total_bytes_tx").is_err()
+            {
+                logging::log_error("Failed to write CSV header");
             }
         }
 
@@ -220,6 +220,22 @@ pub fn check_connection_quality() -> bool {
     }
 }
 
+/// Send a single ICMP echo to `ip` and report whether it answered within `timeout_ms`
+pub fn ping_host(ip: &str, timeout_ms: u64) -> bool {
+    #[cfg(target_os = "windows")]
+    let args = ["-n".to_string(), "1".to_string(), "-w".to_string(), timeout_ms.to_string(), ip.to_string()];
+
+    // Unix ping takes whole seconds
+    #[cfg(not(target_os = "windows"))]
+    let args = ["-c".to_string(), "1".to_string(), "-W".to_string(), timeout_ms.div_ceil(1000).to_string(), ip.to_string()];
+
+    Command::new("ping")
+        .args(args)
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
 /// Get system status logic
 pub fn get_system_status_impl(sys: &mut System) -> SystemStatus {
     sys.refresh_cpu();
@@ -277,7 +293,7 @@ pub struct ArpEntry {
 }
 
 /// Platform-agnostic ARP listing implementation.
-pub fn list_arp_impl(do_ping: bool) -> Vec<ArpEntry> {
+pub fn list_arp_impl(_do_ping: bool) -> Vec<ArpEntry> {
     let mut results: Vec<ArpEntry> = vec![];
 
     #[cfg(target_os = "windows")]
@@ -39,16 +39,14 @@ pub fn create_hotspot(ssid: String, key: String) -> Result<(), String> {
 
 fn start_watchdog() {
     let pid = std::process::id();
-    let script = format!(
-        r#"
+    let script = r#"
         param($TargetPid)
-        while (Get-Process -Id $TargetPid -ErrorAction SilentlyContinue) {{
+        while (Get-Process -Id $TargetPid -ErrorAction SilentlyContinue) {
             Start-Sleep -Seconds 5
-        }}
+        }
         # Parent process ($TargetPid) is gone. Stopping hotspot.
         netsh wlan stop hostednetwork
-        "#,
-    );
+        "#;
 
     // Create a temporary script file
     let mut temp_dir = std::env::temp_dir();
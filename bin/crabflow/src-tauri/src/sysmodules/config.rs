@@ -1,7 +1,9 @@
 use std::{fs, path::PathBuf};
+use std::collections::BTreeMap;
 use serde::{Deserialize, Serialize};
 use serde_json;
 use crate::sysmodules::paths;
+use crate::network::dhcp::hooks::LeaseHook;
 
 pub fn get_project_root() -> PathBuf {
     paths::get_install_dir()
@@ -27,6 +29,80 @@ pub struct DhcpConfig {
     pub gateway: String,
     pub dns_servers: Vec<String>,
     pub lease_time: u64,
+    #[serde(default = "default_decline_quarantine")]
+    pub decline_quarantine_secs: u64, // How long a DHCPDECLINEd address stays out of the pool
+    #[serde(default)]
+    pub conflict_probe: ConflictProbe,
+    #[serde(default = "default_conflict_quarantine")]
+    pub conflict_quarantine_secs: u64, // How long an address found in use by a probe stays out of the pool
+    #[serde(default)]
+    pub options: BTreeMap<String, String>, // Extra options by name or code, e.g. "domain_name", "ntp_servers", "42"
+    #[serde(default)]
+    pub vendor_classes: Vec<VendorClassOptions>,
+    #[serde(default)]
+    pub hooks: Vec<LeaseHook>, // Scripts/webhooks fired on lease events
+}
+
+/// Options handed only to clients whose vendor class (option 60) starts with `vendor_class`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VendorClassOptions {
+    pub vendor_class: String, // e.g. "PXEClient", "MSFT 5.0"
+    pub options: BTreeMap<String, String>,
+}
+
+/// An additional DHCP scope served next to the main `dhcp` block, e.g. a guest hotspot
+/// alongside the staff VLAN. Scopes share the server-wide switches and lease table.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DhcpScope {
+    pub name: String,
+    pub interface: String, // Interface IP or name to serve on, same as bind_address
+    pub range_start: String,
+    pub range_end: String,
+    pub subnet_mask: String,
+    pub gateway: String,
+    pub dns_servers: Vec<String>,
+    pub lease_time: u64,
+    pub captive_portal: bool,
+    #[serde(default)]
+    pub options: BTreeMap<String, String>, // Merged over the main block's options
+}
+
+impl DhcpConfig {
+    /// The effective settings for `scope`: its network settings over the server-wide ones
+    pub fn for_scope(&self, scope: &DhcpScope) -> DhcpConfig {
+        let mut options = self.options.clone();
+        options.extend(scope.options.clone());
+        DhcpConfig {
+            captive_portal: scope.captive_portal,
+            bind_address: scope.interface.clone(),
+            range_start: scope.range_start.clone(),
+            range_end: scope.range_end.clone(),
+            subnet_mask: scope.subnet_mask.clone(),
+            gateway: scope.gateway.clone(),
+            dns_servers: scope.dns_servers.clone(),
+            lease_time: scope.lease_time,
+            options,
+            ..self.clone()
+        }
+    }
+}
+
+/// How the server checks that an address is unused before offering it
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictProbe {
+    #[default]
+    Off,
+    Ping, // ICMP echo to the address
+    Arp,  // Look the address up in the system ARP cache
+}
+
+fn default_decline_quarantine() -> u64 {
+    3600
+}
+
+fn default_conflict_quarantine() -> u64 {
+    1800
 }
 
 impl Default for DhcpConfig {
@@ -43,6 +119,50 @@ impl Default for DhcpConfig {
             gateway: "10.0.0.1".into(),
             dns_servers: vec!["8.8.8.8".into(), "8.8.4.4".into()],
             lease_time: 86400,
+            decline_quarantine_secs: default_decline_quarantine(),
+            conflict_probe: ConflictProbe::Off,
+            conflict_quarantine_secs: default_conflict_quarantine(),
+            options: BTreeMap::new(),
+            vendor_classes: vec![],
+            hooks: vec![],
+        }
+    }
+}
+
+/// IPv6 service: a /64 announced by router advertisements (SLAAC) plus a stateful DHCPv6 pool
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Dhcpv6Config {
+    pub enabled: bool,
+    pub interface: String, // Interface name; RAs and the DHCPv6 multicast group are per-link
+    pub prefix: String, // e.g. "fd00:c0ab:1::/64"
+    pub range_start: String,
+    pub range_end: String,
+    pub dns_servers: Vec<String>,
+    #[serde(default)]
+    pub domain_search: Vec<String>,
+    pub preferred_lifetime: u64,
+    pub valid_lifetime: u64,
+    #[serde(default = "default_decline_quarantine")]
+    pub decline_quarantine_secs: u64, // How long a DECLINEd address stays out of the pool
+    pub stateful: bool, // Set the RA "managed" flag so clients also take a DHCPv6 address
+    pub ra_interval_secs: u64,
+}
+
+impl Default for Dhcpv6Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interface: "Wi-Fi".into(),
+            prefix: "fd00:c0ab:1::/64".into(),
+            range_start: "fd00:c0ab:1::100".into(),
+            range_end: "fd00:c0ab:1::1ff".into(),
+            dns_servers: vec!["2001:4860:4860::8888".into(), "2001:4860:4860::8844".into()],
+            domain_search: vec![],
+            preferred_lifetime: 43200,
+            valid_lifetime: 86400,
+            decline_quarantine_secs: default_decline_quarantine(),
+            stateful: true,
+            ra_interval_secs: 200,
         }
     }
 }
@@ -69,12 +189,199 @@ impl Default for HotspotConfig {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DnsConfig {
     pub allow_non_dhcp_clients: bool,
+    #[serde(default)]
+    pub listen_addresses: Vec<String>, // Interface names or addresses to listen on; empty listens on every IPv4 interface (restart to apply)
+    #[serde(default)]
+    pub allowed_client_networks: Vec<String>, // CIDRs or addresses that may query; empty allows any. This machine is always allowed
+    #[serde(default)]
+    pub client_reject_action: ClientRejectAction,
+    #[serde(default = "default_true")]
+    pub register_dhcp_hostnames: bool, // Answer A/PTR queries for active leases by their option 12 hostname
+    #[serde(default = "default_local_domain")]
+    pub local_domain: String, // Suffix for lease hostnames, e.g. "lan" -> laptop-42.lan
+    #[serde(default = "default_true")]
+    pub cache_enabled: bool,
+    #[serde(default = "default_cache_size")]
+    pub cache_size: usize, // Max cached responses; least recently used are evicted
+    #[serde(default = "default_cache_max_ttl")]
+    pub cache_max_ttl: u32, // Upstream TTLs are capped to this
+    #[serde(default = "default_cache_negative_ttl")]
+    pub cache_negative_ttl: u32, // Cap for NXDOMAIN/NODATA answers (RFC 2308)
+    #[serde(default = "default_upstreams")]
+    pub upstreams: Vec<UpstreamServer>, // Fastest healthy one first; unhealthy ones only when all others fail
+    #[serde(default = "default_upstream_health_interval")]
+    pub upstream_health_interval_secs: u64,
+    #[serde(default)]
+    pub forward_zones: Vec<ForwardZone>, // Split DNS: the longest matching suffix picks the resolvers
+    #[serde(default)]
+    pub block_mode: BlockMode, // How blocked names are answered; subscriptions can override it
+    #[serde(default)]
+    pub dnssec_validation: bool, // Validate forwarded answers: AD on secure ones, SERVFAIL on bogus ones
+    #[serde(default)]
+    pub rebind_protection: bool, // Filter upstream answers pointing at private, loopback, link-local or CGNAT addresses
+    #[serde(default)]
+    pub rebind_action: RebindAction,
+    #[serde(default)]
+    pub rebind_allowlist: Vec<String>, // Domains, with their subdomains, that may resolve to internal addresses
+    #[serde(default = "default_rate_limit_per_ip")]
+    pub rate_limit_per_ip: u32, // Queries per second per client, with a burst of twice that; 0 is unlimited
+    #[serde(default)]
+    pub rate_limit_per_subnet: u32, // Per /24 (IPv4) or /56 (IPv6); 0 is unlimited
+    #[serde(default)]
+    pub rrl_responses_per_second: u32, // Identical UDP responses per second to one subnet (RRL); 0 is off
+    #[serde(default = "default_rate_limit_ban_threshold")]
+    pub rate_limit_ban_threshold: u32, // Throttled queries within a minute that get a client banned; 0 never bans
+    #[serde(default = "default_rate_limit_ban_secs")]
+    pub rate_limit_ban_secs: u64,
+    #[serde(default = "default_true")]
+    pub query_log_persist: bool, // Keep the query log in the database; otherwise only the latest entries in memory
+    #[serde(default = "default_query_log_retention")]
+    pub query_log_retention_days: u32,
+}
+
+/// Answer given for a blocked name
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockMode {
+    #[default]
+    NullIp,    // A 0.0.0.0 / AAAA ::
+    Nxdomain,  // The name doesn't exist
+    Refused,   // The server refuses to answer
+    BlockPage, // A points at the gateway, where the HTTP server explains the block (port 80 must reach it, as for the captive portal)
+}
+
+/// What rebinding protection does with an answer pointing at an internal address
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RebindAction {
+    #[default]
+    Strip,  // Drop the internal addresses and keep the rest of the answer
+    Refuse, // Answer REFUSED
+}
+
+/// What the server does with a query from outside the allowed client networks
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientRejectAction {
+    #[default]
+    Drop,   // Don't answer, so a scan can't tell a resolver is there
+    Refuse, // Answer REFUSED
+}
+
+/// Transport used to reach an upstream resolver
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpstreamProtocol {
+    #[default]
+    Udp,   // Falls back to TCP when the answer is truncated
+    Tcp,
+    Tls,   // DNS-over-TLS (RFC 7858), port 853
+    Https, // DNS-over-HTTPS (RFC 8484)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UpstreamServer {
+    pub address: String, // "1.1.1.1", "1.1.1.1:853", or for HTTPS a URL such as "https://1.1.1.1/dns-query"
+    #[serde(default)]
+    pub protocol: UpstreamProtocol,
+    #[serde(default)]
+    pub tls_name: String, // Name the TLS certificate must match; defaults to the address
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+/// Names under `suffix` go to these resolvers instead of the default upstreams
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ForwardZone {
+    pub suffix: String, // "corp.example.com", or a reverse zone such as "10.in-addr.arpa"
+    pub upstreams: Vec<UpstreamServer>,
+    #[serde(default)]
+    pub source_interface: String, // Local address or interface name to send from, e.g. the VPN's; empty uses the upstream interface
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+impl UpstreamServer {
+    pub fn new(address: &str, protocol: UpstreamProtocol) -> Self {
+        Self { address: address.to_string(), protocol, tls_name: String::new(), enabled: true }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_local_domain() -> String {
+    "lan".to_string()
+}
+
+fn default_cache_size() -> usize {
+    10000
+}
+
+fn default_cache_max_ttl() -> u32 {
+    86400
+}
+
+fn default_cache_negative_ttl() -> u32 {
+    3600
+}
+
+fn default_upstreams() -> Vec<UpstreamServer> {
+    vec![
+        UpstreamServer::new("1.1.1.1:53", UpstreamProtocol::Udp),
+        UpstreamServer::new("8.8.8.8:53", UpstreamProtocol::Udp),
+    ]
+}
+
+fn default_upstream_health_interval() -> u64 {
+    30
+}
+
+fn default_query_log_retention() -> u32 {
+    7
+}
+
+fn default_rate_limit_per_ip() -> u32 {
+    50
+}
+
+fn default_rate_limit_ban_threshold() -> u32 {
+    500
+}
+
+fn default_rate_limit_ban_secs() -> u64 {
+    300
 }
 
 impl Default for DnsConfig {
     fn default() -> Self {
         Self {
             allow_non_dhcp_clients: true,
+            listen_addresses: Vec::new(),
+            allowed_client_networks: Vec::new(),
+            client_reject_action: ClientRejectAction::default(),
+            register_dhcp_hostnames: true,
+            local_domain: default_local_domain(),
+            cache_enabled: true,
+            cache_size: default_cache_size(),
+            cache_max_ttl: default_cache_max_ttl(),
+            cache_negative_ttl: default_cache_negative_ttl(),
+            upstreams: default_upstreams(),
+            upstream_health_interval_secs: default_upstream_health_interval(),
+            forward_zones: Vec::new(),
+            block_mode: BlockMode::default(),
+            dnssec_validation: false,
+            rebind_protection: false,
+            rebind_action: RebindAction::default(),
+            rebind_allowlist: Vec::new(),
+            rate_limit_per_ip: default_rate_limit_per_ip(),
+            rate_limit_per_subnet: 0,
+            rrl_responses_per_second: 0,
+            rate_limit_ban_threshold: default_rate_limit_ban_threshold(),
+            rate_limit_ban_secs: default_rate_limit_ban_secs(),
+            query_log_persist: true,
+            query_log_retention_days: default_query_log_retention(),
         }
     }
 }
@@ -112,6 +419,10 @@ pub struct SetupConfig {
     #[serde(default)]
     pub dhcp: DhcpConfig,
     #[serde(default)]
+    pub dhcp_scopes: Vec<DhcpScope>,
+    #[serde(default)]
+    pub dhcpv6: Dhcpv6Config,
+    #[serde(default)]
     pub dns: DnsConfig,
     #[serde(default)]
     pub hotspot: HotspotConfig,
@@ -119,6 +430,15 @@ pub struct SetupConfig {
     pub advanced: AdvancedConfig,
 }
 
+impl SetupConfig {
+    /// Every scope the DHCP server serves; the main `dhcp` block always comes first
+    pub fn dhcp_scope_configs(&self) -> Vec<DhcpConfig> {
+        std::iter::once(self.dhcp.clone())
+            .chain(self.dhcp_scopes.iter().map(|scope| self.dhcp.for_scope(scope)))
+            .collect()
+    }
+}
+
 fn default_monitor_interval() -> u64 {
     5000
 }
@@ -169,6 +489,8 @@ pub fn load_setup_config() -> Result<SetupConfig, String> {
                 custom_captive_portal: false,
                 ..DhcpConfig::default()
             },
+            dhcp_scopes: vec![],
+            dhcpv6: Dhcpv6Config::default(),
             dns: DnsConfig::default(),
             hotspot: HotspotConfig::default(),
             advanced: AdvancedConfig::default(),
@@ -208,6 +530,8 @@ pub fn reset_setup_config() -> Result<(), String> {
             custom_captive_portal: false,
             ..DhcpConfig::default()
         },
+        dhcp_scopes: vec![],
+        dhcpv6: Dhcpv6Config::default(),
         dns: DnsConfig::default(),
         hotspot: HotspotConfig::default(),
         advanced: AdvancedConfig::default(),
@@ -2,6 +2,7 @@
 
 use std::path::PathBuf;
 use std::fs;
+use std::io::Write;
 use crate::sysmodules::config::SetupConfig;
 use crate::sysmodules::paths;
 
@@ -9,17 +10,20 @@ fn config_path() -> PathBuf {
     paths::get_config_path("crabflow_config.json")
 }
 
-/// Write a file safely to the appropriate directory
-pub fn write_file(filename: &str, data: &str) -> Result<(), String> {
-    // Decide where to write based on filename
-    // leases.json, system_stats.json -> db
-    // others -> config
-    
-    let path = if filename == "leases.json" || filename == "system_stats.json" {
+/// Decide where to write based on filename
+/// leases.json, system_stats.json -> db
+/// others -> config
+fn resolve_path(filename: &str) -> PathBuf {
+    if filename == "leases.json" || filename == "system_stats.json" {
         paths::get_db_path(filename)
     } else {
         paths::get_config_path(filename)
-    };
+    }
+}
+
+/// Write a file safely to the appropriate directory
+pub fn write_file(filename: &str, data: &str) -> Result<(), String> {
+    let path = resolve_path(filename);
 
     // Ensure directory exists
     if let Some(parent) = path.parent() {
@@ -32,11 +36,7 @@ pub fn write_file(filename: &str, data: &str) -> Result<(), String> {
 /// Append data to a file safely
 #[allow(dead_code)]
 pub fn append_file(filename: &str, data: &str) -> Result<(), String> {
-    let path = if filename == "leases.json" || filename == "system_stats.json" {
-        paths::get_db_path(filename)
-    } else {
-        paths::get_config_path(filename)
-    };
+    let path = resolve_path(filename);
 
     // Ensure directory exists
     if let Some(parent) = path.parent() {
@@ -49,6 +49,28 @@ pub fn append_file(filename: &str, data: &str) -> Result<(), String> {
     fs::write(&path, content).map_err(|e| format!("Failed to append file: {}", e))
 }
 
+/// Write a file atomically: the data goes to a temporary file next to the target,
+/// which is then renamed over it, so a crash never leaves a truncated file behind.
+pub fn write_file_atomic(filename: &str, data: &str) -> Result<(), String> {
+    let path = resolve_path(filename);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    let mut tmp_name = path.clone().into_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    {
+        let mut file = fs::File::create(&tmp_path).map_err(|e| format!("Failed to create temp file: {}", e))?;
+        file.write_all(data.as_bytes()).map_err(|e| format!("Failed to write temp file: {}", e))?;
+        file.sync_all().map_err(|e| format!("Failed to sync temp file: {}", e))?;
+    }
+
+    fs::rename(&tmp_path, &path).map_err(|e| format!("Failed to replace file: {}", e))
+}
+
 #[tauri::command]
 pub fn post_setup(config: SetupConfig) -> Result<(), String> {
     let path = config_path();
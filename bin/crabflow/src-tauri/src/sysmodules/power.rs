@@ -14,7 +14,7 @@ pub fn shutdown_system() -> Result<(), String> {
 
     #[cfg(not(target_os = "windows"))]
     let status = Command::new("shutdown")
-        .args(&["-h", "now"])
+        .args(["-h", "now"])
         .status();
 
     match status {
@@ -10,6 +10,12 @@ pub struct SessionStore {
     pub sessions: Arc<Mutex<HashMap<String, User>>>, // Token -> User
 }
 
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl SessionStore {
     pub fn new() -> Self {
         Self {
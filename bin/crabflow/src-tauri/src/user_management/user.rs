@@ -55,6 +55,12 @@ pub struct UserStore {
     pub db: Arc<Mutex<UserDatabase>>,
 }
 
+impl Default for UserStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UserStore {
     pub fn new() -> Self {
         // Initialize with default or empty, real data loaded via load_from_db
@@ -72,26 +78,26 @@ impl UserStore {
         // Load Settings (Single Record)
         let settings: Option<UserSettings> = db.select(("settings", "main")).await.map_err(|e| e.to_string())?;
 
-        let mut data = self.db.lock().map_err(|e| e.to_string())?;
-        
-        if !users.is_empty() {
-             data.users = users.clone();
-        } else {
-            // Ensure default admin exists if DB is empty? 
-            // Default impl of UserDatabase has admin. If DB is empty, we keep default and Save it?
-            // Yes, let's allow saving defaults to DB if empty.
-        }
+        {
+            let mut data = self.db.lock().map_err(|e| e.to_string())?;
 
-        if !groups.is_empty() {
-            data.groups = groups.clone();
-        }
+            if !users.is_empty() {
+                 data.users = users.clone();
+            } else {
+                // Ensure default admin exists if DB is empty? 
+                // Default impl of UserDatabase has admin. If DB is empty, we keep default and Save it?
+                // Yes, let's allow saving defaults to DB if empty.
+            }
 
-        if let Some(s) = settings {
-            data.settings = s;
+            if !groups.is_empty() {
+                data.groups = groups.clone();
+            }
+
+            if let Some(s) = settings {
+                data.settings = s;
+            }
         }
         
-        drop(data);
-        
         // If we loaded empty but had defaults, persist them back to ensure DB is initialized
         if users.is_empty() && groups.is_empty() {
              self.persist().await?;
@@ -421,9 +427,9 @@ pub fn sort_users_by(store: State<UserStore>, field: String, ascending: bool) ->
         },
         "group" => {
             if ascending {
-                db.users.sort_by(|a, b| a.groups.join(",").cmp(&b.groups.join(",")));
+                db.users.sort_by_key(|a| a.groups.join(","));
             } else {
-                db.users.sort_by(|a, b| b.groups.join(",").cmp(&a.groups.join(",")));
+                db.users.sort_by_key(|a| std::cmp::Reverse(a.groups.join(",")));
             }
         },
         _ => return Err("Invalid sort field".to_string()),